@@ -197,8 +197,17 @@ const initialize = async () => {
   const anchor_provider = await getAnchorProvider();
   const program = new anchor.Program(tokenlockIdl, programId, anchor_provider);
 
+  // fees are paid in the fee token and collected by the fee config's creator
+  const mintSER = new web3.PublicKey(document.getElementById('mint_ser').value);
+  const pdaData = await utils.getPdaAccountData(program, mintSER);
+  if(pdaData == null)
+  {
+    alert('pda is not initialized');
+    return;
+  }
+  const feeCollectTokenAccount = await utils.getAssociatedTokenAddress(mintSER, pdaData.initializerKey, false);
   const res = await lib.initialize(program, connection,
-    initializer_amount, taker_amount, mintA, mintB, provider);
+    initializer_amount, taker_amount, mintA, mintB, mintSER, feeCollectTokenAccount, 0, 0, provider);
 
   if(res[0] == null)
   {
@@ -217,7 +226,8 @@ const cancel = async () => {
   const anchor_provider = await getAnchorProvider();
   const program = new anchor.Program(tokenlockIdl, programId, anchor_provider);
 
-  const res = await lib.cancel(program, connection,  mintA, mintB, provider);
+  const mintSER = new web3.PublicKey(document.getElementById('mint_ser').value);
+  const res = await lib.cancel(program, connection,  mintA, mintB, mintSER, provider);
 
   if(res[0] == null)
   {
@@ -239,7 +249,8 @@ const exchange = async () => {
   const anchor_provider = await getAnchorProvider();
   const program = new anchor.Program(tokenlockIdl, programId, anchor_provider);
 
-  const res = await lib.exchange(program, connection, initializer, mintA, mintB, provider);
+  const mintSER = new web3.PublicKey(document.getElementById('mint_ser').value);
+  const res = await lib.exchange(program, connection, initializer, mintA, mintB, mintSER, provider);
 
   if(res[0] == null)
   {
@@ -1,12 +1,38 @@
 const anchor = require('@project-serum/anchor');
 const borsh = require('borsh');
-const { TOKEN_PROGRAM_ID } = require('@solana/spl-token');
+const { TOKEN_PROGRAM_ID, ASSOCIATED_TOKEN_PROGRAM_ID } = require('@solana/spl-token');
 var sha256 = require('js-sha256');
 const { v4: uuidv4 } = require('uuid');
 const BIPS_PRECISION = 10000;
 const utils = require("../../lib/utils");
 const { programId } = require('../config');
 const PDA_SEED  = "ser-escrow";
+const ESCROW_PDA_SEED = "escrow";
+const OPEN_ORDERS_SEED = "open-orders";
+const OPEN_ORDERS_CAPACITY = 32;
+const MARKET_SEED = "market";
+const CLAIM_SEED = "claim";
+const USER_STATS_SEED = "user-stats";
+const STAKE_SEED = "stake";
+const REFERRER_SEED = "referrer";
+const PRICE_HISTORY_SEED = "price-history";
+
+async function findAddress(program, seeds) {
+    const [address] = await anchor.web3.PublicKey.findProgramAddress(seeds, program.programId);
+    return address;
+}
+
+// mints in byte order, as pair accounts are keyed
+function pairSeeds(mintA, mintB, seed) {
+    const [lo, hi] = Buffer.compare(mintA.toBuffer(), mintB.toBuffer()) < 0 ? [mintA, mintB] : [mintB, mintA];
+    return [lo.toBuffer(), hi.toBuffer(), Buffer.from(seed)];
+}
+
+// the fee config's price feed, any account stands in while USD fees are off
+function priceFeed(pdaData, pdaAccount) {
+    const feed = pdaData.usdFees.priceFeed;
+    return feed.equals(new anchor.web3.PublicKey(0)) ? pdaAccount : feed;
+}
 
 
 function formatError(errors, err)
@@ -57,6 +83,25 @@ async function initializePda(program, connection, feeToken, signer){
 }
 
 
+async function initializeOpenOrders(program, connection, capacity, signer){
+    const openOrders = await findAddress(program, [signer.publicKey.toBuffer(), Buffer.from(OPEN_ORDERS_SEED)]);
+    const instr = program.instruction.initializeOpenOrders(
+        capacity,
+        {
+            accounts: {
+                owner: signer.publicKey,
+                openOrders,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            },
+        },
+    );
+    const res = await utils.performInstructions(connection, signer, [instr]);
+    if(res[0])
+        return [openOrders, 'ok'];
+    return [null, formatError(program._idl.errors, res[1])];
+}
+
+
 async function initialize(program, connection, 
     initDepositTokenAmount, 
     takerAmount, 
@@ -94,30 +139,64 @@ async function initialize(program, connection,
         [signer.publicKey.toBuffer(), depositToken.toBuffer(), receiveToken.toBuffer()], program.programId);
     const [vaultAccount] = await anchor.web3.PublicKey.findProgramAddress(
         [escrow.toBuffer()], program.programId);
+    const vaultAuthority = await findAddress(program, [Buffer.from(ESCROW_PDA_SEED), escrow.toBuffer()]);
+
+    //the wallet's open orders index is created with its first escrow
+    const openOrders = await findAddress(program, [signer.publicKey.toBuffer(), Buffer.from(OPEN_ORDERS_SEED)]);
+    if(await connection.getAccountInfo(openOrders) == null){
+        instructions.push(program.instruction.initializeOpenOrders(
+            OPEN_ORDERS_CAPACITY,
+            {
+                accounts: {
+                    owner: signer.publicKey,
+                    openOrders,
+                    systemProgram: anchor.web3.SystemProgram.programId,
+                },
+            },
+        ));
+    }
     
     let instr = program.instruction.initialize(
         new anchor.BN(initDepositTokenAmount),
         new anchor.BN(takerAmount),
         new anchor.BN(feeAmountInitializer),
         new anchor.BN(feeAmountTaker),
+        {
+            hook: null,
+            canonicalReceive: false,
+            proceedsSplit: [],
+            deductFees: false,
+            conditions: [],
+            referrer: null,
+        },
     {
             accounts: {
                 initializer: signer.publicKey,
                 feeToken,
                 pdaAccount,
+                escrowAccount: escrow,                
+                openOrders,
+                market: await findAddress(program, pairSeeds(depositToken, receiveToken, MARKET_SEED)),
+                makerStats: await findAddress(program, [signer.publicKey.toBuffer(), feeToken.toBuffer(), Buffer.from(USER_STATS_SEED)]),
+                makerStake: await findAddress(program, [signer.publicKey.toBuffer(), feeToken.toBuffer(), Buffer.from(STAKE_SEED)]),
+                priceFeed: priceFeed(pdaData, pdaAccount),
+                vaultAuthority,
                 vaultAccount: vaultAccount,
                 depositToken: depositToken,
-                receiveToken: receiveToken,
                 initializerDepositTokenAccount: initializerDepositTokenAccount,
+                receiveToken: receiveToken,
                 initializerReceiveTokenAccount: initializerReceiveTokenAccount,
-                escrowAccount: escrow,                
-                vaultFeeAccount: pdaData.vaultFeeAccount,
+                refundTokenAccount: initializerDepositTokenAccount,
                 feeCollectTokenAccount,
-                initializerFeePayingTokenAccount: initFeePayTokenAcc,
                 tokenProgram: TOKEN_PROGRAM_ID,
                 systemProgram: anchor.web3.SystemProgram.programId,
                 rent: anchor.web3.SYSVAR_RENT_PUBKEY,                
-            }
+            },
+            // the fee is paid in the fee token, into its vault
+            remainingAccounts: [
+                { pubkey: initFeePayTokenAcc, isSigner: false, isWritable: true },
+                { pubkey: pdaData.vaultFeeAccount, isSigner: false, isWritable: true },
+            ],
         }
     );
     instructions.push(instr);
@@ -156,22 +235,22 @@ async function cancel(
         return [null, 'no exist escrow'];
     }
 
-    const [vaultAuthority] = await anchor.web3.PublicKey.findProgramAddress(
-        [Buffer.from('escrow'), escrow.toBuffer()], program.programId);
-
     const instr = program.instruction.cancel(
         {
             accounts: {
                 initializer: signer.publicKey,
                 pdaAccount,                
                 escrowAccount: escrow,
+                openOrders: await findAddress(program, [signer.publicKey.toBuffer(), Buffer.from(OPEN_ORDERS_SEED)]),
+                market: await findAddress(program, pairSeeds(depositToken, receiveToken, MARKET_SEED)),
                 vaultAccount: escrowData.vaultAccount,
-                vaultAuthority: vaultAuthority,
+                vaultAuthority: escrowData.vaultAuthority,
                 vaultFeeAccount: pdaData.vaultFeeAccount,
                 vaultFeeAuthority: pdaData.vaultFeeAuthority,
-                initializerDepositTokenAccount: escrowData.initializerDepositTokenAccount,
+                refundTokenAccount: escrowData.refundTokenAccount,
                 initializerFeePayingTokenAccount: escrowData.initializerFeePayingTokenAccount,                
                 tokenProgram: TOKEN_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
             },
         },
     );
@@ -186,6 +265,50 @@ async function cancel(
     return [null, formatError(program._idl.errors, res[1])];
 }
 
+// the taker's fee account and the escrow's referrer, stats, stake and
+// price feed the exchange can use, passed only when needed or when they
+// exist, in ExchangeExtras order
+async function exchangeExtras(program, connection, pdaData, escrowData, taker, takerFeePayAcc, feeToken) {
+    const initializer = escrowData.initializerKey;
+    const existing = async (pubkey) => (await connection.getAccountInfo(pubkey)) != null ? pubkey : null;
+    const makerReferrer = async (tokenAccount) => {
+        const info = await connection.getParsedAccountInfo(tokenAccount);
+        const owner = info.value
+            ? new anchor.web3.PublicKey(info.value.data.parsed.info.owner)
+            : new anchor.web3.PublicKey(0);
+        return [tokenAccount, await findAddress(program, [owner.toBuffer(), feeToken.toBuffer(), Buffer.from(REFERRER_SEED)])];
+    };
+    // with fee tiers on both stats addresses go in, opened or not
+    const stats = async (owner) => {
+        const address = await findAddress(program, [owner.toBuffer(), feeToken.toBuffer(), Buffer.from(USER_STATS_SEED)]);
+        return pdaData.feeTiers.length > 0 ? address : existing(address);
+    };
+    const accounts = {
+        takerFeePaying: escrowData.feeDeducted ? null : takerFeePayAcc,
+        referrer: null,
+        makerReferrer: escrowData.referrer.equals(new anchor.web3.PublicKey(0))
+            ? null
+            : await makerReferrer(escrowData.referrer),
+        makerStats: await stats(initializer),
+        takerStats: await stats(taker),
+        takerStake: await existing(await findAddress(program, [taker.toBuffer(), feeToken.toBuffer(), Buffer.from(STAKE_SEED)])),
+        priceFeed: pdaData.usdFees.priceFeed.equals(new anchor.web3.PublicKey(0)) ? null : pdaData.usdFees.priceFeed,
+        takerReceipt: null,
+        makerReceipt: null,
+        feeRevenueVault: escrowData.makerRebate.toNumber() > 0 && !pdaData.feeRevenueVault.equals(new anchor.web3.PublicKey(0))
+            ? pdaData.feeRevenueVault
+            : null,
+    };
+    const extras = {};
+    const remainingAccounts = [];
+    Object.entries(accounts).forEach(([name, value]) => {
+        extras[name] = value != null;
+        [].concat(value || []).forEach((pubkey) => remainingAccounts.push(
+            { pubkey, isSigner: false, isWritable: name != 'takerStake' && name != 'priceFeed' }));
+    });
+    return [extras, remainingAccounts];
+}
+
 async function exchange(
     program,
     connection,
@@ -194,8 +317,16 @@ async function exchange(
     receiveToken,
     feeToken,
     signer,
+    maxTakerFee = null,
 ) {
 
+    const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
+        [program.programId.toBuffer(), feeToken.toBuffer(), Buffer.from(PDA_SEED)], program.programId);
+    const pdaData = await utils.getPdaAccount(program, pdaAccount);
+    if(pdaData == null){
+        return [null, 'pda is not initialized'];
+    }
+
     const [escrow] = await anchor.web3.PublicKey.findProgramAddress(
         [initializer.toBuffer(), depositToken.toBuffer(), receiveToken.toBuffer()], program.programId);
 
@@ -204,28 +335,62 @@ async function exchange(
     {
         return [null, 'no exist escrow'];
     }
+    if(escrowData.hook != null || escrowData.conditions.length > 0)
+    {
+        return [null, 'escrow needs hook or condition accounts'];
+    }
 
     const takerDepositTokenAccount = await utils.getAssociatedTokenAddress(receiveToken, signer.publicKey, false);
     const takerReceiveTokenAccount = await utils.getAssociatedTokenAddress(depositToken, signer.publicKey, false);    
     const takerFeePayAcc = await utils.getAssociatedTokenAddress(feeToken, signer.publicKey, false);
-
+    // once fees are distributed they collect in the revenue vault
+    const feeCollect = pdaData.feeRevenueVault.equals(new anchor.web3.PublicKey(0))
+        ? escrowData.feeCollectTokenAccount
+        : pdaData.feeRevenueVault;
+    const [extras, remainingAccounts] = await exchangeExtras(
+        program, connection, pdaData, escrowData, signer.publicKey, takerFeePayAcc, feeToken);
+    const splitClaims = await Promise.all(escrowData.proceedsOwners.map((owner) => findAddress(
+        program, [owner.toBuffer(), receiveToken.toBuffer(), Buffer.from(CLAIM_SEED)])));
+
+    //the taker agrees to the escrow's current terms and fee
     const instr = program.instruction.exchange(
+        new anchor.BN(escrowData.takerAmount),
+        new anchor.BN(escrowData.initializerAmount),
+        new anchor.BN(maxTakerFee != null ? maxTakerFee : escrowData.feeAmountTaker),
+        [],
+        extras,
         {
             accounts: {
                 taker: signer.publicKey,
+                pdaAccount,
+                initializer: initializer,
+                escrowAccount: escrow,
+                openOrders: await findAddress(program, [initializer.toBuffer(), Buffer.from(OPEN_ORDERS_SEED)]),
+                market: await findAddress(program, pairSeeds(depositToken, receiveToken, MARKET_SEED)),
+                priceHistory: await findAddress(program, pairSeeds(depositToken, receiveToken, PRICE_HISTORY_SEED)),
                 takerDepositTokenAccount: takerDepositTokenAccount,
                 takerReceiveTokenAccount: takerReceiveTokenAccount,
+                depositToken,
                 initializerReceiveTokenAccount: escrowData.initializerReceiveTokenAccount,
-                initializer: initializer,
-                escrowAccount: escrow,
+                receiveToken,
+                claimAccount: await findAddress(program, [initializer.toBuffer(), receiveToken.toBuffer(), Buffer.from(CLAIM_SEED)]),
                 vaultAccount: escrowData.vaultAccount,
                 vaultAuthority: escrowData.vaultAuthority,
                 vaultFeeAccount: pdaData.vaultFeeAccount,
                 vaultFeeAuthority: pdaData.vaultFeeAuthority,
-                feeCollectTokenAccount: escrowData.feeCollectTokenAccount,
-                takerFeePayingTokenAccount: takerFeePayAcc,                
+                initializerFeeCollectTokenAccount: feeCollect,
+                takerFeeCollectTokenAccount: feeCollect,
+                initializerFeePayingTokenAccount: escrowData.initializerFeePayingTokenAccount,
                 tokenProgram: TOKEN_PROGRAM_ID,
+                associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+                rent: anchor.web3.SYSVAR_RENT_PUBKEY,
             },
+            remainingAccounts: [
+                ...remainingAccounts,
+                ...escrowData.proceedsSplit.map((share) => ({ pubkey: share.recipient, isSigner: false, isWritable: true })),
+                ...splitClaims.map((pubkey) => ({ pubkey, isSigner: false, isWritable: true })),
+            ],
         },
     );
     const res = await utils.performInstructions(connection, signer, [instr]);
@@ -238,6 +403,7 @@ async function exchange(
 
 module.exports = {
     initializePda,
+    initializeOpenOrders,
     initialize,
     cancel,
     exchange
@@ -88,6 +88,11 @@ async function createWallet(connection, wallet, lamports) {
 }
 
 
+async function getTokenAccountMint(connection, tokenAccount) {
+    const accInfo = await connection.getParsedAccountInfo(tokenAccount);
+    return new anchor.web3.PublicKey(accInfo.value.data.parsed.info.mint);
+}
+
 async function getTokenAccountBalance(connection, tokenAccount) {
     try{
         const accInfo = await connection.getTokenAccountBalance(tokenAccount);
@@ -245,10 +250,23 @@ class EscrowAccount{
     feeToken = null;
     initializerAmount = 0;
     takerAmount = 0;
-    initialized = 0;
+    status = 0;
     feeCollectTokenAccount = null;
     feeAmountInitializer = 0;
     feeAmountTaker = 0;
+    market = null;
+    orderEpoch = 0;
+    hook = null;
+    canonicalReceive = false;
+    refundTokenAccount = null;
+    proceedsSplit = [];
+    feeTiered = false;
+    makerRebate = 0;
+    initializerFeeMint = null;
+    feeDeducted = false;
+    conditions = [];
+    proceedsOwners = [];
+    referrer = null;
 
     deser(buffer){
         const reader = new borsh.BinaryReader(buffer);
@@ -267,10 +285,35 @@ class EscrowAccount{
         this.feeToken = new anchor.web3.PublicKey(reader.readFixedArray(32));
         this.initializerAmount = reader.readU64().toNumber();
         this.takerAmount = reader.readU64().toNumber();
-        this.initialized = reader.readU8();
+        this.status = reader.readU8();
         this.feeCollectTokenAccount = new anchor.web3.PublicKey(reader.readFixedArray(32));
         this.feeAmountInitializer = reader.readU64().toNumber();
         this.feeAmountTaker = reader.readU64().toNumber();
+        this.market = new anchor.web3.PublicKey(reader.readFixedArray(32));
+        this.orderEpoch = reader.readU64().toNumber();
+        if(reader.readU8() == 1){
+            const program = new anchor.web3.PublicKey(reader.readFixedArray(32));
+            const count = reader.readU32();
+            const accounts = [];
+            for(let i = 0; i < count; i++){
+                accounts.push(new anchor.web3.PublicKey(reader.readFixedArray(32)));
+            }
+            this.hook = { program, accounts };
+        }
+        this.canonicalReceive = reader.readU8() == 1;
+        this.refundTokenAccount = new anchor.web3.PublicKey(reader.readFixedArray(32));
+        this.proceedsSplit = readProceedsSplit(reader);
+        this.feeTiered = reader.readU8() == 1;
+        this.makerRebate = reader.readU64().toNumber();
+        this.initializerFeeMint = new anchor.web3.PublicKey(reader.readFixedArray(32));
+        this.feeDeducted = reader.readU8() == 1;
+        this.conditions = readConditions(reader);
+        const ownerCount = reader.readU32();
+        this.proceedsOwners = [];
+        for(let i = 0; i < ownerCount; i++){
+            this.proceedsOwners.push(new anchor.web3.PublicKey(reader.readFixedArray(32)));
+        }
+        this.referrer = new anchor.web3.PublicKey(reader.readFixedArray(32));
     }
 }
 
@@ -288,6 +331,51 @@ async function getEscrowData1(connection, programId, initializer, depositToken,
 }
 
 
+function readProceedsSplit(reader){
+    const shares = [];
+    const count = reader.readU32();
+    for(let i = 0; i < count; i++){
+        const recipient = new anchor.web3.PublicKey(reader.readFixedArray(32));
+        const bps = reader.readU16();
+        shares.push({ recipient, bps });
+    }
+    return shares;
+}
+
+// conditions keyed like anchor enums, e.g. { expiry: 1700000000 }
+function readConditions(reader){
+    const conditions = [];
+    const count = reader.readU32();
+    for(let i = 0; i < count; i++){
+        switch(reader.readU8()){
+            case 0:
+                conditions.push({ expiry: reader.readU64().toNumber() });
+                break;
+            case 1:
+                conditions.push({ taker: new anchor.web3.PublicKey(reader.readFixedArray(32)) });
+                break;
+            case 2:
+                conditions.push({ hashlock: Buffer.from(reader.readFixedArray(32)) });
+                break;
+            case 3: {
+                const oracle = new anchor.web3.PublicKey(reader.readFixedArray(32));
+                const direction = reader.readU8() == 0 ? 'above' : 'below';
+                const threshold = reader.readU64().toNumber();
+                const maxStaleness = reader.readU64().toNumber();
+                conditions.push({ price: { oracle, direction, threshold, maxStaleness } });
+                break;
+            }
+            case 4:
+                conditions.push({ approval: new anchor.web3.PublicKey(reader.readFixedArray(32)) });
+                break;
+            case 5:
+                conditions.push({ cancelAfter: reader.readU64().toNumber() });
+                break;
+        }
+    }
+    return conditions;
+}
+
 class InitializeEvent{
     initializerKey = null;
     depositToken = null;
@@ -298,6 +386,9 @@ class InitializeEvent{
     takerAmount = 0;
     feeAmountInitializer = 0;
     feeAmountTaker = 0;
+    proceedsRecipient = null;
+    refundRecipient = null;
+    proceedsSplit = [];
 
     deser(buffer){
         const reader = new borsh.BinaryReader(buffer);
@@ -314,6 +405,9 @@ class InitializeEvent{
         this.takerAmount = reader.readU64().toNumber();
         this.feeAmountInitializer = reader.readU64().toNumber();
         this.feeAmountTaker = reader.readU64().toNumber();
+        this.proceedsRecipient = new anchor.web3.PublicKey(reader.readFixedArray(32));
+        this.refundRecipient = new anchor.web3.PublicKey(reader.readFixedArray(32));
+        this.proceedsSplit = readProceedsSplit(reader);
     }
 }
 
@@ -323,6 +417,7 @@ class CancelEvent{
     receiveToken = null;
     initializerAmount = 0;
     receiveAmount = 0;
+    refundRecipient = null;
 
     deser(buffer){
         const reader = new borsh.BinaryReader(buffer);
@@ -335,6 +430,7 @@ class CancelEvent{
         this.receiveToken = new anchor.web3.PublicKey(reader.readFixedArray(32));
         this.initializerAmount = reader.readU64().toNumber();
         this.receiveAmount = reader.readU64().toNumber();
+        this.refundRecipient = new anchor.web3.PublicKey(reader.readFixedArray(32));
     }
 }
 
@@ -345,6 +441,13 @@ class ExchangeEvent{
     receiveToken = null;
     initializerAmount = 0;
     receiveAmount = 0;
+    proceedsRecipient = null;
+    takerRecipient = null;
+    proceedsSplit = [];
+    referrer = null;
+    referralAmount = 0;
+    makerReferrer = null;
+    makerReferralAmount = 0;
 
     deser(buffer){
         const reader = new borsh.BinaryReader(buffer);
@@ -358,6 +461,13 @@ class ExchangeEvent{
         this.receiveToken = new anchor.web3.PublicKey(reader.readFixedArray(32));
         this.initializerAmount = reader.readU64().toNumber();
         this.receiveAmount = reader.readU64().toNumber();
+        this.proceedsRecipient = new anchor.web3.PublicKey(reader.readFixedArray(32));
+        this.takerRecipient = new anchor.web3.PublicKey(reader.readFixedArray(32));
+        this.proceedsSplit = readProceedsSplit(reader);
+        this.referrer = new anchor.web3.PublicKey(reader.readFixedArray(32));
+        this.referralAmount = reader.readU64().toNumber();
+        this.makerReferrer = new anchor.web3.PublicKey(reader.readFixedArray(32));
+        this.makerReferralAmount = reader.readU64().toNumber();
     }
 }
 
@@ -424,6 +534,7 @@ module.exports = {
     createAssociatedTokenAccount,
     createWallet,
     getTokenAccountBalance,
+    getTokenAccountMint,
     mintTo,
     transferToken,
     createToken,
@@ -0,0 +1,359 @@
+//! Trades through `scrt_escrow` on behalf of a PDA, to exercise the escrow's
+//! CPI interface. The PDA at `[AUTHORITY_SEED]` is system owned and funded
+//! by the client, so it can pay for the accounts the escrow creates.
+
+use anchor_lang::prelude::*;
+use scrt_escrow::account::{EscrowOptions, ExchangeExtras};
+use scrt_escrow::interface;
+
+declare_id!("HxrvP2B5Fm3pZZBjSDUcMDuGek51KGBUWHXHYDYxxJQa");
+
+pub const AUTHORITY_SEED: &[u8] = b"authority";
+
+#[program]
+pub mod escrow_client {
+    use super::*;
+
+    pub fn open_orders(ctx: Context<OpenOrders>, capacity: u16) -> ProgramResult {
+        let (_authority, bump) = Pubkey::find_program_address(&[AUTHORITY_SEED], ctx.program_id);
+        let seeds = &[AUTHORITY_SEED, &[bump]];
+        let cpi_accounts = interface::accounts::InitializeOpenOrders {
+            owner: ctx.accounts.authority.clone(),
+            open_orders: ctx.accounts.open_orders.clone(),
+            system_program: ctx.accounts.system_program.clone(),
+        };
+        interface::initialize_open_orders(
+            CpiContext::new_with_signer(ctx.accounts.escrow_program.clone(), cpi_accounts, &[&seeds[..]]),
+            capacity,
+        )
+    }
+
+    pub fn make(
+        ctx: Context<Make>,
+        initializer_amount: u64,
+        taker_amount: u64,
+        fee_amount_initializer: u64,
+        fee_amount_taker: u64,
+    ) -> ProgramResult {
+        let (_authority, bump) = Pubkey::find_program_address(&[AUTHORITY_SEED], ctx.program_id);
+        let seeds = &[AUTHORITY_SEED, &[bump]];
+        let cpi_accounts = interface::accounts::Initialize {
+            initializer: ctx.accounts.authority.clone(),
+            fee_token: ctx.accounts.fee_token.clone(),
+            pda_account: ctx.accounts.pda_account.clone(),
+            deposit_token: ctx.accounts.deposit_token.clone(),
+            escrow_account: ctx.accounts.escrow_account.clone(),
+            market: ctx.accounts.market.clone(),
+            maker_stats: ctx.accounts.maker_stats.clone(),
+            maker_stake: ctx.accounts.maker_stake.clone(),
+            price_feed: ctx.accounts.price_feed.clone(),
+            open_orders: ctx.accounts.open_orders.clone(),
+            vault_authority: ctx.accounts.vault_authority.clone(),
+            vault_account: ctx.accounts.vault_account.clone(),
+            initializer_deposit_token_account: ctx.accounts.deposit_token_account.clone(),
+            receive_token: ctx.accounts.receive_token.clone(),
+            initializer_receive_token_account: ctx.accounts.receive_token_account.clone(),
+            refund_token_account: ctx.accounts.deposit_token_account.clone(),
+            fee_collect_token_account: ctx.accounts.fee_collect_token_account.clone(),
+            token_program: ctx.accounts.token_program.clone(),
+            system_program: ctx.accounts.system_program.clone(),
+            rent: ctx.accounts.rent.clone(),
+        };
+        let fee_accounts = vec![
+            ctx.accounts.fee_paying_token_account.clone(),
+            ctx.accounts.vault_fee_account.clone(),
+        ];
+        interface::initialize(
+            CpiContext::new_with_signer(ctx.accounts.escrow_program.clone(), cpi_accounts, &[&seeds[..]])
+                .with_remaining_accounts(fee_accounts),
+            initializer_amount,
+            taker_amount,
+            fee_amount_initializer,
+            fee_amount_taker,
+            EscrowOptions::default(),
+        )
+    }
+
+    pub fn cancel(ctx: Context<Cancel>) -> ProgramResult {
+        let (_authority, bump) = Pubkey::find_program_address(&[AUTHORITY_SEED], ctx.program_id);
+        let seeds = &[AUTHORITY_SEED, &[bump]];
+        let cpi_accounts = interface::accounts::Cancel {
+            initializer: ctx.accounts.authority.clone(),
+            pda_account: ctx.accounts.pda_account.clone(),
+            escrow_account: ctx.accounts.escrow_account.clone(),
+            open_orders: ctx.accounts.open_orders.clone(),
+            market: ctx.accounts.market.clone(),
+            vault_account: ctx.accounts.vault_account.clone(),
+            vault_authority: ctx.accounts.vault_authority.clone(),
+            refund_token_account: ctx.accounts.deposit_token_account.clone(),
+            initializer_fee_paying_token_account: ctx.accounts.fee_paying_token_account.clone(),
+            vault_fee_account: ctx.accounts.vault_fee_account.clone(),
+            vault_fee_authority: ctx.accounts.vault_fee_authority.clone(),
+            token_program: ctx.accounts.token_program.clone(),
+            system_program: ctx.accounts.system_program.clone(),
+        };
+        interface::cancel(CpiContext::new_with_signer(
+            ctx.accounts.escrow_program.clone(),
+            cpi_accounts,
+            &[&seeds[..]],
+        ))
+    }
+
+    pub fn close(ctx: Context<Close>) -> ProgramResult {
+        let (_authority, bump) = Pubkey::find_program_address(&[AUTHORITY_SEED], ctx.program_id);
+        let seeds = &[AUTHORITY_SEED, &[bump]];
+        let cpi_accounts = interface::accounts::CloseEscrow {
+            initializer: ctx.accounts.authority.clone(),
+            escrow_account: ctx.accounts.escrow_account.clone(),
+        };
+        interface::close_escrow(CpiContext::new_with_signer(
+            ctx.accounts.escrow_program.clone(),
+            cpi_accounts,
+            &[&seeds[..]],
+        ))
+    }
+
+    /// Remaining accounts are passed on to `exchange` as they are.
+    pub fn take<'info>(
+        ctx: Context<'_, '_, '_, 'info, Take<'info>>,
+        expected_taker_amount: u64,
+        expected_initializer_amount: u64,
+        max_taker_fee: u64,
+        preimages: Vec<Vec<u8>>,
+        extras: ExchangeExtras,
+    ) -> ProgramResult {
+        let (_authority, bump) = Pubkey::find_program_address(&[AUTHORITY_SEED], ctx.program_id);
+        let seeds = &[AUTHORITY_SEED, &[bump]];
+        let cpi_accounts = interface::accounts::Exchange {
+            taker: ctx.accounts.authority.clone(),
+            pda_account: ctx.accounts.pda_account.clone(),
+            initializer: ctx.accounts.initializer.clone(),
+            escrow_account: ctx.accounts.escrow_account.clone(),
+            open_orders: ctx.accounts.open_orders.clone(),
+            market: ctx.accounts.market.clone(),
+            price_history: ctx.accounts.price_history.clone(),
+            taker_deposit_token_account: ctx.accounts.deposit_token_account.clone(),
+            taker_receive_token_account: ctx.accounts.receive_token_account.clone(),
+            deposit_token: ctx.accounts.deposit_token.clone(),
+            initializer_receive_token_account: ctx.accounts.initializer_receive_token_account.clone(),
+            receive_token: ctx.accounts.receive_token.clone(),
+            claim_account: ctx.accounts.claim_account.clone(),
+            vault_account: ctx.accounts.vault_account.clone(),
+            vault_authority: ctx.accounts.vault_authority.clone(),
+            vault_fee_account: ctx.accounts.vault_fee_account.clone(),
+            vault_fee_authority: ctx.accounts.vault_fee_authority.clone(),
+            initializer_fee_collect_token_account: ctx.accounts.initializer_fee_collect_token_account.clone(),
+            taker_fee_collect_token_account: ctx.accounts.taker_fee_collect_token_account.clone(),
+            initializer_fee_paying_token_account: ctx.accounts.initializer_fee_paying_token_account.clone(),
+            token_program: ctx.accounts.token_program.clone(),
+            associated_token_program: ctx.accounts.associated_token_program.clone(),
+            system_program: ctx.accounts.system_program.clone(),
+            rent: ctx.accounts.rent.clone(),
+        };
+        interface::exchange(
+            CpiContext::new_with_signer(ctx.accounts.escrow_program.clone(), cpi_accounts, &[&seeds[..]])
+                .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            expected_taker_amount,
+            expected_initializer_amount,
+            max_taker_fee,
+            preimages,
+            extras,
+        )
+    }
+}
+
+#[derive(Accounts)]
+pub struct OpenOrders<'info> {
+    /// CHECK
+    #[account(mut, seeds = [AUTHORITY_SEED], bump)]
+    pub authority: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub open_orders: AccountInfo<'info>,
+    /// CHECK
+    pub system_program: AccountInfo<'info>,
+    /// CHECK
+    #[account(address = scrt_escrow::ID)]
+    pub escrow_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Make<'info> {
+    /// CHECK
+    #[account(mut, seeds = [AUTHORITY_SEED], bump)]
+    pub authority: AccountInfo<'info>,
+    /// CHECK
+    pub fee_token: AccountInfo<'info>,
+    /// CHECK
+    pub pda_account: AccountInfo<'info>,
+    /// CHECK
+    pub deposit_token: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub escrow_account: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub market: AccountInfo<'info>,
+    /// CHECK
+    pub maker_stats: AccountInfo<'info>,
+    /// CHECK
+    pub maker_stake: AccountInfo<'info>,
+    /// CHECK
+    pub price_feed: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub open_orders: AccountInfo<'info>,
+    /// CHECK
+    pub vault_authority: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub vault_account: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub deposit_token_account: AccountInfo<'info>,
+    /// CHECK
+    pub receive_token: AccountInfo<'info>,
+    /// CHECK
+    pub receive_token_account: AccountInfo<'info>,
+    /// CHECK
+    pub fee_collect_token_account: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub vault_fee_account: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub fee_paying_token_account: AccountInfo<'info>,
+    /// CHECK
+    pub token_program: AccountInfo<'info>,
+    /// CHECK
+    pub system_program: AccountInfo<'info>,
+    /// CHECK
+    pub rent: AccountInfo<'info>,
+    /// CHECK
+    #[account(address = scrt_escrow::ID)]
+    pub escrow_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Cancel<'info> {
+    /// CHECK
+    #[account(mut, seeds = [AUTHORITY_SEED], bump)]
+    pub authority: AccountInfo<'info>,
+    /// CHECK
+    pub pda_account: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub escrow_account: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub open_orders: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub market: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub vault_account: AccountInfo<'info>,
+    /// CHECK
+    pub vault_authority: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub deposit_token_account: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub fee_paying_token_account: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub vault_fee_account: AccountInfo<'info>,
+    /// CHECK
+    pub vault_fee_authority: AccountInfo<'info>,
+    /// CHECK
+    pub token_program: AccountInfo<'info>,
+    /// CHECK
+    pub system_program: AccountInfo<'info>,
+    /// CHECK
+    #[account(address = scrt_escrow::ID)]
+    pub escrow_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Close<'info> {
+    /// CHECK
+    #[account(mut, seeds = [AUTHORITY_SEED], bump)]
+    pub authority: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub escrow_account: AccountInfo<'info>,
+    /// CHECK
+    #[account(address = scrt_escrow::ID)]
+    pub escrow_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Take<'info> {
+    /// CHECK
+    #[account(mut, seeds = [AUTHORITY_SEED], bump)]
+    pub authority: AccountInfo<'info>,
+    /// CHECK
+    pub pda_account: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub initializer: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub escrow_account: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub open_orders: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub market: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub price_history: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub deposit_token_account: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub receive_token_account: AccountInfo<'info>,
+    /// CHECK
+    pub deposit_token: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub initializer_receive_token_account: AccountInfo<'info>,
+    /// CHECK
+    pub receive_token: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub claim_account: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub vault_account: AccountInfo<'info>,
+    /// CHECK
+    pub vault_authority: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub vault_fee_account: AccountInfo<'info>,
+    /// CHECK
+    pub vault_fee_authority: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub initializer_fee_collect_token_account: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub taker_fee_collect_token_account: AccountInfo<'info>,
+    /// CHECK
+    #[account(mut)]
+    pub initializer_fee_paying_token_account: AccountInfo<'info>,
+    /// CHECK
+    pub token_program: AccountInfo<'info>,
+    /// CHECK
+    pub associated_token_program: AccountInfo<'info>,
+    /// CHECK
+    pub system_program: AccountInfo<'info>,
+    /// CHECK
+    pub rent: AccountInfo<'info>,
+    /// CHECK
+    #[account(address = scrt_escrow::ID)]
+    pub escrow_program: AccountInfo<'info>,
+}
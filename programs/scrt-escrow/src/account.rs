@@ -1,7 +1,38 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{CloseAccount, SetAuthority, TokenAccount, Transfer};
+use anchor_spl::token::{CloseAccount, Mint, SetAuthority, TokenAccount, Transfer};
+use spl_associated_token_account::get_associated_token_address;
+use std::cmp::{max, min};
+
+use crate::book::{OrderBook, ORDER_BOOK_SEED};
+use crate::condition::{Condition, CONDITIONS_SPACE};
+use crate::error::ScrtEscrowErrors;
+use crate::fee_mint::{AcceptedFeeMint, FEE_MINTS_SPACE};
+use crate::history::{PriceHistory, PRICE_HISTORY_SEED};
+use crate::hook::{FillHook, FILL_HOOK_SPACE};
+use crate::oracle::{UsdFees, USD_FEES_SPACE};
+use crate::split::{ProceedsShare, PROCEEDS_OWNERS_SPACE, PROCEEDS_SPLIT_SPACE, TOTAL_BPS};
+use crate::stake::{StakeDiscount, STAKE_DISCOUNTS_SPACE};
+use crate::tier::{FeeTier, FEE_TIERS_SPACE};
 
 pub const PDA_SEED: &[u8] = b"ser-escrow";
+pub const ESCROW_PDA_SEED: &[u8] = b"escrow";
+pub const OPEN_ORDERS_SEED: &[u8] = b"open-orders";
+/// Accounts can't be resized, and one created inside the program can't
+/// be larger than 10KB, so this bounds `OpenOrders::space`.
+pub const MAX_OPEN_ORDERS_CAPACITY: u16 = 256;
+pub const MARKET_SEED: &[u8] = b"market";
+pub const PROGRAM_CONFIG_SEED: &[u8] = b"program-config";
+pub const CLAIM_SEED: &[u8] = b"claim";
+pub const REFERRER_SEED: &[u8] = b"referrer";
+pub const USER_STATS_SEED: &[u8] = b"user-stats";
+pub const STAKE_SEED: &[u8] = b"stake";
+pub const STAKE_VAULT_SEED: &[u8] = b"stake-vault";
+pub const FEE_REVENUE_SEED: &[u8] = b"fee-revenue";
+pub const FEE_VAULT_SEED: &[u8] = b"fee-vault";
+pub const PRICE_SEED: &[u8] = b"price";
+pub const BATCH_EXCHANGE_ACCOUNTS: usize = 8;
+pub const BATCH_CANCEL_ACCOUNTS: usize = 6;
+pub const RING_TRADE_ACCOUNTS: usize = 13;
 
 #[account]
 #[derive(Default)]
@@ -10,6 +41,201 @@ pub struct PdaAccount {
     pub fee_token: Pubkey,
     pub vault_fee_account: Pubkey,
     pub vault_fee_authority: Pubkey,
+    pub book_maker_fee: u64,
+    pub book_taker_fee: u64,
+    /// Share of the taker's and of the initializer's exchange fee paid to
+    /// whoever referred them, in bps.
+    pub referral_share_bps: u16,
+    /// Volume tiers, empty for flat per-order fees.
+    pub fee_tiers: Vec<FeeTier>,
+    /// Holds staked fee tokens, owned by `vault_fee_authority`.
+    pub stake_vault: Pubkey,
+    /// Seconds unstaked tokens wait before they can be withdrawn.
+    pub unbonding_period: i64,
+    pub stake_discounts: Vec<StakeDiscount>,
+    /// Collects fee token fees once a distribution is set up, owned by
+    /// `vault_fee_authority`. Fees in other mints then collect in that
+    /// authority's associated token accounts.
+    pub fee_revenue_vault: Pubkey,
+    /// Destinations of `distribute_fees`, empty until set up.
+    pub fee_distribution: Vec<ProceedsShare>,
+    /// Other mints fees may be paid in, each at its own rate.
+    pub accepted_fee_mints: Vec<AcceptedFeeMint>,
+    /// Owner of the collector accounts in deducted fee mode, unset while
+    /// the mode is off. Ignored once fees are distributed.
+    pub deducted_fee_collector: Pubkey,
+    /// Taken out of the deposit in deducted fee mode.
+    pub deducted_maker_bps: u16,
+    /// Taken out of the taker's payment in deducted fee mode.
+    pub deducted_taker_bps: u16,
+    /// Prices fee token fees in USD when set up.
+    pub usd_fees: UsdFees,
+}
+
+impl PdaAccount {
+    pub const SPACE: usize = 8 + 32 * 4 + 8 * 2 + 2 + FEE_TIERS_SPACE + 32 + 8 + STAKE_DISCOUNTS_SPACE + 32 + PROCEEDS_SPLIT_SPACE + FEE_MINTS_SPACE + 32 + 2 * 2 + USD_FEES_SPACE;
+
+    pub fn referral_cut(&self, fee: u64) -> u64 {
+        (fee as u128 * self.referral_share_bps as u128 / TOTAL_BPS as u128) as u64
+    }
+
+    pub fn fee_mint(&self, mint: &Pubkey) -> Option<&AcceptedFeeMint> {
+        self.accepted_fee_mints.iter().find(|fee_mint| fee_mint.mint == *mint)
+    }
+
+    pub fn accepts(&self, mint: &Pubkey) -> bool {
+        *mint == self.fee_token || self.fee_mint(mint).is_some()
+    }
+
+    /// Holds open escrows' initializer fees paid in `mint`.
+    pub fn fee_vault(&self, mint: &Pubkey) -> Option<Pubkey> {
+        if *mint == self.fee_token {
+            return Some(self.vault_fee_account);
+        }
+        self.fee_mint(mint).map(|fee_mint| fee_mint.vault)
+    }
+
+    pub fn distributes_fees(&self) -> bool {
+        self.fee_revenue_vault != Pubkey::default()
+    }
+
+    /// Holds distributable fees in `mint`: the revenue vault for the fee
+    /// token, `vault_fee_authority`'s associated account for other mints.
+    pub fn revenue_vault(&self, mint: &Pubkey) -> Pubkey {
+        if *mint == self.fee_token {
+            return self.fee_revenue_vault;
+        }
+        get_associated_token_address(&self.vault_fee_authority, mint)
+    }
+
+    /// Where fees paid in `mint` go. Once fees are distributed that's the
+    /// mint's revenue vault, before it fees in `fee_token` go to the
+    /// escrow's own fee collect account.
+    pub fn fee_collector(&self, mint: &Pubkey, escrow_collector: &Pubkey) -> Option<Pubkey> {
+        if self.distributes_fees() {
+            return self.accepts(mint).then(|| self.revenue_vault(mint));
+        }
+        if *mint == self.fee_token {
+            return Some(*escrow_collector);
+        }
+        self.fee_mint(mint).map(|fee_mint| fee_mint.collector)
+    }
+}
+
+/// What a referrer has earned from fills in one fee token.
+#[account]
+#[derive(Default)]
+pub struct ReferrerStats {
+    pub owner: Pubkey,
+    pub fee_token: Pubkey,
+    pub fills: u64,
+    pub fee_earned: u64,
+}
+
+impl ReferrerStats {
+    pub const SPACE: usize = 8 + 32 * 2 + 8 * 2;
+}
+
+/// A user's filled volume in one fee token, which picks their fee tier.
+#[account]
+#[derive(Default)]
+pub struct UserStats {
+    pub owner: Pubkey,
+    pub fee_token: Pubkey,
+    pub volume: u64,
+    pub fills: u64,
+}
+
+impl UserStats {
+    pub const SPACE: usize = 8 + 32 * 2 + 8 * 2;
+}
+
+/// A price feed kept by `authority`, for local testing where no Pyth
+/// accounts exist. `product` only tells one authority's feeds apart.
+/// Only `test-oracle` builds create or read these.
+#[account]
+#[derive(Default)]
+pub struct PriceAccount {
+    pub authority: Pubkey,
+    pub product: Pubkey,
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: i64,
+}
+
+impl PriceAccount {
+    pub const SPACE: usize = 8 + 32 * 2 + 8 * 2 + 4 + 8;
+}
+
+/// A user's fee token stake. Only `active` stake earns a discount.
+#[account]
+#[derive(Default)]
+pub struct StakeAccount {
+    pub owner: Pubkey,
+    pub fee_token: Pubkey,
+    pub active: u64,
+    pub unbonding: u64,
+    pub unbonding_ends: i64,
+}
+
+impl StakeAccount {
+    pub const SPACE: usize = 8 + 32 * 2 + 8 * 3;
+}
+
+/// `owner`'s active stake of `fee_token` behind `info`, zero if the user
+/// never opened a stake account.
+pub fn active_stake(info: &AccountInfo, owner: &Pubkey, fee_token: &Pubkey) -> Result<u64, ProgramError> {
+    if info.data_is_empty() {
+        return Ok(0);
+    }
+    let stake = Account::<StakeAccount>::try_from(info)?;
+    if stake.owner != *owner || stake.fee_token != *fee_token {
+        return Err(ScrtEscrowErrors::InvalidStakeAccount.into());
+    }
+    Ok(stake.active)
+}
+
+/// Loads a user's stats if they have opened them. Users without stats
+/// trade at the first tier and don't accumulate volume.
+pub fn load_user_stats<'info>(info: &AccountInfo<'info>) -> Result<Option<Account<'info, UserStats>>, ProgramError> {
+    if info.data_is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Account::try_from(info)?))
+}
+
+/// Loads `owner`'s stats in `fee_token` from an account the caller didn't
+/// derive. It must be their stats address even if they have no stats.
+pub fn load_stats_of<'info>(
+    info: &AccountInfo<'info>,
+    owner: &Pubkey,
+    fee_token: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<Option<Account<'info, UserStats>>, ProgramError> {
+    let (address, _) = Pubkey::find_program_address(
+        &[owner.as_ref(), fee_token.as_ref(), USER_STATS_SEED],
+        program_id,
+    );
+    if *info.key != address {
+        return Err(ScrtEscrowErrors::InvalidUserStats.into());
+    }
+    load_user_stats(info)
+}
+
+/// Loads a referrer's fee token account and their stats, which must be
+/// the same owner's in `fee_token`.
+pub fn load_referrer<'info>(
+    token_account: &AccountInfo<'info>,
+    stats_account: &AccountInfo<'info>,
+    fee_token: &Pubkey,
+) -> Result<(Account<'info, TokenAccount>, Account<'info, ReferrerStats>), ProgramError> {
+    let referrer: Account<TokenAccount> = Account::try_from(token_account)?;
+    let stats: Account<ReferrerStats> = Account::try_from(stats_account)?;
+    if referrer.mint != *fee_token || stats.owner != referrer.owner || stats.fee_token != *fee_token {
+        return Err(ScrtEscrowErrors::InvalidReferrer.into());
+    }
+    Ok((referrer, stats))
 }
 
 #[account]
@@ -28,12 +254,283 @@ pub struct EscrowAccount {
 
     pub initializer_amount: u64,
     pub taker_amount: u64,
-    pub initialized: u8,
+    pub status: EscrowStatus,
     pub fee_collect_token_account: Pubkey,
     pub fee_amount_initializer: u64,
     pub fee_amount_taker: u64,
+    pub market: Pubkey,
+    pub order_epoch: u64,
+    pub hook: Option<FillHook>,
+    /// Receive account must stay the initializer's associated token account.
+    pub canonical_receive: bool,
+    /// Gets the deposit back on cancel, may belong to someone else.
+    pub refund_token_account: Pubkey,
+    pub proceeds_split: Vec<ProceedsShare>,
+    /// Fees came from the volume tiers: the taker pays their own tier at fill.
+    pub fee_tiered: bool,
+    /// Paid to the initializer out of the fee revenue vault at fill, as far
+    /// as its balance goes.
+    pub maker_rebate: u64,
+    /// Mint the initializer paid their fee in.
+    pub initializer_fee_mint: Pubkey,
+    /// Fees are taken out of the traded amounts: `fee_amount_initializer`
+    /// is in the deposit mint and `fee_amount_taker` in the receive mint.
+    pub fee_deducted: bool,
+    /// Checked on every fill and cancel.
+    pub conditions: Vec<Condition>,
+    /// Owners of the split recipients when the escrow was made, in split
+    /// order. A share a recipient can't take goes to its owner's claim.
+    pub proceeds_owners: Vec<Pubkey>,
+    /// Fee token account of whoever referred the maker, paid a cut of the
+    /// initializer fee at fill. Default if nobody did.
+    pub referrer: Pubkey,
+}
+
+/// Optional maker settings passed to `initialize`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct EscrowOptions {
+    pub hook: Option<FillHook>,
+    pub canonical_receive: bool,
+    pub proceeds_split: Vec<ProceedsShare>,
+    /// Take fees out of the traded amounts instead of fee token accounts.
+    pub deduct_fees: bool,
+    pub conditions: Vec<Condition>,
+    /// Fee token account of whoever referred the maker. It and its
+    /// referrer stats follow the split recipients.
+    pub referrer: Option<Pubkey>,
+}
+
+/// Settled escrows keep their account, with the final status, until the
+/// initializer closes it with `close_escrow`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EscrowStatus {
+    #[default]
+    Open,
+    Filled,
+    Cancelled,
+    /// Cancelled after its expiry condition passed.
+    Expired,
+}
+
+impl EscrowStatus {
+    pub fn is_live(&self) -> bool {
+        matches!(self, EscrowStatus::Open)
+    }
+}
+
+/// Index of a user's live escrows, kept in sync by `initialize`/`cancel`/`exchange`
+/// once the user has called `initialize_open_orders`. Escrows made before
+/// that aren't listed. Its capacity is fixed at creation.
+#[account]
+#[derive(Default)]
+pub struct OpenOrders {
+    pub owner: Pubkey,
+    pub capacity: u16,
+    pub escrows: Vec<Pubkey>,
+    /// Escrows created under an older epoch can no longer be taken.
+    pub order_epoch: u64,
+}
+
+impl OpenOrders {
+    pub fn space(capacity: u16) -> usize {
+        8 + 32 + 2 + 4 + 32 * min(capacity, MAX_OPEN_ORDERS_CAPACITY) as usize + 8
+    }
+
+    pub fn insert(&mut self, escrow: Pubkey) -> ProgramResult {
+        if self.escrows.len() >= self.capacity as usize {
+            return Err(ScrtEscrowErrors::OpenOrdersFull.into());
+        }
+        self.escrows.push(escrow);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, escrow: &Pubkey) {
+        self.escrows.retain(|key| key != escrow);
+    }
+}
+
+/// Program wide settings, created once by the program's upgrade authority.
+#[account]
+#[derive(Default)]
+pub struct ProgramConfig {
+    /// Creates markets.
+    pub admin: Pubkey,
+}
+
+impl ProgramConfig {
+    pub const SPACE: usize = 8 + 32;
+}
+
+/// The upgrade authority recorded in `program_data`, the program data
+/// account of `program_id` under the upgradeable loader. None once the
+/// program is immutable.
+pub fn upgrade_authority(program_data: &AccountInfo, program_id: &Pubkey) -> Result<Option<Pubkey>, ProgramError> {
+    let loader = anchor_lang::solana_program::bpf_loader_upgradeable::id();
+    let (address, _) = Pubkey::find_program_address(&[program_id.as_ref()], &loader);
+    if *program_data.key != address || *program_data.owner != loader {
+        return Err(ScrtEscrowErrors::InvalidProgramData.into());
+    }
+    // bincode UpgradeableLoaderState::ProgramData: u32 tag 3, u64 slot,
+    // then an Option<Pubkey>
+    let data = program_data.try_borrow_data()?;
+    if data.len() < 13 || data[..4] != 3u32.to_le_bytes() {
+        return Err(ScrtEscrowErrors::InvalidProgramData.into());
+    }
+    if data[12] == 0 {
+        return Ok(None);
+    }
+    if data.len() < 45 {
+        return Err(ScrtEscrowErrors::InvalidProgramData.into());
+    }
+    Ok(Some(Pubkey::new(&data[13..45])))
+}
+
+/// Per-pair trading rules, keyed by the pair's mints in byte order.
+/// Prices are quote atoms per `lot_size` base atoms.
+#[account]
+#[derive(Default)]
+pub struct Market {
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub tick_size: u64,
+    pub lot_size: u64,
+    pub min_notional: u64,
+    pub enabled: bool,
+    pub open_orders: u64,
+    pub base_volume: u64,
+    pub quote_volume: u64,
+    pub order_book: Pubkey,
+}
+
+impl Market {
+    pub const SPACE: usize = 8 + 32 * 2 + 8 * 3 + 1 + 8 * 3 + 32;
+
+    /// Splits an escrow's amounts into (base, quote) for this market.
+    pub fn base_quote(&self, deposit_token: &Pubkey, initializer_amount: u64, taker_amount: u64) -> (u64, u64) {
+        if *deposit_token == self.base_mint {
+            (initializer_amount, taker_amount)
+        } else {
+            (taker_amount, initializer_amount)
+        }
+    }
+
+    /// Updates the running counters when one of this market's escrows
+    /// leaves the book, adding its volume if it was filled.
+    pub fn record_close(&mut self, escrow: &EscrowAccount, filled: bool) {
+        self.open_orders = self.open_orders.saturating_sub(1);
+        if filled {
+            self.record_volume(&escrow.deposit_token, escrow.initializer_amount, escrow.taker_amount);
+        }
+    }
+
+    pub fn record_volume(&mut self, deposit_token: &Pubkey, deposit_amount: u64, receive_amount: u64) {
+        let (base_amount, quote_amount) = self.base_quote(deposit_token, deposit_amount, receive_amount);
+        self.base_volume = self.base_volume.saturating_add(base_amount);
+        self.quote_volume = self.quote_volume.saturating_add(quote_amount);
+    }
+
+    pub fn validate_order(&self, base_amount: u64, quote_amount: u64) -> ProgramResult {
+        if !self.enabled {
+            return Err(ScrtEscrowErrors::MarketDisabled.into());
+        }
+        if base_amount.checked_rem(self.lot_size) != Some(0) {
+            return Err(ScrtEscrowErrors::OddLotOrder.into());
+        }
+        let lots = base_amount / self.lot_size;
+        let price = quote_amount.checked_div(lots).unwrap_or(0);
+        if price == 0 || price * lots != quote_amount || price.checked_rem(self.tick_size) != Some(0) {
+            return Err(ScrtEscrowErrors::InvalidTickSize.into());
+        }
+        if quote_amount < self.min_notional {
+            return Err(ScrtEscrowErrors::BelowMinNotional.into());
+        }
+        Ok(())
+    }
+}
+
+/// Loads the pair's market if the admin has created one.
+pub fn load_market<'info>(info: &AccountInfo<'info>) -> Result<Option<Account<'info, Market>>, ProgramError> {
+    if info.data_is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Account::try_from(info)?))
+}
+
+/// Loads `owner`'s open orders, None while they haven't created them.
+/// Their escrows are then made under order epoch 0.
+pub fn load_open_orders<'info>(
+    info: &AccountInfo<'info>,
+    owner: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<Option<Account<'info, OpenOrders>>, ProgramError> {
+    let (address, _bump) = Pubkey::find_program_address(&[owner.as_ref(), OPEN_ORDERS_SEED], program_id);
+    if *info.key != address {
+        return Err(ScrtEscrowErrors::InvalidOpenOrders.into());
+    }
+    if info.data_is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Account::try_from(info)?))
+}
+
+/// Epoch of `owner`'s escrows, 0 without open orders.
+pub fn order_epoch(open_orders: &Option<Account<OpenOrders>>) -> u64 {
+    open_orders.as_ref().map_or(0, |open_orders| open_orders.order_epoch)
+}
+
+/// Derived address of the price history of the pair of `mint_a` and `mint_b`.
+pub fn price_history_address(mint_a: &Pubkey, mint_b: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    let (low, high) = (min(mint_a, mint_b), max(mint_a, mint_b));
+    Pubkey::find_program_address(&[low.as_ref(), high.as_ref(), PRICE_HISTORY_SEED], program_id).0
+}
+
+/// Loads the price history of the pair of `mint_a` and `mint_b`, which
+/// has to be at its derived address and already created.
+pub fn load_price_history<'info>(
+    info: &AccountInfo<'info>,
+    mint_a: &Pubkey,
+    mint_b: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<Loader<'info, PriceHistory>, ProgramError> {
+    let (low, high) = (min(mint_a, mint_b), max(mint_a, mint_b));
+    if *info.key != price_history_address(mint_a, mint_b, program_id) {
+        return Err(ScrtEscrowErrors::InvalidPriceHistory.into());
+    }
+    if info.data_is_empty() {
+        return Err(ScrtEscrowErrors::PriceHistoryMissing.into());
+    }
+    let history: Loader<PriceHistory> = Loader::try_from(program_id, info)?;
+    {
+        let data = history.load()?;
+        if min(&data.base_mint, &data.quote_mint) != low || max(&data.base_mint, &data.quote_mint) != high {
+            return Err(ScrtEscrowErrors::InvalidPriceHistory.into());
+        }
+    }
+    Ok(history)
+}
+
+/// Durable record of a settled escrow, created on request by `cancel`/`exchange`.
+#[account]
+#[derive(Default)]
+pub struct SettlementReceipt {
+    pub owner: Pubkey,
+    /// Paid the rent, which goes back to them on close.
+    pub payer: Pubkey,
+    pub escrow: Pubkey,
+    pub initializer: Pubkey,
+    pub taker: Pubkey,
+    pub deposit_token: Pubkey,
+    pub receive_token: Pubkey,
+    pub initializer_amount: u64,
+    pub taker_amount: u64,
+    pub fee_amount_initializer: u64,
+    pub fee_amount_taker: u64,
+    pub status: EscrowStatus,
+    pub slot: u64,
 }
 
+pub const SETTLEMENT_RECEIPT_SPACE: usize = 8 + 32 * 7 + 8 * 4 + 1 + 8;
+
 #[derive(Accounts)]
 pub struct InitializePda<'info> {
     /// CHECK
@@ -45,7 +542,7 @@ pub struct InitializePda<'info> {
         seeds = [program_id.as_ref(), fee_token.key.as_ref(), PDA_SEED],
         bump,
         payer = initializer,
-        space = 8 + 32 * 4
+        space = PdaAccount::SPACE
     )]
     pub pda_account: ProgramAccount<'info, PdaAccount>,
 
@@ -77,6 +574,119 @@ impl<'info> InitializePda<'info> {
     }
 }
 
+#[derive(Accounts)]
+pub struct UpdateBookFees<'info> {
+    /// CHECK
+    #[account(signer)]
+    pub admin: AccountInfo<'info>,
+
+    #[account(mut, constraint = pda_account.initializer_key == *admin.key)]
+    pub pda_account: ProgramAccount<'info, PdaAccount>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeProgramConfig<'info> {
+    /// CHECK
+    /// Has to be the program's upgrade authority.
+    #[account(mut, signer)]
+    pub authority: AccountInfo<'info>,
+
+    /// CHECK
+    pub program_data: AccountInfo<'info>,
+
+    #[account(init,
+        seeds = [PROGRAM_CONFIG_SEED],
+        bump,
+        payer = authority,
+        space = ProgramConfig::SPACE
+    )]
+    pub program_config: ProgramAccount<'info, ProgramConfig>,
+
+    /// CHECK
+    pub system_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateProgramConfig<'info> {
+    /// CHECK
+    #[account(signer)]
+    pub admin: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROGRAM_CONFIG_SEED],
+        bump,
+        constraint = program_config.admin == *admin.key
+    )]
+    pub program_config: ProgramAccount<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeMarket<'info> {
+    /// CHECK
+    #[account(mut, signer)]
+    pub admin: AccountInfo<'info>,
+
+    #[account(
+        seeds = [PROGRAM_CONFIG_SEED],
+        bump,
+        constraint = program_config.admin == *admin.key
+    )]
+    pub program_config: ProgramAccount<'info, ProgramConfig>,
+
+    /// CHECK
+    pub base_mint: AccountInfo<'info>,
+    /// CHECK
+    pub quote_mint: AccountInfo<'info>,
+
+    #[account(init,
+        seeds = [min(base_mint.key, quote_mint.key).as_ref(), max(base_mint.key, quote_mint.key).as_ref(), MARKET_SEED],
+        bump,
+        payer = admin,
+        space = Market::SPACE
+    )]
+    pub market: ProgramAccount<'info, Market>,
+
+    /// CHECK
+    pub system_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateMarket<'info> {
+    /// CHECK
+    #[account(signer)]
+    pub admin: AccountInfo<'info>,
+
+    #[account(
+        seeds = [PROGRAM_CONFIG_SEED],
+        bump,
+        constraint = program_config.admin == *admin.key
+    )]
+    pub program_config: ProgramAccount<'info, ProgramConfig>,
+
+    #[account(mut)]
+    pub market: ProgramAccount<'info, Market>,
+}
+
+#[derive(Accounts)]
+#[instruction(capacity: u16)]
+pub struct InitializeOpenOrders<'info> {
+    /// CHECK
+    #[account(mut, signer)]
+    pub owner: AccountInfo<'info>,
+
+    #[account(init,
+        seeds = [owner.key.as_ref(), OPEN_ORDERS_SEED],
+        bump,
+        payer = owner,
+        space = OpenOrders::space(capacity)
+    )]
+    pub open_orders: ProgramAccount<'info, OpenOrders>,
+
+    /// CHECK
+    pub system_program: AccountInfo<'info>,
+}
+
 #[derive(Accounts)]
 //#[instruction(initializer_amount: u64, fee_amount_initializer: u64)]
 pub struct Initialize<'info> {
@@ -98,17 +708,62 @@ pub struct Initialize<'info> {
         seeds = [initializer.key.as_ref(), deposit_token.key.as_ref(), receive_token.key.as_ref()],
         bump,
         payer = initializer,
-        space = 8 + 32 * 9 + 8 + 8 + 1 + 32 + 8 + 8
+        space = 8 + 32 * 9 + 8 + 8 + 1 + 32 + 8 + 8 + 32 + 8 + FILL_HOOK_SPACE + 1 + 32 + PROCEEDS_SPLIT_SPACE + 1 + 8 + 32 + 1 + CONDITIONS_SPACE + PROCEEDS_OWNERS_SPACE + 32
     )]
     pub escrow_account: ProgramAccount<'info, EscrowAccount>,
 
+    /// CHECK
+    #[account(
+        mut,
+        seeds = [min(deposit_token.key, receive_token.key).as_ref(), max(deposit_token.key, receive_token.key).as_ref(), MARKET_SEED],
+        bump,
+    )]
+    pub market: AccountInfo<'info>,
+
+    /// CHECK
+    /// May be empty if the initializer has no stats.
+    #[account(
+        seeds = [initializer.key.as_ref(), fee_token.key.as_ref(), USER_STATS_SEED],
+        bump,
+    )]
+    pub maker_stats: AccountInfo<'info>,
+
+    /// CHECK
+    /// May be empty if the initializer doesn't stake.
+    #[account(
+        seeds = [initializer.key.as_ref(), fee_token.key.as_ref(), STAKE_SEED],
+        bump,
+    )]
+    pub maker_stake: AccountInfo<'info>,
+
+    /// CHECK
+    /// The fee config's price feed, any account while USD fees are off.
+    #[account(constraint = !pda_account.usd_fees.enabled() || pda_account.usd_fees.price_feed == *price_feed.key)]
+    pub price_feed: AccountInfo<'info>,
+
+    /// CHECK
+    /// May be empty if the initializer never created open orders.
+    #[account(
+        mut,
+        seeds = [initializer.key.as_ref(), OPEN_ORDERS_SEED],
+        bump,
+    )]
+    pub open_orders: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(
+        seeds = [ESCROW_PDA_SEED, escrow_account.to_account_info().key.as_ref()],
+        bump,
+    )]
+    pub vault_authority: AccountInfo<'info>,
+
     #[account(
         init,
         seeds = [escrow_account.to_account_info().key.as_ref()],
         bump,
         payer = initializer,
         token::mint = deposit_token,
-        token::authority = initializer,
+        token::authority = vault_authority,
     )]
     pub vault_account: Box<Account<'info, TokenAccount>>,
 
@@ -123,35 +778,24 @@ pub struct Initialize<'info> {
 
     /// CHECK
     pub receive_token: AccountInfo<'info>,
+    /// Proceeds recipient, may belong to someone else.
     #[account(
         constraint = *initializer_receive_token_account.to_account_info().owner == *token_program.key,
         constraint = initializer_receive_token_account.mint == *receive_token.key,
-        constraint = initializer_receive_token_account.owner == *initializer.key
     )]
     pub initializer_receive_token_account: Box<Account<'info, TokenAccount>>,
 
-    // fee collecting
-    #[account(
-        constraint = initializer_fee_paying_token_account.mint == pda_account.fee_token,
-        constraint = *fee_collect_token_account.to_account_info().owner == *token_program.key,
-    )]
-    pub fee_collect_token_account: Box<Account<'info, TokenAccount>>,
-
-    /// CHECK
     #[account(
-        mut,
-        constraint = *vault_fee_account.key == pda_account.vault_fee_account,
+        constraint = *refund_token_account.to_account_info().owner == *token_program.key,
+        constraint = refund_token_account.mint == *deposit_token.key,
     )]
-    pub vault_fee_account: AccountInfo<'info>,
+    pub refund_token_account: Box<Account<'info, TokenAccount>>,
 
+    // fee collecting
     #[account(
-        mut,
-        constraint = *initializer_fee_paying_token_account.to_account_info().owner == *token_program.key,
-        constraint = initializer_fee_paying_token_account.mint == pda_account.fee_token,
-//        constraint = initializer_fee_paying_token_account.amount >= fee_amount_initializer,
-        constraint = initializer_fee_paying_token_account.owner == *initializer.key
+        constraint = *fee_collect_token_account.to_account_info().owner == *token_program.key,
     )]
-    pub initializer_fee_paying_token_account: Box<Account<'info, TokenAccount>>,
+    pub fee_collect_token_account: Box<Account<'info, TokenAccount>>,
 
     /// CHECK
     #[account(address = anchor_spl::token::ID)]
@@ -176,23 +820,14 @@ impl<'info> Initialize<'info> {
         CpiContext::new(self.token_program.clone(), cpi_accounts)
     }
 
-    pub fn into_set_authority_context(&self) -> CpiContext<'_, '_, '_, 'info, SetAuthority<'info>> {
-        let cpi_accounts = SetAuthority {
-            account_or_mint: self.vault_account.to_account_info().clone(),
-            current_authority: self.initializer.clone(),
-        };
-        CpiContext::new(self.token_program.clone(), cpi_accounts)
-    }
-
     pub fn into_transfer_fee_to_vault_fee_context(
         &self,
+        fee_paying: &Account<'info, TokenAccount>,
+        vault_fee_account: &AccountInfo<'info>,
     ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
         let cpi_accounts = Transfer {
-            from: self
-                .initializer_fee_paying_token_account
-                .to_account_info()
-                .clone(),
-            to: self.vault_fee_account.to_account_info().clone(),
+            from: fee_paying.to_account_info(),
+            to: vault_fee_account.clone(),
             authority: self.initializer.clone(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
@@ -203,7 +838,8 @@ impl<'info> Initialize<'info> {
 #[derive(Accounts)]
 pub struct Cancel<'info> {
     /// CHECK
-    #[account(mut, signer)]
+    /// Has to sign unless the escrow has expired.
+    #[account(mut)]
     pub initializer: AccountInfo<'info>,
 
     #[account(
@@ -215,10 +851,26 @@ pub struct Cancel<'info> {
     #[account(
         mut,
         constraint = escrow_account.initializer_key == *initializer.key,
-        close = initializer
     )]
     pub escrow_account: ProgramAccount<'info, EscrowAccount>,
 
+    /// CHECK
+    /// May be empty if the initializer never created open orders.
+    #[account(
+        mut,
+        seeds = [initializer.key.as_ref(), OPEN_ORDERS_SEED],
+        bump,
+    )]
+    pub open_orders: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(
+        mut,
+        seeds = [min(&escrow_account.deposit_token, &escrow_account.receive_token).as_ref(), max(&escrow_account.deposit_token, &escrow_account.receive_token).as_ref(), MARKET_SEED],
+        bump,
+    )]
+    pub market: AccountInfo<'info>,
+
     #[account(mut,
         constraint = escrow_account.vault_account == *vault_account.to_account_info().key,
     )]
@@ -231,9 +883,9 @@ pub struct Cancel<'info> {
     pub vault_authority: AccountInfo<'info>,
 
     #[account(mut,
-        constraint = escrow_account.initializer_deposit_token_account == *initializer_deposit_token_account.to_account_info().key,
+        constraint = escrow_account.refund_token_account == *refund_token_account.to_account_info().key,
     )]
-    pub initializer_deposit_token_account: Box<Account<'info, TokenAccount>>,
+    pub refund_token_account: Box<Account<'info, TokenAccount>>,
 
     #[account(mut,
         constraint = escrow_account.initializer_fee_paying_token_account == *initializer_fee_paying_token_account.to_account_info().key,
@@ -244,7 +896,7 @@ pub struct Cancel<'info> {
     /// CHECK
     #[account(
         mut,
-        constraint = *vault_fee_account.key == pda_account.vault_fee_account,
+        constraint = pda_account.fee_vault(&escrow_account.initializer_fee_mint) == Some(*vault_fee_account.key),
     )]
     pub vault_fee_account: AccountInfo<'info>,
 
@@ -257,6 +909,10 @@ pub struct Cancel<'info> {
     /// CHECK
     #[account(address = anchor_spl::token::ID)]
     pub token_program: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(address = anchor_lang::solana_program::system_program::ID)]
+    pub system_program: AccountInfo<'info>,
 }
 
 impl<'info> Cancel<'info> {
@@ -265,10 +921,7 @@ impl<'info> Cancel<'info> {
     ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
         let cpi_accounts = Transfer {
             from: self.vault_account.to_account_info().clone(),
-            to: self
-                .initializer_deposit_token_account
-                .to_account_info()
-                .clone(),
+            to: self.refund_token_account.to_account_info().clone(),
             authority: self.vault_authority.clone(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
@@ -298,7 +951,112 @@ impl<'info> Cancel<'info> {
     }
 }
 
+///////////Close escrow///////////////////
+#[derive(Accounts)]
+pub struct CloseEscrow<'info> {
+    /// CHECK
+    #[account(mut, signer)]
+    pub initializer: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        constraint = escrow_account.initializer_key == *initializer.key,
+        close = initializer
+    )]
+    pub escrow_account: ProgramAccount<'info, EscrowAccount>,
+}
+
 ///////////Exchange///////////////////
+/// Optional accounts a taker passes to `exchange`. Those set lead its
+/// remaining accounts, in field order.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExchangeExtras {
+    /// The taker's fee paying token account, required unless the escrow
+    /// deducts its fees.
+    pub taker_fee_paying: bool,
+    /// The referrer's fee token account, then their referrer stats.
+    pub referrer: bool,
+    /// The escrow's referrer's fee token account, then their referrer
+    /// stats. Required when the escrow has a referrer.
+    pub maker_referrer: bool,
+    /// Both required while the fee config has fee tiers.
+    pub maker_stats: bool,
+    pub taker_stats: bool,
+    pub taker_stake: bool,
+    /// Required while the fee config has USD fees on.
+    pub price_feed: bool,
+    /// Fresh keypairs signing the transaction, which become settlement
+    /// receipts of the taker and of the maker. The taker pays their rent.
+    pub taker_receipt: bool,
+    pub maker_receipt: bool,
+    /// Pays the maker rebate, required when the escrow has one and the fee
+    /// config has a revenue vault.
+    pub fee_revenue_vault: bool,
+}
+
+/// The accounts an `ExchangeExtras` asked for.
+pub struct ExtraAccounts<'a, 'info> {
+    pub taker_fee_paying: Option<&'a AccountInfo<'info>>,
+    pub referrer: Option<(&'a AccountInfo<'info>, &'a AccountInfo<'info>)>,
+    pub maker_referrer: Option<(&'a AccountInfo<'info>, &'a AccountInfo<'info>)>,
+    pub maker_stats: Option<&'a AccountInfo<'info>>,
+    pub taker_stats: Option<&'a AccountInfo<'info>>,
+    pub taker_stake: Option<&'a AccountInfo<'info>>,
+    pub price_feed: Option<&'a AccountInfo<'info>>,
+    pub taker_receipt: Option<&'a AccountInfo<'info>>,
+    pub maker_receipt: Option<&'a AccountInfo<'info>>,
+    pub fee_revenue_vault: Option<&'a AccountInfo<'info>>,
+}
+
+impl ExchangeExtras {
+    /// How many remaining accounts these take.
+    pub fn accounts_len(&self) -> usize {
+        [
+            self.taker_fee_paying,
+            self.referrer,
+            self.referrer,
+            self.maker_referrer,
+            self.maker_referrer,
+            self.maker_stats,
+            self.taker_stats,
+            self.taker_stake,
+            self.price_feed,
+            self.taker_receipt,
+            self.maker_receipt,
+            self.fee_revenue_vault,
+        ]
+        .iter()
+        .filter(|set| **set)
+        .count()
+    }
+
+    /// Splits the requested accounts off the head of `accounts`.
+    pub fn take<'a, 'info>(
+        &self,
+        accounts: &'a [AccountInfo<'info>],
+    ) -> Result<(ExtraAccounts<'a, 'info>, &'a [AccountInfo<'info>]), ProgramError> {
+        if accounts.len() < self.accounts_len() {
+            return Err(ScrtEscrowErrors::InvalidRemainingAccounts.into());
+        }
+        let (head, rest) = accounts.split_at(self.accounts_len());
+        let mut head = head.iter();
+        let mut next = |set: bool| if set { head.next() } else { None };
+        let extras = ExtraAccounts {
+            taker_fee_paying: next(self.taker_fee_paying),
+            referrer: next(self.referrer).zip(next(self.referrer)),
+            maker_referrer: next(self.maker_referrer).zip(next(self.maker_referrer)),
+            maker_stats: next(self.maker_stats),
+            taker_stats: next(self.taker_stats),
+            taker_stake: next(self.taker_stake),
+            price_feed: next(self.price_feed),
+            taker_receipt: next(self.taker_receipt),
+            maker_receipt: next(self.maker_receipt),
+            fee_revenue_vault: next(self.fee_revenue_vault),
+        };
+        Ok((extras, rest))
+    }
+}
+
 #[derive(Accounts)]
 pub struct Exchange<'info> {
     /// CHECK
@@ -318,10 +1076,35 @@ pub struct Exchange<'info> {
     #[account(
         mut,
         constraint = escrow_account.initializer_key == *initializer.key,
-        close = initializer
     )]
     pub escrow_account: ProgramAccount<'info, EscrowAccount>,
 
+    /// CHECK
+    /// May be empty if the initializer never created open orders.
+    #[account(
+        mut,
+        seeds = [initializer.key.as_ref(), OPEN_ORDERS_SEED],
+        bump,
+    )]
+    pub open_orders: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(
+        mut,
+        seeds = [min(&escrow_account.deposit_token, &escrow_account.receive_token).as_ref(), max(&escrow_account.deposit_token, &escrow_account.receive_token).as_ref(), MARKET_SEED],
+        bump,
+    )]
+    pub market: AccountInfo<'info>,
+
+    /// CHECK
+    /// The pair's price history, whether or not anyone has created it.
+    #[account(
+        mut,
+        seeds = [min(&escrow_account.deposit_token, &escrow_account.receive_token).as_ref(), max(&escrow_account.deposit_token, &escrow_account.receive_token).as_ref(), PRICE_HISTORY_SEED],
+        bump,
+    )]
+    pub price_history: AccountInfo<'info>,
+
     #[account(mut,
         constraint = taker_deposit_token_account.owner == *taker.key,
         constraint = taker_deposit_token_account.mint == escrow_account.receive_token,
@@ -330,12 +1113,16 @@ pub struct Exchange<'info> {
     )]
     pub taker_deposit_token_account: Box<Account<'info, TokenAccount>>,
 
-    #[account(mut,
-        constraint = taker_receive_token_account.owner == *taker.key,
-        constraint = taker_receive_token_account.mint == escrow_account.deposit_token,
-        constraint = *taker_receive_token_account.to_account_info().owner == *token_program.key,
+    /// CHECK
+    /// Created as the taker's associated token account if it doesn't exist.
+    #[account(mut)]
+    pub taker_receive_token_account: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(
+        constraint = *deposit_token.key == escrow_account.deposit_token
     )]
-    pub taker_receive_token_account: Box<Account<'info, TokenAccount>>,
+    pub deposit_token: AccountInfo<'info>,
 
     /// CHECK
     #[account(mut,
@@ -343,6 +1130,20 @@ pub struct Exchange<'info> {
     )]
     pub initializer_receive_token_account: AccountInfo<'info>,
 
+    /// CHECK
+    #[account(
+        constraint = *receive_token.key == escrow_account.receive_token
+    )]
+    pub receive_token: AccountInfo<'info>,
+
+    /// CHECK
+    /// Takes the proceeds when the receive account can't, created on demand.
+    #[account(mut,
+        seeds = [initializer.key.as_ref(), receive_token.key.as_ref(), CLAIM_SEED],
+        bump,
+    )]
+    pub claim_account: AccountInfo<'info>,
+
     /// CHECK
     #[account(mut,
         constraint = escrow_account.vault_account == *vault_account.key,
@@ -358,7 +1159,7 @@ pub struct Exchange<'info> {
     // fee collecting
     /// CHECK
     #[account(mut,
-        constraint = pda_account.vault_fee_account == *vault_fee_account.key,
+        constraint = pda_account.fee_vault(&escrow_account.initializer_fee_mint) == Some(*vault_fee_account.key),
     )]
     pub vault_fee_account: AccountInfo<'info>,
 
@@ -369,45 +1170,88 @@ pub struct Exchange<'info> {
     pub vault_fee_authority: AccountInfo<'info>,
 
     /// CHECK
+    /// Collector of the initializer's fee mint, or of the deposit mint in
+    /// deducted fee mode.
     #[account(mut,
-        constraint = *fee_collect_token_account.key == escrow_account.fee_collect_token_account,
+        constraint = escrow_account.fee_deducted
+            || pda_account.fee_collector(&escrow_account.initializer_fee_mint, &escrow_account.fee_collect_token_account)
+                == Some(*initializer_fee_collect_token_account.key),
     )]
-    pub fee_collect_token_account: AccountInfo<'info>,
+    pub initializer_fee_collect_token_account: AccountInfo<'info>,
 
+    /// CHECK
+    /// Collector of the taker's fee mint, or of the receive mint in
+    /// deducted fee mode.
+    #[account(mut)]
+    pub taker_fee_collect_token_account: AccountInfo<'info>,
+
+    /// CHECK
+    /// Takes the maker rebate, if any.
     #[account(mut,
-        constraint = taker_fee_paying_token_account.owner == *taker.key,
-        constraint = taker_fee_paying_token_account.mint == pda_account.fee_token,
-        constraint = taker_fee_paying_token_account.amount >= escrow_account.fee_amount_taker,
+        constraint = *initializer_fee_paying_token_account.key == escrow_account.initializer_fee_paying_token_account
     )]
-    pub taker_fee_paying_token_account: Box<Account<'info, TokenAccount>>,
+    pub initializer_fee_paying_token_account: AccountInfo<'info>,
 
     /// CHECK
+    #[account(address = anchor_spl::token::ID)]
     pub token_program: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(address = spl_associated_token_account::ID)]
+    pub associated_token_program: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(address = anchor_lang::solana_program::system_program::ID)]
+    pub system_program: AccountInfo<'info>,
+
+    pub rent: Sysvar<'info, Rent>,
 }
 
 impl<'info> Exchange<'info> {
-    pub fn into_transfer_to_initializer_context(
+    pub fn into_transfer_referral_from_taker_context(
         &self,
+        taker_fee_paying: &Account<'info, TokenAccount>,
+        referrer_token_account: &AccountInfo<'info>,
     ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
         let cpi_accounts = Transfer {
-            from: self.taker_deposit_token_account.to_account_info().clone(),
-            to: self
-                .initializer_receive_token_account
-                .to_account_info()
-                .clone(),
+            from: taker_fee_paying.to_account_info(),
+            to: referrer_token_account.clone(),
             authority: self.taker.clone(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
     }
+
+    pub fn into_transfer_referral_from_vault_context(
+        &self,
+        referrer_token_account: &AccountInfo<'info>,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault_fee_account.to_account_info().clone(),
+            to: referrer_token_account.clone(),
+            authority: self.vault_fee_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    pub fn into_transfer_rebate_context(
+        &self,
+        fee_revenue_vault: &AccountInfo<'info>,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: fee_revenue_vault.clone(),
+            to: self.initializer_fee_paying_token_account.clone(),
+            authority: self.vault_fee_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
     pub fn into_transfer_fee_from_taker_context(
         &self,
+        taker_fee_paying: &Account<'info, TokenAccount>,
     ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
         let cpi_accounts = Transfer {
-            from: self
-                .taker_fee_paying_token_account
-                .to_account_info()
-                .clone(),
-            to: self.fee_collect_token_account.to_account_info().clone(),
+            from: taker_fee_paying.to_account_info(),
+            to: self.taker_fee_collect_token_account.clone(),
             authority: self.taker.clone(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
@@ -416,7 +1260,29 @@ impl<'info> Exchange<'info> {
     pub fn into_transfer_to_taker_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
         let cpi_accounts = Transfer {
             from: self.vault_account.to_account_info().clone(),
-            to: self.taker_receive_token_account.to_account_info().clone(),
+            to: self.taker_receive_token_account.clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    pub fn into_transfer_deducted_fee_from_taker_context(
+        &self,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.taker_deposit_token_account.to_account_info().clone(),
+            to: self.taker_fee_collect_token_account.clone(),
+            authority: self.taker.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    pub fn into_transfer_deducted_fee_from_vault_context(
+        &self,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault_account.to_account_info().clone(),
+            to: self.initializer_fee_collect_token_account.clone(),
             authority: self.vault_authority.clone(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
@@ -427,7 +1293,7 @@ impl<'info> Exchange<'info> {
     ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
         let cpi_accounts = Transfer {
             from: self.vault_fee_account.to_account_info().clone(),
-            to: self.fee_collect_token_account.to_account_info().clone(),
+            to: self.initializer_fee_collect_token_account.clone(),
             authority: self.vault_fee_authority.clone(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
@@ -442,3 +1308,1278 @@ impl<'info> Exchange<'info> {
         CpiContext::new(self.token_program.clone(), cpi_accounts)
     }
 }
+
+///////////Flash exchange///////////////////
+/// Remaining accounts are forwarded to `callback_program`.
+#[derive(Accounts)]
+pub struct FlashExchange<'info> {
+    /// CHECK
+    #[account(mut, signer)]
+    pub taker: AccountInfo<'info>,
+
+    #[account(
+        seeds = [program_id.as_ref(), escrow_account.fee_token.as_ref(), PDA_SEED],
+        bump,
+    )]
+    pub pda_account: ProgramAccount<'info, PdaAccount>,
+
+    /// CHECK
+    #[account(mut)]
+    pub initializer: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        constraint = escrow_account.initializer_key == *initializer.key,
+    )]
+    pub escrow_account: ProgramAccount<'info, EscrowAccount>,
+
+    /// CHECK
+    /// May be empty if the initializer never created open orders.
+    #[account(
+        mut,
+        seeds = [initializer.key.as_ref(), OPEN_ORDERS_SEED],
+        bump,
+    )]
+    pub open_orders: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(
+        mut,
+        seeds = [min(&escrow_account.deposit_token, &escrow_account.receive_token).as_ref(), max(&escrow_account.deposit_token, &escrow_account.receive_token).as_ref(), MARKET_SEED],
+        bump,
+    )]
+    pub market: AccountInfo<'info>,
+
+    /// CHECK
+    /// The pair's price history, whether or not anyone has created it.
+    #[account(
+        mut,
+        seeds = [min(&escrow_account.deposit_token, &escrow_account.receive_token).as_ref(), max(&escrow_account.deposit_token, &escrow_account.receive_token).as_ref(), PRICE_HISTORY_SEED],
+        bump,
+    )]
+    pub price_history: AccountInfo<'info>,
+
+    #[account(mut,
+        constraint = taker_receive_token_account.mint == escrow_account.deposit_token,
+        constraint = *taker_receive_token_account.to_account_info().owner == *token_program.key,
+    )]
+    pub taker_receive_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK
+    /// The callback leaves the payment here, the maker is paid from it.
+    #[account(mut,
+        constraint = taker_deposit_token_account.owner == *taker.key,
+        constraint = taker_deposit_token_account.mint == escrow_account.receive_token,
+        constraint = *taker_deposit_token_account.to_account_info().owner == *token_program.key,
+    )]
+    pub taker_deposit_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK
+    #[account(mut,
+        constraint = *initializer_receive_token_account.key == escrow_account.initializer_receive_token_account
+    )]
+    pub initializer_receive_token_account: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(
+        constraint = *receive_token.key == escrow_account.receive_token
+    )]
+    pub receive_token: AccountInfo<'info>,
+
+    /// CHECK
+    /// Takes the proceeds when the receive account can't, created on demand.
+    #[account(mut,
+        seeds = [initializer.key.as_ref(), receive_token.key.as_ref(), CLAIM_SEED],
+        bump,
+    )]
+    pub claim_account: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(mut,
+        constraint = escrow_account.vault_account == *vault_account.key,
+    )]
+    pub vault_account: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(
+        constraint = escrow_account.vault_authority == *vault_authority.key,
+    )]
+    pub vault_authority: AccountInfo<'info>,
+
+    // fee collecting
+    /// CHECK
+    #[account(mut,
+        constraint = pda_account.vault_fee_account == *vault_fee_account.key,
+    )]
+    pub vault_fee_account: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(
+        constraint = pda_account.vault_fee_authority == *vault_fee_authority.key,
+    )]
+    pub vault_fee_authority: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(mut,
+        constraint = pda_account.fee_collector(&pda_account.fee_token, &escrow_account.fee_collect_token_account)
+            == Some(*fee_collect_token_account.key),
+    )]
+    pub fee_collect_token_account: AccountInfo<'info>,
+
+    #[account(mut,
+        constraint = taker_fee_paying_token_account.owner == *taker.key,
+        constraint = taker_fee_paying_token_account.mint == pda_account.fee_token,
+    )]
+    pub taker_fee_paying_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK
+    /// Empty if the taker doesn't stake.
+    #[account(
+        seeds = [taker.key.as_ref(), escrow_account.fee_token.as_ref(), STAKE_SEED],
+        bump,
+    )]
+    pub taker_stake: AccountInfo<'info>,
+
+    /// CHECK
+    /// The fee config's price feed, any account while USD fees are off.
+    pub price_feed: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(executable)]
+    pub callback_program: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(address = anchor_lang::solana_program::system_program::ID)]
+    pub system_program: AccountInfo<'info>,
+
+    pub rent: Sysvar<'info, Rent>,
+}
+
+impl<'info> FlashExchange<'info> {
+    pub fn into_transfer_to_taker_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault_account.clone(),
+            to: self.taker_receive_token_account.to_account_info().clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    pub fn into_transfer_fee_from_taker_context(
+        &self,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self
+                .taker_fee_paying_token_account
+                .to_account_info()
+                .clone(),
+            to: self.fee_collect_token_account.clone(),
+            authority: self.taker.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    pub fn into_transfer_fee_from_vault_context(
+        &self,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault_fee_account.clone(),
+            to: self.fee_collect_token_account.clone(),
+            authority: self.vault_fee_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    pub fn into_close_vault_context(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+        let cpi_accounts = CloseAccount {
+            account: self.vault_account.clone(),
+            destination: self.initializer.clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+///////////Referrals///////////////////
+#[derive(Accounts)]
+pub struct UpdateReferralShare<'info> {
+    /// CHECK
+    #[account(signer)]
+    pub admin: AccountInfo<'info>,
+
+    #[account(mut, constraint = pda_account.initializer_key == *admin.key)]
+    pub pda_account: ProgramAccount<'info, PdaAccount>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeReferrer<'info> {
+    /// CHECK
+    #[account(mut, signer)]
+    pub owner: AccountInfo<'info>,
+
+    /// CHECK
+    pub fee_token: AccountInfo<'info>,
+
+    #[account(init,
+        seeds = [owner.key.as_ref(), fee_token.key.as_ref(), REFERRER_SEED],
+        bump,
+        payer = owner,
+        space = ReferrerStats::SPACE
+    )]
+    pub referrer_stats: ProgramAccount<'info, ReferrerStats>,
+
+    /// CHECK
+    pub system_program: AccountInfo<'info>,
+}
+
+///////////Fee tiers///////////////////
+#[derive(Accounts)]
+pub struct UpdateFeeTiers<'info> {
+    /// CHECK
+    #[account(signer)]
+    pub admin: AccountInfo<'info>,
+
+    #[account(mut, constraint = pda_account.initializer_key == *admin.key)]
+    pub pda_account: ProgramAccount<'info, PdaAccount>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeUserStats<'info> {
+    /// CHECK
+    #[account(mut, signer)]
+    pub owner: AccountInfo<'info>,
+
+    /// CHECK
+    pub fee_token: AccountInfo<'info>,
+
+    #[account(init,
+        seeds = [owner.key.as_ref(), fee_token.key.as_ref(), USER_STATS_SEED],
+        bump,
+        payer = owner,
+        space = UserStats::SPACE
+    )]
+    pub user_stats: ProgramAccount<'info, UserStats>,
+
+    /// CHECK
+    pub system_program: AccountInfo<'info>,
+}
+
+///////////Staking///////////////////
+#[derive(Accounts)]
+pub struct InitializeStakeVault<'info> {
+    /// CHECK
+    #[account(mut, signer)]
+    pub admin: AccountInfo<'info>,
+
+    #[account(mut, constraint = pda_account.initializer_key == *admin.key)]
+    pub pda_account: ProgramAccount<'info, PdaAccount>,
+
+    /// CHECK
+    #[account(address = pda_account.fee_token)]
+    pub fee_token: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(address = pda_account.vault_fee_authority)]
+    pub vault_fee_authority: AccountInfo<'info>,
+
+    #[account(
+        init,
+        seeds = [pda_account.to_account_info().key.as_ref(), STAKE_VAULT_SEED],
+        bump,
+        payer = admin,
+        token::mint = fee_token,
+        token::authority = vault_fee_authority,
+    )]
+    pub stake_vault: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+
+    /// CHECK
+    pub system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateStakeConfig<'info> {
+    /// CHECK
+    #[account(signer)]
+    pub admin: AccountInfo<'info>,
+
+    #[account(mut, constraint = pda_account.initializer_key == *admin.key)]
+    pub pda_account: ProgramAccount<'info, PdaAccount>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeStakeAccount<'info> {
+    /// CHECK
+    #[account(mut, signer)]
+    pub owner: AccountInfo<'info>,
+
+    /// CHECK
+    pub fee_token: AccountInfo<'info>,
+
+    #[account(init,
+        seeds = [owner.key.as_ref(), fee_token.key.as_ref(), STAKE_SEED],
+        bump,
+        payer = owner,
+        space = StakeAccount::SPACE
+    )]
+    pub stake_account: ProgramAccount<'info, StakeAccount>,
+
+    /// CHECK
+    pub system_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Stake<'info> {
+    /// CHECK
+    #[account(signer)]
+    pub owner: AccountInfo<'info>,
+
+    #[account(
+        seeds = [program_id.as_ref(), stake_account.fee_token.as_ref(), PDA_SEED],
+        bump,
+    )]
+    pub pda_account: ProgramAccount<'info, PdaAccount>,
+
+    #[account(mut, constraint = stake_account.owner == *owner.key)]
+    pub stake_account: ProgramAccount<'info, StakeAccount>,
+
+    /// CHECK
+    #[account(mut, address = pda_account.stake_vault)]
+    pub stake_vault: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(mut)]
+    pub source_token_account: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> Stake<'info> {
+    pub fn into_transfer_to_stake_vault_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.source_token_account.clone(),
+            to: self.stake_vault.clone(),
+            authority: self.owner.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+#[derive(Accounts)]
+pub struct Unstake<'info> {
+    /// CHECK
+    #[account(signer)]
+    pub owner: AccountInfo<'info>,
+
+    #[account(
+        seeds = [program_id.as_ref(), stake_account.fee_token.as_ref(), PDA_SEED],
+        bump,
+    )]
+    pub pda_account: ProgramAccount<'info, PdaAccount>,
+
+    #[account(mut, constraint = stake_account.owner == *owner.key)]
+    pub stake_account: ProgramAccount<'info, StakeAccount>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawStake<'info> {
+    /// CHECK
+    #[account(signer)]
+    pub owner: AccountInfo<'info>,
+
+    #[account(
+        seeds = [program_id.as_ref(), stake_account.fee_token.as_ref(), PDA_SEED],
+        bump,
+    )]
+    pub pda_account: ProgramAccount<'info, PdaAccount>,
+
+    #[account(mut, constraint = stake_account.owner == *owner.key)]
+    pub stake_account: ProgramAccount<'info, StakeAccount>,
+
+    /// CHECK
+    #[account(mut, address = pda_account.stake_vault)]
+    pub stake_vault: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(address = pda_account.vault_fee_authority)]
+    pub vault_fee_authority: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(mut)]
+    pub destination_token_account: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> WithdrawStake<'info> {
+    pub fn into_transfer_from_stake_vault_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.stake_vault.clone(),
+            to: self.destination_token_account.clone(),
+            authority: self.vault_fee_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+///////////Fee distribution///////////////////
+#[derive(Accounts)]
+pub struct InitializeFeeDistribution<'info> {
+    /// CHECK
+    #[account(mut, signer)]
+    pub admin: AccountInfo<'info>,
+
+    #[account(mut, constraint = pda_account.initializer_key == *admin.key)]
+    pub pda_account: ProgramAccount<'info, PdaAccount>,
+
+    /// CHECK
+    #[account(address = pda_account.fee_token)]
+    pub fee_token: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(address = pda_account.vault_fee_authority)]
+    pub vault_fee_authority: AccountInfo<'info>,
+
+    #[account(
+        init,
+        seeds = [pda_account.to_account_info().key.as_ref(), FEE_REVENUE_SEED],
+        bump,
+        payer = admin,
+        token::mint = fee_token,
+        token::authority = vault_fee_authority,
+    )]
+    pub fee_revenue_vault: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+
+    /// CHECK
+    pub system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateFeeDistribution<'info> {
+    /// CHECK
+    #[account(signer)]
+    pub admin: AccountInfo<'info>,
+
+    #[account(mut, constraint = pda_account.initializer_key == *admin.key)]
+    pub pda_account: ProgramAccount<'info, PdaAccount>,
+}
+
+/// Remaining accounts are the distribution's destination token accounts,
+/// in table order. For a revenue vault in another mint than the fee token
+/// they are followed by the associated token accounts of their owners for
+/// that mint, which get paid instead. Anyone may call it.
+#[derive(Accounts)]
+pub struct DistributeFees<'info> {
+    pub pda_account: ProgramAccount<'info, PdaAccount>,
+
+    /// The revenue vault of any mint fees collect in.
+    #[account(mut,
+        constraint = pda_account.distributes_fees(),
+        constraint = pda_account.revenue_vault(&fee_revenue_vault.mint) == *fee_revenue_vault.to_account_info().key,
+    )]
+    pub fee_revenue_vault: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK
+    #[account(address = pda_account.vault_fee_authority)]
+    pub vault_fee_authority: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> DistributeFees<'info> {
+    pub fn into_transfer_context(&self, to: AccountInfo<'info>) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.fee_revenue_vault.to_account_info().clone(),
+            to,
+            authority: self.vault_fee_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+///////////Fee mints///////////////////
+#[derive(Accounts)]
+pub struct AddFeeMint<'info> {
+    /// CHECK
+    #[account(mut, signer)]
+    pub admin: AccountInfo<'info>,
+
+    #[account(mut, constraint = pda_account.initializer_key == *admin.key)]
+    pub pda_account: ProgramAccount<'info, PdaAccount>,
+
+    /// CHECK
+    pub mint: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(address = pda_account.vault_fee_authority)]
+    pub vault_fee_authority: AccountInfo<'info>,
+
+    #[account(
+        init,
+        seeds = [pda_account.to_account_info().key.as_ref(), mint.key.as_ref(), FEE_VAULT_SEED],
+        bump,
+        payer = admin,
+        token::mint = mint,
+        token::authority = vault_fee_authority,
+    )]
+    pub fee_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(constraint = collector.mint == *mint.key)]
+    pub collector: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+
+    /// CHECK
+    pub system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateFeeMint<'info> {
+    /// CHECK
+    #[account(signer)]
+    pub admin: AccountInfo<'info>,
+
+    #[account(mut, constraint = pda_account.initializer_key == *admin.key)]
+    pub pda_account: ProgramAccount<'info, PdaAccount>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateDeductedFees<'info> {
+    /// CHECK
+    #[account(signer)]
+    pub admin: AccountInfo<'info>,
+
+    #[account(mut, constraint = pda_account.initializer_key == *admin.key)]
+    pub pda_account: ProgramAccount<'info, PdaAccount>,
+}
+
+///////////USD fees///////////////////
+#[derive(Accounts)]
+pub struct UpdateUsdFees<'info> {
+    /// CHECK
+    #[account(signer)]
+    pub admin: AccountInfo<'info>,
+
+    #[account(mut, constraint = pda_account.initializer_key == *admin.key)]
+    pub pda_account: ProgramAccount<'info, PdaAccount>,
+
+    #[account(address = pda_account.fee_token)]
+    pub fee_token: Account<'info, Mint>,
+}
+
+#[derive(Accounts)]
+pub struct InitializePriceAccount<'info> {
+    /// CHECK
+    #[account(mut, signer)]
+    pub authority: AccountInfo<'info>,
+
+    /// CHECK
+    pub product: AccountInfo<'info>,
+
+    #[account(init,
+        seeds = [authority.key.as_ref(), product.key.as_ref(), PRICE_SEED],
+        bump,
+        payer = authority,
+        space = PriceAccount::SPACE
+    )]
+    pub price_account: ProgramAccount<'info, PriceAccount>,
+
+    /// CHECK
+    pub system_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdatePrice<'info> {
+    /// CHECK
+    #[account(signer)]
+    pub authority: AccountInfo<'info>,
+
+    #[account(mut, constraint = price_account.authority == *authority.key)]
+    pub price_account: ProgramAccount<'info, PriceAccount>,
+}
+
+///////////Price history///////////////////
+#[derive(Accounts)]
+pub struct InitializePriceHistory<'info> {
+    /// CHECK
+    #[account(mut, signer)]
+    pub payer: AccountInfo<'info>,
+
+    /// CHECK
+    pub base_mint: AccountInfo<'info>,
+    /// CHECK
+    pub quote_mint: AccountInfo<'info>,
+
+    #[account(init,
+        seeds = [min(base_mint.key, quote_mint.key).as_ref(), max(base_mint.key, quote_mint.key).as_ref(), PRICE_HISTORY_SEED],
+        bump,
+        payer = payer,
+        space = PriceHistory::SPACE
+    )]
+    pub price_history: Loader<'info, PriceHistory>,
+
+    /// CHECK
+    pub system_program: AccountInfo<'info>,
+}
+
+///////////Claim///////////////////
+#[derive(Accounts)]
+pub struct Claim<'info> {
+    /// CHECK
+    #[account(mut, signer)]
+    pub owner: AccountInfo<'info>,
+
+    /// CHECK
+    pub mint: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(mut,
+        seeds = [owner.key.as_ref(), mint.key.as_ref(), CLAIM_SEED],
+        bump,
+    )]
+    pub claim_account: AccountInfo<'info>,
+
+    #[account(mut,
+        constraint = destination_token_account.mint == *mint.key,
+    )]
+    pub destination_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> Claim<'info> {
+    pub fn into_transfer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.claim_account.clone(),
+            to: self.destination_token_account.to_account_info().clone(),
+            authority: self.claim_account.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    pub fn into_close_context(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+        let cpi_accounts = CloseAccount {
+            account: self.claim_account.clone(),
+            destination: self.owner.clone(),
+            authority: self.claim_account.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+#[derive(Accounts)]
+pub struct UpdateReceiveAccount<'info> {
+    /// CHECK
+    #[account(signer)]
+    pub initializer: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        constraint = escrow_account.initializer_key == *initializer.key,
+    )]
+    pub escrow_account: ProgramAccount<'info, EscrowAccount>,
+
+    #[account(
+        constraint = *initializer_receive_token_account.to_account_info().owner == anchor_spl::token::ID,
+        constraint = initializer_receive_token_account.mint == escrow_account.receive_token,
+    )]
+    pub initializer_receive_token_account: Box<Account<'info, TokenAccount>>,
+}
+
+///////////Batch exchange///////////////////
+/// Remaining accounts carry one group of `BATCH_EXCHANGE_ACCOUNTS` per escrow,
+/// in fill order: escrow, initializer, open_orders, vault, vault_authority,
+/// initializer_receive_token_account, fee_collect_token_account and the
+/// initializer's claim account for the receive mint.
+#[derive(Accounts)]
+pub struct BatchExchange<'info> {
+    /// CHECK
+    #[account(mut, signer)]
+    pub taker: AccountInfo<'info>,
+
+    pub pda_account: ProgramAccount<'info, PdaAccount>,
+
+    /// CHECK
+    pub deposit_token: AccountInfo<'info>,
+    /// CHECK
+    pub receive_token: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(
+        mut,
+        seeds = [min(deposit_token.key, receive_token.key).as_ref(), max(deposit_token.key, receive_token.key).as_ref(), MARKET_SEED],
+        bump,
+    )]
+    pub market: AccountInfo<'info>,
+
+    /// CHECK
+    /// The pair's price history, whether or not anyone has created it.
+    #[account(
+        mut,
+        seeds = [min(deposit_token.key, receive_token.key).as_ref(), max(deposit_token.key, receive_token.key).as_ref(), PRICE_HISTORY_SEED],
+        bump,
+    )]
+    pub price_history: AccountInfo<'info>,
+
+    #[account(mut,
+        constraint = taker_deposit_token_account.owner == *taker.key,
+        constraint = taker_deposit_token_account.mint == *receive_token.key,
+    )]
+    pub taker_deposit_token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut,
+        constraint = taker_receive_token_account.mint == *deposit_token.key,
+    )]
+    pub taker_receive_token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut,
+        constraint = taker_fee_paying_token_account.owner == *taker.key,
+        constraint = taker_fee_paying_token_account.mint == pda_account.fee_token,
+    )]
+    pub taker_fee_paying_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK
+    /// Empty if the taker doesn't stake.
+    #[account(
+        seeds = [taker.key.as_ref(), pda_account.fee_token.as_ref(), STAKE_SEED],
+        bump,
+    )]
+    pub taker_stake: AccountInfo<'info>,
+
+    /// CHECK
+    /// The fee config's price feed, any account while USD fees are off.
+    pub price_feed: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(mut,
+        constraint = pda_account.vault_fee_account == *vault_fee_account.key,
+    )]
+    pub vault_fee_account: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(
+        constraint = pda_account.vault_fee_authority == *vault_fee_authority.key,
+    )]
+    pub vault_fee_authority: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(address = anchor_lang::solana_program::system_program::ID)]
+    pub system_program: AccountInfo<'info>,
+
+    pub rent: Sysvar<'info, Rent>,
+}
+
+impl<'info> BatchExchange<'info> {
+    pub fn into_transfer_context(
+        &self,
+        from: AccountInfo<'info>,
+        to: AccountInfo<'info>,
+        authority: AccountInfo<'info>,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from,
+            to,
+            authority,
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    pub fn into_close_vault_context(
+        &self,
+        account: AccountInfo<'info>,
+        destination: AccountInfo<'info>,
+        authority: AccountInfo<'info>,
+    ) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+        let cpi_accounts = CloseAccount {
+            account,
+            destination,
+            authority,
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+///////////Batch cancel///////////////////
+/// Remaining accounts carry one group of `BATCH_CANCEL_ACCOUNTS` per escrow:
+/// escrow, vault, vault_authority, refund_token_account,
+/// initializer_fee_paying_token_account, market.
+#[derive(Accounts)]
+pub struct BatchCancel<'info> {
+    /// CHECK
+    #[account(mut, signer)]
+    pub initializer: AccountInfo<'info>,
+
+    pub pda_account: ProgramAccount<'info, PdaAccount>,
+
+    /// CHECK
+    /// May be empty if the initializer never created open orders.
+    #[account(
+        mut,
+        seeds = [initializer.key.as_ref(), OPEN_ORDERS_SEED],
+        bump,
+    )]
+    pub open_orders: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(
+        mut,
+        constraint = *vault_fee_account.key == pda_account.vault_fee_account,
+    )]
+    pub vault_fee_account: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(
+        constraint = *vault_fee_authority.key == pda_account.vault_fee_authority,
+    )]
+    pub vault_fee_authority: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> BatchCancel<'info> {
+    pub fn into_transfer_context(
+        &self,
+        from: AccountInfo<'info>,
+        to: AccountInfo<'info>,
+        authority: AccountInfo<'info>,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from,
+            to,
+            authority,
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    pub fn into_close_vault_context(
+        &self,
+        account: AccountInfo<'info>,
+        authority: AccountInfo<'info>,
+    ) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+        let cpi_accounts = CloseAccount {
+            account,
+            destination: self.initializer.clone(),
+            authority,
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+///////////Ring trade///////////////////
+/// Remaining accounts carry one group of `RING_TRADE_ACCOUNTS` per leg, in
+/// ring order: escrow, initializer, open_orders, vault, vault_authority,
+/// initializer_receive_token_account, refund_token_account,
+/// fee_collect_token_account, market, receive mint, the initializer's
+/// claim account for it, the fee collector of the deposit mint and the
+/// pair's price history. Each
+/// leg's deposit pays the next leg's maker, and the last leg pays the
+/// first. A leg without a taker fee may pass any account as its deposit
+/// mint's collector.
+#[derive(Accounts)]
+pub struct RingTrade<'info> {
+    /// CHECK
+    /// Pays for claim accounts a leg has to open.
+    #[account(mut, signer)]
+    pub cranker: AccountInfo<'info>,
+
+    pub pda_account: ProgramAccount<'info, PdaAccount>,
+
+    /// CHECK
+    /// The fee config's price feed, any account while USD fees are off.
+    pub price_feed: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(
+        mut,
+        constraint = *vault_fee_account.key == pda_account.vault_fee_account,
+    )]
+    pub vault_fee_account: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(
+        constraint = *vault_fee_authority.key == pda_account.vault_fee_authority,
+    )]
+    pub vault_fee_authority: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(address = anchor_lang::solana_program::system_program::ID)]
+    pub system_program: AccountInfo<'info>,
+
+    pub rent: Sysvar<'info, Rent>,
+}
+
+impl<'info> RingTrade<'info> {
+    pub fn into_transfer_context(
+        &self,
+        from: AccountInfo<'info>,
+        to: AccountInfo<'info>,
+        authority: AccountInfo<'info>,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from,
+            to,
+            authority,
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    pub fn into_close_vault_context(
+        &self,
+        account: AccountInfo<'info>,
+        destination: AccountInfo<'info>,
+        authority: AccountInfo<'info>,
+    ) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+        let cpi_accounts = CloseAccount {
+            account,
+            destination,
+            authority,
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+#[derive(Accounts)]
+pub struct BumpOrderEpoch<'info> {
+    /// CHECK
+    #[account(signer)]
+    pub owner: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [owner.key.as_ref(), OPEN_ORDERS_SEED],
+        bump,
+    )]
+    pub open_orders: ProgramAccount<'info, OpenOrders>,
+}
+
+///////////Receipt///////////////////
+#[derive(Accounts)]
+pub struct CloseReceipt<'info> {
+    /// CHECK
+    #[account(signer)]
+    pub owner: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(mut)]
+    pub payer: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        constraint = receipt.owner == *owner.key,
+        constraint = receipt.payer == *payer.key,
+        close = payer
+    )]
+    pub receipt: ProgramAccount<'info, SettlementReceipt>,
+}
+
+///////////Order book///////////////////
+#[derive(Accounts)]
+pub struct InitializeOrderBook<'info> {
+    /// CHECK
+    #[account(mut, signer)]
+    pub admin: AccountInfo<'info>,
+
+    #[account(
+        seeds = [PROGRAM_CONFIG_SEED],
+        bump,
+        constraint = program_config.admin == *admin.key
+    )]
+    pub program_config: ProgramAccount<'info, ProgramConfig>,
+
+    #[account(
+        mut,
+        constraint = market.order_book == Pubkey::default(),
+    )]
+    pub market: ProgramAccount<'info, Market>,
+
+    pub pda_account: ProgramAccount<'info, PdaAccount>,
+
+    #[account(zero)]
+    pub order_book: Loader<'info, OrderBook>,
+
+    /// CHECK
+    #[account(address = market.base_mint)]
+    pub base_mint: AccountInfo<'info>,
+    /// CHECK
+    #[account(address = market.quote_mint)]
+    pub quote_mint: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(
+        seeds = [order_book.to_account_info().key.as_ref(), ORDER_BOOK_SEED],
+        bump,
+    )]
+    pub book_authority: AccountInfo<'info>,
+
+    #[account(
+        init,
+        seeds = [order_book.to_account_info().key.as_ref(), base_mint.key.as_ref()],
+        bump,
+        payer = admin,
+        token::mint = base_mint,
+        token::authority = book_authority,
+    )]
+    pub base_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        init,
+        seeds = [order_book.to_account_info().key.as_ref(), quote_mint.key.as_ref()],
+        bump,
+        payer = admin,
+        token::mint = quote_mint,
+        token::authority = book_authority,
+    )]
+    pub quote_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        constraint = fee_collect_token_account.mint == pda_account.fee_token,
+    )]
+    pub fee_collect_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+
+    /// CHECK
+    pub system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct PlaceOrder<'info> {
+    /// CHECK
+    #[account(mut, signer)]
+    pub owner: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        constraint = market.order_book == *order_book.to_account_info().key,
+    )]
+    pub market: ProgramAccount<'info, Market>,
+
+    #[account(mut)]
+    pub order_book: Loader<'info, OrderBook>,
+
+    #[account(
+        constraint = *pda_account.to_account_info().key == order_book.load()?.fee_config,
+    )]
+    pub pda_account: ProgramAccount<'info, PdaAccount>,
+
+    /// CHECK
+    #[account(mut,
+        constraint = *base_vault.key == order_book.load()?.base_vault,
+    )]
+    pub base_vault: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(mut,
+        constraint = *quote_vault.key == order_book.load()?.quote_vault,
+    )]
+    pub quote_vault: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(
+        seeds = [order_book.to_account_info().key.as_ref(), ORDER_BOOK_SEED],
+        bump,
+    )]
+    pub book_authority: AccountInfo<'info>,
+
+    #[account(mut,
+        constraint = owner_base_token_account.owner == *owner.key,
+        constraint = owner_base_token_account.mint == market.base_mint,
+    )]
+    pub owner_base_token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut,
+        constraint = owner_quote_token_account.owner == *owner.key,
+        constraint = owner_quote_token_account.mint == market.quote_mint,
+    )]
+    pub owner_quote_token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut,
+        constraint = owner_fee_paying_token_account.owner == *owner.key,
+        constraint = owner_fee_paying_token_account.mint == pda_account.fee_token,
+    )]
+    pub owner_fee_paying_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK
+    /// The market pair's price history, whether or not anyone has created it.
+    #[account(
+        mut,
+        seeds = [min(&market.base_mint, &market.quote_mint).as_ref(), max(&market.base_mint, &market.quote_mint).as_ref(), PRICE_HISTORY_SEED],
+        bump,
+    )]
+    pub price_history: AccountInfo<'info>,
+
+    /// CHECK
+    /// Mints of makers' claim accounts.
+    #[account(address = market.base_mint)]
+    pub base_mint: AccountInfo<'info>,
+    /// CHECK
+    #[account(address = market.quote_mint)]
+    pub quote_mint: AccountInfo<'info>,
+    /// CHECK
+    #[account(address = pda_account.fee_token)]
+    pub fee_token: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(mut,
+        constraint = pda_account.vault_fee_account == *vault_fee_account.key,
+    )]
+    pub vault_fee_account: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(
+        constraint = pda_account.vault_fee_authority == *vault_fee_authority.key,
+    )]
+    pub vault_fee_authority: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(mut,
+        constraint = pda_account.fee_collector(&pda_account.fee_token, &order_book.load()?.fee_collect_token_account)
+            == Some(*fee_collect_token_account.key),
+    )]
+    pub fee_collect_token_account: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(address = anchor_lang::solana_program::system_program::ID)]
+    pub system_program: AccountInfo<'info>,
+
+    pub rent: Sysvar<'info, Rent>,
+}
+
+impl<'info> PlaceOrder<'info> {
+    pub fn into_transfer_context(
+        &self,
+        from: AccountInfo<'info>,
+        to: AccountInfo<'info>,
+        authority: AccountInfo<'info>,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from,
+            to,
+            authority,
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+#[derive(Accounts)]
+pub struct CancelOrder<'info> {
+    /// CHECK
+    #[account(signer)]
+    pub owner: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        constraint = market.order_book == *order_book.to_account_info().key,
+    )]
+    pub market: ProgramAccount<'info, Market>,
+
+    #[account(mut)]
+    pub order_book: Loader<'info, OrderBook>,
+
+    #[account(
+        constraint = *pda_account.to_account_info().key == order_book.load()?.fee_config,
+    )]
+    pub pda_account: ProgramAccount<'info, PdaAccount>,
+
+    /// CHECK
+    #[account(mut,
+        constraint = *base_vault.key == order_book.load()?.base_vault,
+    )]
+    pub base_vault: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(mut,
+        constraint = *quote_vault.key == order_book.load()?.quote_vault,
+    )]
+    pub quote_vault: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(
+        seeds = [order_book.to_account_info().key.as_ref(), ORDER_BOOK_SEED],
+        bump,
+    )]
+    pub book_authority: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(mut)]
+    pub refund_token_account: AccountInfo<'info>,
+
+    #[account(mut,
+        constraint = owner_fee_paying_token_account.owner == *owner.key,
+        constraint = owner_fee_paying_token_account.mint == pda_account.fee_token,
+    )]
+    pub owner_fee_paying_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK
+    #[account(mut,
+        constraint = pda_account.vault_fee_account == *vault_fee_account.key,
+    )]
+    pub vault_fee_account: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(
+        constraint = pda_account.vault_fee_authority == *vault_fee_authority.key,
+    )]
+    pub vault_fee_authority: AccountInfo<'info>,
+
+    /// CHECK
+    #[account(address = anchor_spl::token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> CancelOrder<'info> {
+    pub fn into_transfer_context(
+        &self,
+        from: AccountInfo<'info>,
+        to: AccountInfo<'info>,
+        authority: AccountInfo<'info>,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from,
+            to,
+            authority,
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
@@ -0,0 +1,53 @@
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::{self, Create};
+use anchor_spl::token::TokenAccount;
+use spl_associated_token_account::get_associated_token_address;
+
+use crate::error::ScrtEscrowErrors;
+
+pub fn is_canonical(account: &Pubkey, wallet: &Pubkey, mint: &Pubkey) -> bool {
+    *account == get_associated_token_address(wallet, mint)
+}
+
+/// Creates `wallet`'s associated token account for `mint` at `account` if
+/// it doesn't exist yet, paid by `payer`. An existing account is left alone.
+#[allow(clippy::too_many_arguments)]
+pub fn create_if_missing<'info>(
+    account: &AccountInfo<'info>,
+    wallet: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    rent: &AccountInfo<'info>,
+    associated_token_program: &AccountInfo<'info>,
+) -> ProgramResult {
+    if !account.data_is_empty() {
+        return Ok(());
+    }
+    if !is_canonical(account.key, wallet.key, mint.key) {
+        return Err(ScrtEscrowErrors::InvalidAssociatedTokenAccount.into());
+    }
+    associated_token::create(CpiContext::new(
+        associated_token_program.clone(),
+        Create {
+            payer: payer.clone(),
+            associated_token: account.clone(),
+            authority: wallet.clone(),
+            mint: mint.clone(),
+            system_program: system_program.clone(),
+            token_program: token_program.clone(),
+            rent: rent.clone(),
+        },
+    ))
+}
+
+/// Checks that `account` is a token account of `mint`. Its owner is up to
+/// whoever chose it as recipient.
+pub fn check_token_account(account: &AccountInfo, mint: &Pubkey) -> ProgramResult {
+    let token_account = Account::<TokenAccount>::try_from(account)?;
+    if token_account.mint != *mint {
+        return Err(ScrtEscrowErrors::InvalidReceiveTokenAccount.into());
+    }
+    Ok(())
+}
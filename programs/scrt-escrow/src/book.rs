@@ -0,0 +1,181 @@
+use anchor_lang::prelude::*;
+
+use crate::error::ScrtEscrowErrors;
+
+pub const ORDER_BOOK_SEED: &[u8] = b"order-book";
+pub const BOOK_CAPACITY: usize = 32;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Bid,
+    Ask,
+}
+
+impl Side {
+    pub fn opposite(&self) -> Side {
+        match self {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid,
+        }
+    }
+
+    /// True if a resting order at `resting` price crosses an incoming order
+    /// on this side limited at `limit`.
+    pub fn crosses(&self, limit: u64, resting: u64) -> bool {
+        match self {
+            Side::Bid => resting <= limit,
+            Side::Ask => resting >= limit,
+        }
+    }
+
+    /// True if `price` is strictly better than `other` on this side.
+    pub fn beats(&self, price: u64, other: u64) -> bool {
+        match self {
+            Side::Bid => price > other,
+            Side::Ask => price < other,
+        }
+    }
+}
+
+/// A resting order. `quantity` is in base atoms, `price` in quote atoms per lot.
+#[zero_copy]
+#[allow(clippy::repr_packed_without_abi)]
+pub struct BookOrder {
+    pub owner: Pubkey,
+    pub receive_account: Pubkey,
+    pub refund_account: Pubkey,
+    /// Takes back the unused maker fee if the order is evicted.
+    pub fee_account: Pubkey,
+    pub price: u64,
+    pub quantity: u64,
+    pub seq: u64,
+    /// Prepaid maker fee not yet charged, taken pro rata as the order fills.
+    pub maker_fee: u64,
+}
+
+impl BookOrder {
+    /// The part of the remaining maker fee a fill of `fill` base atoms is
+    /// charged. The fill that empties the order takes the rest.
+    pub fn maker_fee_for(&self, fill: u64) -> u64 {
+        let (quantity, maker_fee) = (self.quantity, self.maker_fee);
+        if fill >= quantity {
+            return maker_fee;
+        }
+        (maker_fee as u128 * fill as u128 / quantity as u128) as u64
+    }
+}
+
+/// Bids and asks of one market, each kept sorted best price first and
+/// FIFO within a price level.
+#[account(zero_copy)]
+#[allow(clippy::repr_packed_without_abi)]
+pub struct OrderBook {
+    pub market: Pubkey,
+    pub fee_config: Pubkey,
+    pub base_vault: Pubkey,
+    pub quote_vault: Pubkey,
+    pub fee_collect_token_account: Pubkey,
+    pub next_seq: u64,
+    pub bid_count: u64,
+    pub ask_count: u64,
+    // literal lengths, the IDL parser can't resolve BOOK_CAPACITY
+    pub bids: [BookOrder; 32],
+    pub asks: [BookOrder; 32],
+}
+
+// fails to compile once the literal lengths above drift from BOOK_CAPACITY
+const _: fn(&OrderBook) -> ([BookOrder; BOOK_CAPACITY], [BookOrder; BOOK_CAPACITY]) = |book| (book.bids, book.asks);
+
+impl OrderBook {
+    pub fn count(&self, side: Side) -> usize {
+        match side {
+            Side::Bid => self.bid_count as usize,
+            Side::Ask => self.ask_count as usize,
+        }
+    }
+
+    fn set_count(&mut self, side: Side, count: usize) {
+        match side {
+            Side::Bid => self.bid_count = count as u64,
+            Side::Ask => self.ask_count = count as u64,
+        }
+    }
+
+    fn orders(&self, side: Side) -> &[BookOrder; BOOK_CAPACITY] {
+        match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        }
+    }
+
+    fn orders_mut(&mut self, side: Side) -> &mut [BookOrder; BOOK_CAPACITY] {
+        match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        }
+    }
+
+    pub fn best(&self, side: Side) -> Option<BookOrder> {
+        if self.count(side) == 0 {
+            return None;
+        }
+        Some(self.orders(side)[0])
+    }
+
+    pub fn find(&self, side: Side, seq: u64) -> Option<usize> {
+        self.orders(side)[..self.count(side)]
+            .iter()
+            .position(|order| ({ order.seq }) == seq)
+    }
+
+    pub fn get(&self, side: Side, index: usize) -> BookOrder {
+        self.orders(side)[index]
+    }
+
+    /// Takes `fill` base atoms and `maker_fee` off a partly filled order.
+    pub fn fill(&mut self, side: Side, index: usize, fill: u64, maker_fee: u64) {
+        let order = &mut self.orders_mut(side)[index];
+        order.quantity -= fill;
+        order.maker_fee -= maker_fee;
+    }
+
+    /// Rests `order`. A full side makes room by evicting its worst order,
+    /// which is returned, if `order` beats its price.
+    pub fn insert(&mut self, side: Side, order: BookOrder) -> Result<Option<BookOrder>, ProgramError> {
+        let price = order.price;
+        let mut evicted = None;
+        if self.count(side) >= BOOK_CAPACITY {
+            let worst = self.orders(side)[BOOK_CAPACITY - 1];
+            if !side.beats(price, worst.price) {
+                return Err(ScrtEscrowErrors::OrderBookFull.into());
+            }
+            evicted = Some(self.remove(side, BOOK_CAPACITY - 1));
+        }
+        let count = self.count(side);
+        let orders = self.orders_mut(side);
+        let index = orders[..count]
+            .iter()
+            .position(|resting| side.beats(price, resting.price))
+            .unwrap_or(count);
+        orders.copy_within(index..count, index + 1);
+        orders[index] = order;
+        self.set_count(side, count + 1);
+        Ok(evicted)
+    }
+
+    pub fn remove(&mut self, side: Side, index: usize) -> BookOrder {
+        let count = self.count(side);
+        let orders = self.orders_mut(side);
+        let order = orders[index];
+        orders.copy_within(index + 1..count, index);
+        self.set_count(side, count - 1);
+        order
+    }
+}
+
+/// Quote atoms owed for `quantity` base atoms at `price` per lot.
+pub fn quote_amount(quantity: u64, price: u64, lot_size: u64) -> Result<u64, ProgramError> {
+    (quantity / lot_size)
+        .checked_mul(price)
+        .ok_or_else(|| ScrtEscrowErrors::AmountOverflow.into())
+}
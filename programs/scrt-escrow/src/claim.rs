@@ -0,0 +1,58 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    program::{invoke, invoke_signed},
+    program_pack::Pack,
+    system_instruction,
+};
+use anchor_spl::token::TokenAccount;
+
+/// True if `info` can take a transfer of `mint` right now. A closed, frozen
+/// or re-purposed receive account fails this and the fill goes to the
+/// claim account of its owner instead.
+pub fn can_receive(info: &AccountInfo, mint: &Pubkey) -> bool {
+    match Account::<TokenAccount>::try_from(info) {
+        Ok(account) => account.mint == *mint && !account.is_frozen(),
+        Err(_) => false,
+    }
+}
+
+/// Creates the claim token account at its PDA if it doesn't exist yet. The
+/// account is its own authority, so only this program can move funds out.
+/// Anyone can send lamports to the address beforehand, so it's topped up,
+/// allocated and assigned rather than created.
+pub fn open_claim_account<'info>(
+    claim: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    rent: &AccountInfo<'info>,
+    claim_seeds: &[&[u8]],
+) -> ProgramResult {
+    if !claim.data_is_empty() {
+        return Ok(());
+    }
+    let lamports = Rent::get()?
+        .minimum_balance(spl_token::state::Account::LEN)
+        .saturating_sub(claim.lamports());
+    if lamports > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, claim.key, lamports),
+            &[payer.clone(), claim.clone(), system_program.clone()],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(claim.key, spl_token::state::Account::LEN as u64),
+        &[claim.clone(), system_program.clone()],
+        &[claim_seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(claim.key, token_program.key),
+        &[claim.clone(), system_program.clone()],
+        &[claim_seeds],
+    )?;
+    invoke(
+        &spl_token::instruction::initialize_account(token_program.key, claim.key, mint.key, claim.key)?,
+        &[claim.clone(), mint.clone(), claim.clone(), rent.clone(), token_program.clone()],
+    )
+}
@@ -0,0 +1,127 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hash;
+
+use crate::error::ScrtEscrowErrors;
+use crate::oracle::PriceTrigger;
+
+pub const MAX_CONDITIONS: usize = 4;
+/// The largest variant, a price trigger, plus its tag.
+pub const CONDITION_SPACE: usize = 1 + 32 + 1 + 8 * 2;
+pub const CONDITIONS_SPACE: usize = 4 + CONDITION_SPACE * MAX_CONDITIONS;
+
+/// A condition on settling an escrow. All of an escrow's conditions have
+/// to hold. New kinds go at the end so stored escrows keep decoding.
+/// Variants name their fields since clients can't encode tuple variants.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Condition {
+    /// Fillable only before this unix timestamp.
+    Expiry { ends: i64 },
+    /// Only this taker may fill.
+    Taker { taker: Pubkey },
+    /// The fill must reveal a preimage with this sha256 hash.
+    Hashlock { digest: [u8; 32] },
+    /// Fillable only while an oracle price is past a threshold.
+    Price { trigger: PriceTrigger },
+    /// This key must co-sign the fill.
+    Approval { approver: Pubkey },
+    /// The maker can't cancel before this unix timestamp.
+    CancelAfter { unlocks: i64 },
+}
+
+impl Condition {
+    /// Price and approval conditions each need an account at fill.
+    pub fn takes_account(&self) -> bool {
+        matches!(self, Condition::Price { .. } | Condition::Approval { .. })
+    }
+}
+
+/// What a fill brings to satisfy an escrow's conditions.
+pub struct FillWitness<'a, 'info> {
+    pub taker: &'a Pubkey,
+    pub preimages: &'a [Vec<u8>],
+    /// One per condition that takes an account, in order.
+    pub accounts: &'a [AccountInfo<'info>],
+}
+
+pub fn validate_conditions(conditions: &[Condition]) -> ProgramResult {
+    if conditions.len() > MAX_CONDITIONS {
+        return Err(ScrtEscrowErrors::InvalidConditions.into());
+    }
+    for condition in conditions {
+        if let Condition::Price { trigger } = condition {
+            trigger.validate()?;
+        }
+    }
+    Ok(())
+}
+
+/// How many accounts a fill passes for `conditions`.
+pub fn condition_accounts_len(conditions: &[Condition]) -> usize {
+    conditions.iter().filter(|condition| condition.takes_account()).count()
+}
+
+pub fn check_fill(conditions: &[Condition], witness: &FillWitness, program_id: &Pubkey) -> ProgramResult {
+    if conditions.is_empty() {
+        return Ok(());
+    }
+    let now = Clock::get()?.unix_timestamp;
+    let mut accounts = witness.accounts.iter();
+    for condition in conditions {
+        match condition {
+            Condition::Expiry { ends } => {
+                if now >= *ends {
+                    return Err(ScrtEscrowErrors::EscrowExpired.into());
+                }
+            }
+            Condition::Taker { taker } => {
+                if taker != witness.taker {
+                    return Err(ScrtEscrowErrors::TakerNotAllowed.into());
+                }
+            }
+            Condition::Hashlock { digest } => {
+                if !witness.preimages.iter().any(|preimage| hash(preimage).to_bytes() == *digest) {
+                    return Err(ScrtEscrowErrors::HashlockNotMet.into());
+                }
+            }
+            Condition::Price { trigger } => {
+                let oracle = accounts
+                    .next()
+                    .filter(|info| *info.key == trigger.oracle)
+                    .ok_or(ScrtEscrowErrors::ConditionAccountsMissing)?;
+                trigger.check(oracle, program_id)?;
+            }
+            Condition::Approval { approver } => {
+                let signed = accounts
+                    .next()
+                    .ok_or(ScrtEscrowErrors::ConditionAccountsMissing)?;
+                if signed.key != approver || !signed.is_signer {
+                    return Err(ScrtEscrowErrors::ApprovalMissing.into());
+                }
+            }
+            Condition::CancelAfter { .. } => {}
+        }
+    }
+    Ok(())
+}
+
+/// Whether an expiry condition has passed at `now`.
+pub fn expired(conditions: &[Condition], now: i64) -> bool {
+    conditions
+        .iter()
+        .any(|condition| matches!(condition, Condition::Expiry { ends } if now >= *ends))
+}
+
+pub fn check_cancel(conditions: &[Condition]) -> ProgramResult {
+    if conditions.is_empty() {
+        return Ok(());
+    }
+    let now = Clock::get()?.unix_timestamp;
+    for condition in conditions {
+        if let Condition::CancelAfter { unlocks } = condition {
+            if now < *unlocks {
+                return Err(ScrtEscrowErrors::CancelLocked.into());
+            }
+        }
+    }
+    Ok(())
+}
@@ -19,4 +19,280 @@ pub enum ScrtEscrowErrors {
 
     #[msg("Taker fee amount is not enough")]
     TakerFeeAmountInsufficient,
+
+    #[msg("Escrow taker amount differs from the expected amount")]
+    TakerAmountMismatch,
+
+    #[msg("Escrow initializer amount differs from the expected amount")]
+    InitializerAmountMismatch,
+
+    #[msg("Escrow taker fee exceeds the maximum accepted by the taker")]
+    TakerFeeExceedsMax,
+
+    #[msg("Escrow is not open")]
+    EscrowNotOpen,
+
+    #[msg("Open orders index is full")]
+    OpenOrdersFull,
+
+    #[msg("Market is disabled")]
+    MarketDisabled,
+
+    #[msg("Market tick size and lot size must be greater or equal to 1")]
+    InvalidMarketConfig,
+
+    #[msg("Base amount is not a multiple of the market lot size")]
+    OddLotOrder,
+
+    #[msg("Price is not a multiple of the market tick size")]
+    InvalidTickSize,
+
+    #[msg("Order notional is below the market minimum")]
+    BelowMinNotional,
+
+    #[msg("Order book is full and the order doesn't beat its worst price")]
+    OrderBookFull,
+
+    #[msg("Order not found in the order book")]
+    OrderNotFound,
+
+    #[msg("Order belongs to another owner")]
+    OrderOwnerMismatch,
+
+    #[msg("Missing receive account for a matched maker")]
+    MakerAccountMissing,
+
+    #[msg("Account does not match the one recorded on the order")]
+    OrderAccountMismatch,
+
+    #[msg("Amount overflow")]
+    AmountOverflow,
+
+    #[msg("Remaining accounts do not form complete groups")]
+    InvalidRemainingAccounts,
+
+    #[msg("Escrow does not match the batch")]
+    BatchEscrowMismatch,
+
+    #[msg("Batch received less than the minimum")]
+    BatchMinReceiveNotMet,
+
+    #[msg("Escrow was created under an older order epoch")]
+    StaleOrderEpoch,
+
+    #[msg("Ring trade needs at least two legs")]
+    RingTooShort,
+
+    #[msg("Ring legs do not chain deposit and receive mints")]
+    RingMintMismatch,
+
+    #[msg("Ring leg deposit does not cover the next leg's ask")]
+    RingAmountUnsatisfiable,
+
+    #[msg("Flash exchange callback cannot be this program")]
+    InvalidFlashCallback,
+
+    #[msg("Taker payment did not arrive after the flash exchange callback")]
+    FlashPaymentMissing,
+
+    #[msg("Fill hook program or accounts are invalid")]
+    InvalidFillHook,
+
+    #[msg("Fill hook accounts were not supplied")]
+    FillHookAccountsMissing,
+
+    #[msg("Nothing to claim")]
+    NothingToClaim,
+
+    #[msg("Account is not the owner's associated token account")]
+    InvalidAssociatedTokenAccount,
+
+    #[msg("Receive token account has the wrong mint")]
+    InvalidReceiveTokenAccount,
+
+    #[msg("Proceeds split needs 1 to 4 nonzero shares summing to 10000 bps")]
+    InvalidProceedsSplit,
+
+    #[msg("Proceeds split recipients don't match the escrow")]
+    ProceedsSplitMismatch,
+
+    #[msg("Escrows with a proceeds split can only be filled by exchange")]
+    ProceedsSplitUnsupported,
+
+    #[msg("Referral share can't exceed 10000 bps")]
+    InvalidReferralShare,
+
+    #[msg("Referrer token account or stats don't match")]
+    InvalidReferrer,
+
+    #[msg("Fee tiers must start at zero volume, increase and stay within 10000 bps")]
+    InvalidFeeTiers,
+
+    #[msg("Tiered initializer fee exceeds the given maximum")]
+    InitializerFeeExceedsMax,
+
+    #[msg("Escrows with tiered fees can only be filled by exchange")]
+    TieredFeesUnsupported,
+
+    #[msg("Stake discounts must increase and stay within 10000 bps")]
+    InvalidStakeDiscounts,
+
+    #[msg("Unbonding period can't be negative")]
+    InvalidUnbondingPeriod,
+
+    #[msg("Not enough active stake")]
+    InsufficientStake,
+
+    #[msg("Unstaked tokens are still unbonding")]
+    StakeStillUnbonding,
+
+    #[msg("Nothing to withdraw")]
+    NothingToWithdraw,
+
+    #[msg("Fees must be collected into the fee revenue vault")]
+    FeeCollectorMismatch,
+
+    #[msg("Fee revenue vault is empty")]
+    NothingToDistribute,
+
+    #[msg("Fee distribution needs 1 to 4 nonzero shares summing to 10000 bps")]
+    InvalidFeeDistribution,
+
+    #[msg("Fee distribution destinations don't match the table")]
+    FeeDistributionMismatch,
+
+    #[msg("Fee mint is already accepted, is the fee token, is unknown or its bps exceed 10000")]
+    InvalidFeeMint,
+
+    #[msg("Bps fee mint must be one of the traded mints")]
+    FeeMintUnpriceable,
+
+    #[msg("Escrows with fees in another mint can only be filled by exchange")]
+    FeeMintUnsupported,
+
+    #[msg("Fee vault doesn't match the fee mint")]
+    FeeVaultMismatch,
+
+    #[msg("Deducted fee mode is off for this fee config")]
+    FeeDeductionDisabled,
+
+    #[msg("Deducted fee collector must be the fee config's collector account for the mint")]
+    InvalidFeeCollector,
+
+    #[msg("Escrows with deducted fees can only be filled by exchange")]
+    FeeDeductionUnsupported,
+
+    #[msg("Deducted fee rates can't exceed 10000 bps")]
+    InvalidDeductedFees,
+
+    #[msg("Price feed is neither a Pyth price account nor one of this program's")]
+    InvalidPriceFeed,
+
+    #[msg("USD fees need a positive staleness bound and a confidence bound of at most 10000 bps")]
+    InvalidUsdFees,
+
+    #[msg("Price conversion overflowed")]
+    PriceOverflow,
+
+    #[msg("Price triggers need a positive threshold and staleness bound")]
+    InvalidPriceTrigger,
+
+    #[msg("Oracle price is stale")]
+    StalePrice,
+
+    #[msg("Oracle price hasn't reached the escrow's trigger")]
+    PriceTriggerNotMet,
+
+    #[msg("Escrows with conditions can only be filled by exchange")]
+    ConditionsUnsupported,
+
+    #[msg("At most 4 conditions per escrow")]
+    InvalidConditions,
+
+    #[msg("Escrow has expired")]
+    EscrowExpired,
+
+    #[msg("Escrow is reserved for another taker")]
+    TakerNotAllowed,
+
+    #[msg("No preimage matches the escrow's hashlock")]
+    HashlockNotMet,
+
+    #[msg("Missing or misordered condition accounts")]
+    ConditionAccountsMissing,
+
+    #[msg("Escrow's approver hasn't signed")]
+    ApprovalMissing,
+
+    #[msg("Escrow can't be cancelled yet")]
+    CancelLocked,
+
+    #[msg("Only the initializer can cancel an escrow that hasn't expired")]
+    CancelUnauthorized,
+
+    #[msg("Escrow is still open")]
+    EscrowStillOpen,
+
+    #[msg("Open orders capacity must be between 1 and MAX_OPEN_ORDERS_CAPACITY")]
+    InvalidOpenOrdersCapacity,
+
+    #[msg("Not the program's data account under the upgradeable loader")]
+    InvalidProgramData,
+
+    #[msg("Signer isn't the program's upgrade authority")]
+    NotUpgradeAuthority,
+
+    #[msg("Escrows with a fill hook can only be filled by exchange")]
+    HookUnsupported,
+
+    #[msg("Fill hook program isn't deployed")]
+    FillHookUnavailable,
+
+    #[msg("Stake account belongs to someone else")]
+    InvalidStakeAccount,
+
+    #[msg("Not the maker's claim account for the mint")]
+    ClaimAccountMismatch,
+
+    #[msg("Proceeds split recipients must be token accounts of the receive mint")]
+    InvalidSplitRecipient,
+
+    #[msg("Users can't refer themselves")]
+    SelfReferral,
+
+    #[msg("Local price feeds are only available in test-oracle builds")]
+    TestOracleDisabled,
+
+    #[msg("User stats don't belong to this user and fee token")]
+    InvalidUserStats,
+
+    #[msg("Price history isn't the one of this pair")]
+    InvalidPriceHistory,
+
+    #[msg("The pair's price history hasn't been created")]
+    PriceHistoryMissing,
+
+    #[msg("Open orders aren't the initializer's")]
+    InvalidOpenOrders,
+
+    #[msg("The escrow's referrer wasn't passed")]
+    ReferrerMissing,
+
+    #[msg("Escrows with a referrer can only be filled by exchange")]
+    ReferrerUnsupported,
+
+    #[msg("Both sides' stats must be passed while fee tiers are on")]
+    UserStatsMissing,
+
+    #[msg("The maker rebate needs the fee revenue vault")]
+    FeeRevenueVaultMissing,
+
+    #[msg("Fee paying token account must belong to the user")]
+    InvalidFeePayingAccount,
+
+    #[msg("Takers must pass a fee paying account unless fees are deducted")]
+    TakerFeeAccountMissing,
+
+    #[msg("A ring leg's taker fee must be payable in its deposit mint")]
+    RingFeeUnpayable,
 }
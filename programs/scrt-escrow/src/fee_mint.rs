@@ -0,0 +1,41 @@
+use anchor_lang::prelude::*;
+
+use crate::error::ScrtEscrowErrors;
+use crate::split::TOTAL_BPS;
+use crate::tier::bps_of;
+
+pub const MAX_FEE_MINTS: usize = 4;
+pub const FEE_MINTS_SPACE: usize = 4 + (32 * 3 + 8 + 2) * MAX_FEE_MINTS;
+
+/// A mint fees may be paid in besides the config's `fee_token`. Fees in
+/// it are held in `vault` while an escrow is open and paid to `collector`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AcceptedFeeMint {
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+    pub collector: Pubkey,
+    /// Charged when `fee_bps` is zero.
+    pub flat_fee: u64,
+    /// Of the trade's leg in this mint.
+    pub fee_bps: u16,
+}
+
+impl AcceptedFeeMint {
+    pub fn validate(&self) -> ProgramResult {
+        if self.fee_bps > TOTAL_BPS {
+            return Err(ScrtEscrowErrors::InvalidFeeMint.into());
+        }
+        Ok(())
+    }
+
+    /// The fee for a trade whose leg in this mint is `notional`, if any.
+    pub fn fee(&self, notional: Option<u64>) -> Result<u64, ProgramError> {
+        if self.fee_bps == 0 {
+            return Ok(self.flat_fee);
+        }
+        match notional {
+            Some(notional) => Ok(bps_of(notional, self.fee_bps)),
+            None => Err(ScrtEscrowErrors::FeeMintUnpriceable.into()),
+        }
+    }
+}
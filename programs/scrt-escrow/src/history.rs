@@ -0,0 +1,131 @@
+use anchor_lang::prelude::*;
+
+use crate::error::ScrtEscrowErrors;
+
+pub const PRICE_HISTORY_SEED: &[u8] = b"price-history";
+pub const PRICE_HISTORY_CAPACITY: usize = 64;
+/// Prices are quote atoms per base atom, scaled by this.
+pub const PRICE_SCALE: u128 = 1_000_000_000_000;
+
+/// State of a pair's history right after one fill.
+#[zero_copy]
+#[allow(clippy::repr_packed_without_abi)]
+pub struct PriceObservation {
+    pub timestamp: i64,
+    pub price: u128,
+    pub cumulative_price: u128,
+    pub cumulative_base_volume: u128,
+    pub cumulative_quote_volume: u128,
+}
+
+/// Prices of one pair's `exchange` fills, keyed by its mints in byte
+/// order. `cumulative_price` sums each price times the seconds it stood,
+/// so the difference between two points over the time between them is
+/// the average price in between.
+#[account(zero_copy)]
+#[allow(clippy::repr_packed_without_abi)]
+pub struct PriceHistory {
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub last_price: u128,
+    pub last_update: i64,
+    pub cumulative_price: u128,
+    pub cumulative_base_volume: u128,
+    pub cumulative_quote_volume: u128,
+    /// Total fills recorded; the newest observation is at
+    /// `(fills - 1) % PRICE_HISTORY_CAPACITY`.
+    pub fills: u64,
+    // PRICE_HISTORY_CAPACITY, spelled out for the IDL parser
+    pub observations: [PriceObservation; 64],
+}
+
+// fails to compile once the literal length above drifts from PRICE_HISTORY_CAPACITY
+const _: fn(&PriceHistory) -> [PriceObservation; PRICE_HISTORY_CAPACITY] = |history| history.observations;
+
+impl PriceHistory {
+    pub const SPACE: usize = 8 + 32 * 2 + 16 + 8 + 16 * 3 + 8 + (8 + 16 * 4) * PRICE_HISTORY_CAPACITY;
+
+    /// Records a fill of `deposit_amount` of `deposit_token` for
+    /// `receive_amount` of `receive_token` at `now`. The mints have to be
+    /// this history's pair, in either order.
+    pub fn record_fill(
+        &mut self,
+        deposit_token: &Pubkey,
+        receive_token: &Pubkey,
+        deposit_amount: u64,
+        receive_amount: u64,
+        now: i64,
+    ) -> ProgramResult {
+        let (base_amount, quote_amount) = if *deposit_token == self.base_mint && *receive_token == self.quote_mint {
+            (deposit_amount, receive_amount)
+        } else if *deposit_token == self.quote_mint && *receive_token == self.base_mint {
+            (receive_amount, deposit_amount)
+        } else {
+            return Err(ScrtEscrowErrors::InvalidPriceHistory.into());
+        };
+        if base_amount == 0 {
+            return Ok(());
+        }
+        if self.fills > 0 {
+            let elapsed = now.saturating_sub(self.last_update).max(0) as u128;
+            self.cumulative_price = self.cumulative_price.wrapping_add(self.last_price.wrapping_mul(elapsed));
+        }
+        self.last_price = quote_amount as u128 * PRICE_SCALE / base_amount as u128;
+        self.last_update = now;
+        self.cumulative_base_volume = self.cumulative_base_volume.wrapping_add(base_amount as u128);
+        self.cumulative_quote_volume = self.cumulative_quote_volume.wrapping_add(quote_amount as u128);
+
+        let index = (self.fills % PRICE_HISTORY_CAPACITY as u64) as usize;
+        self.observations[index] = PriceObservation {
+            timestamp: now,
+            price: self.last_price,
+            cumulative_price: self.cumulative_price,
+            cumulative_base_volume: self.cumulative_base_volume,
+            cumulative_quote_volume: self.cumulative_quote_volume,
+        };
+        self.fills += 1;
+        Ok(())
+    }
+
+    /// Cumulative price as of `now`, the last price standing since the
+    /// last fill.
+    pub fn cumulative_price_at(&self, now: i64) -> u128 {
+        let elapsed = now.saturating_sub(self.last_update).max(0) as u128;
+        self.cumulative_price.wrapping_add(self.last_price.wrapping_mul(elapsed))
+    }
+
+    /// Time weighted average price over the last `window` seconds, or as
+    /// far back as the ring buffer reaches. None before the first fill.
+    pub fn twap(&self, now: i64, window: i64) -> Option<u128> {
+        if self.fills == 0 {
+            return None;
+        }
+        let start = now.saturating_sub(window);
+        let stored = self.fills.min(PRICE_HISTORY_CAPACITY as u64);
+        // newest first, stop at the first observation at or before start
+        let mut from = None;
+        for age in 0..stored {
+            let index = ((self.fills - 1 - age) % PRICE_HISTORY_CAPACITY as u64) as usize;
+            let observation = self.observations[index];
+            from = Some(observation);
+            if observation.timestamp <= start {
+                break;
+            }
+        }
+        let from = from?;
+        let (from_time, from_cumulative) = if from.timestamp < start {
+            // the price after that fill stood until start
+            (
+                start,
+                from.cumulative_price
+                    .wrapping_add(from.price.wrapping_mul((start - from.timestamp) as u128)),
+            )
+        } else {
+            (from.timestamp, from.cumulative_price)
+        };
+        if now <= from_time {
+            return Some(self.last_price);
+        }
+        Some(self.cumulative_price_at(now).wrapping_sub(from_cumulative) / (now - from_time) as u128)
+    }
+}
@@ -0,0 +1,90 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{instruction::Instruction, program::invoke};
+
+use crate::error::ScrtEscrowErrors;
+
+/// The hook runs on what's left of the fill instruction's compute budget,
+/// this runtime has no separate meter for a CPI, so what it may touch is
+/// capped instead.
+pub const MAX_HOOK_ACCOUNTS: usize = 8;
+pub const FILL_HOOK_SPACE: usize = 1 + 32 + 4 + 32 * MAX_HOOK_ACCOUNTS;
+
+/// Anchor sighash of `global:on_fill`, so a hook written with Anchor can
+/// expose `on_fill(ctx, fill: FillNotification)`.
+pub const FILL_HOOK_DISCRIMINATOR: [u8; 8] = [133, 76, 152, 104, 24, 119, 244, 223];
+
+/// A maker program notified by `exchange` once the fill has settled.
+/// The hook is part of the fill: if it errors, runs out of compute or is
+/// no longer deployed, the whole fill fails, since the runtime gives the
+/// caller no way to recover from a failed CPI.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FillHook {
+    pub program: Pubkey,
+    pub accounts: Vec<Pubkey>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FillNotification {
+    pub escrow: Pubkey,
+    pub initializer: Pubkey,
+    pub taker: Pubkey,
+    pub deposit_token: Pubkey,
+    pub receive_token: Pubkey,
+    pub deposit_amount: u64,
+    pub receive_amount: u64,
+}
+
+impl FillHook {
+    pub fn validate(&self, program_id: &Pubkey) -> ProgramResult {
+        // calling back into the escrow program is the only reentry the
+        // runtime allows, so refuse it up front
+        if self.program == *program_id || self.accounts.len() > MAX_HOOK_ACCOUNTS {
+            return Err(ScrtEscrowErrors::InvalidFillHook.into());
+        }
+        Ok(())
+    }
+
+    /// Calls the hook with `accounts`, which must start with the hook
+    /// program followed by the registered accounts in order.
+    pub fn notify<'info>(
+        &self,
+        accounts: &[AccountInfo<'info>],
+        fill: &FillNotification,
+    ) -> ProgramResult {
+        let count = 1 + self.accounts.len();
+        let supplied = accounts.len() >= count
+            && *accounts[0].key == self.program
+            && accounts[1..count]
+                .iter()
+                .zip(self.accounts.iter())
+                .all(|(info, key)| info.key == key);
+        if !supplied {
+            return Err(ScrtEscrowErrors::FillHookAccountsMissing.into());
+        }
+        if !accounts[0].executable {
+            return Err(ScrtEscrowErrors::FillHookUnavailable.into());
+        }
+
+        let mut data = FILL_HOOK_DISCRIMINATOR.to_vec();
+        fill.serialize(&mut data)?;
+        // never forward signatures, the hook is chosen by the maker
+        let metas = accounts[1..count]
+            .iter()
+            .map(|info| {
+                if info.is_writable {
+                    AccountMeta::new(*info.key, false)
+                } else {
+                    AccountMeta::new_readonly(*info.key, false)
+                }
+            })
+            .collect();
+        invoke(
+            &Instruction {
+                program_id: self.program,
+                accounts: metas,
+                data,
+            },
+            &accounts[..count],
+        )
+    }
+}
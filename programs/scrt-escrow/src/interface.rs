@@ -0,0 +1,143 @@
+//! Calling the escrow from another program.
+//!
+//! Enable the `cpi` feature and use the generated helpers re-exported here,
+//! one per instruction, each taking a `CpiContext` over the matching
+//! `accounts::*` struct:
+//!
+//! ```ignore
+//! use scrt_escrow::interface::{self, accounts::Initialize};
+//!
+//! let ctx = CpiContext::new_with_signer(escrow_program, accounts, &[&seeds[..]]);
+//! interface::initialize(ctx, initializer_amount, taker_amount, fee_initializer, fee_taker, EscrowOptions::default())?;
+//! ```
+//!
+//! Every `initializer`, `taker`, `owner` or `cranker` may be a PDA of the
+//! calling program signed through `CpiContext::new_with_signer`. The escrow
+//! never needs the signer after the instruction returns: vaults are created
+//! owned by the escrow's own vault authority. Where the signer also pays for
+//! new accounts (`initialize`, `initialize_open_orders`, receipts) it has to
+//! be a system owned PDA holding enough lamports.
+//!
+//! `exchange` takes the optional accounts picked by its `ExchangeExtras`
+//! at the head of `CpiContext::with_remaining_accounts`. Unless its fees are
+//! deducted, `initialize` takes the initializer's fee paying token account
+//! and the fee vault of its mint there.
+//!
+//! The functions below derive the addresses the accounts structs ask for.
+//!
+//! Prices of a pair's fills are kept in its `PriceHistory` once someone has
+//! created it. Every fill instruction takes the pair's history address,
+//! created or not, so no fill goes unrecorded. Read it with [`twap`].
+
+use anchor_lang::prelude::*;
+use std::cmp::{max, min};
+
+use crate::account::{
+    load_price_history, CLAIM_SEED, ESCROW_PDA_SEED, FEE_REVENUE_SEED, FEE_VAULT_SEED, MARKET_SEED, OPEN_ORDERS_SEED,
+    PDA_SEED, PRICE_SEED, REFERRER_SEED, STAKE_SEED, STAKE_VAULT_SEED, USER_STATS_SEED,
+};
+
+pub use crate::cpi::*;
+
+/// Fee config for `fee_token`, created by `initialize_pda`.
+pub fn pda_account_address(fee_token: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[crate::ID.as_ref(), fee_token.as_ref(), PDA_SEED],
+        &crate::ID,
+    )
+    .0
+}
+
+/// Owner of every fee config's vault fee account.
+pub fn vault_fee_authority_address(pda_account: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[pda_account.as_ref(), ESCROW_PDA_SEED], &crate::ID).0
+}
+
+pub fn escrow_address(initializer: &Pubkey, deposit_token: &Pubkey, receive_token: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[initializer.as_ref(), deposit_token.as_ref(), receive_token.as_ref()],
+        &crate::ID,
+    )
+    .0
+}
+
+pub fn vault_address(escrow: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[escrow.as_ref()], &crate::ID).0
+}
+
+pub fn vault_authority_address(escrow: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[ESCROW_PDA_SEED, escrow.as_ref()], &crate::ID).0
+}
+
+pub fn open_orders_address(owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[owner.as_ref(), OPEN_ORDERS_SEED], &crate::ID).0
+}
+
+/// Market of a pair, in either mint order. Pass it even when the pair has
+/// no market; the escrow treats an empty account as unrestricted.
+pub fn market_address(mint_a: &Pubkey, mint_b: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[min(mint_a, mint_b).as_ref(), max(mint_a, mint_b).as_ref(), MARKET_SEED],
+        &crate::ID,
+    )
+    .0
+}
+
+/// Where `exchange` parks `owner`'s proceeds in `mint` when their receive
+/// account can't take them.
+pub fn claim_address(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[owner.as_ref(), mint.as_ref(), CLAIM_SEED], &crate::ID).0
+}
+
+/// Stats of `owner` as a referrer in `fee_token`, created by
+/// `initialize_referrer`.
+pub fn referrer_stats_address(owner: &Pubkey, fee_token: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[owner.as_ref(), fee_token.as_ref(), REFERRER_SEED], &crate::ID).0
+}
+
+/// Volume stats of `owner` in `fee_token`, created by `initialize_user_stats`.
+pub fn user_stats_address(owner: &Pubkey, fee_token: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[owner.as_ref(), fee_token.as_ref(), USER_STATS_SEED], &crate::ID).0
+}
+
+/// Where `owner` stakes `fee_token`, created by `initialize_stake_account`.
+pub fn stake_address(owner: &Pubkey, fee_token: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[owner.as_ref(), fee_token.as_ref(), STAKE_SEED], &crate::ID).0
+}
+
+pub fn stake_vault_address(pda_account: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[pda_account.as_ref(), STAKE_VAULT_SEED], &crate::ID).0
+}
+
+/// Where escrows' fees collect once the fee config distributes them.
+pub fn fee_revenue_vault_address(pda_account: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[pda_account.as_ref(), FEE_REVENUE_SEED], &crate::ID).0
+}
+
+/// Holds open escrows' initializer fees in an accepted `mint`, created by
+/// `add_fee_mint`.
+pub fn fee_vault_address(pda_account: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[pda_account.as_ref(), mint.as_ref(), FEE_VAULT_SEED], &crate::ID).0
+}
+
+/// Local price feed of `authority` for `product`, created by
+/// `initialize_price_account`.
+pub fn price_account_address(authority: &Pubkey, product: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[authority.as_ref(), product.as_ref(), PRICE_SEED], &crate::ID).0
+}
+
+/// Fill price history of a pair, in either mint order, created by
+/// `initialize_price_history`.
+pub fn price_history_address(mint_a: &Pubkey, mint_b: &Pubkey) -> Pubkey {
+    crate::account::price_history_address(mint_a, mint_b, &crate::ID)
+}
+
+/// Time weighted average fill price of the pair of `mint_a` and `mint_b`
+/// over the last `window` seconds, as quote atoms per base atom scaled by
+/// `PRICE_SCALE`. Fails unless `price_history` is the pair's created
+/// history, None before its first fill.
+pub fn twap(price_history: &AccountInfo, mint_a: &Pubkey, mint_b: &Pubkey, window: i64) -> Result<Option<u128>, ProgramError> {
+    let history = load_price_history(price_history, mint_a, mint_b, &crate::ID)?;
+    let twap = history.load()?.twap(Clock::get()?.unix_timestamp, window);
+    Ok(twap)
+}
@@ -1,12 +1,37 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self};
+use anchor_lang::solana_program::{instruction::Instruction, program::invoke};
+use anchor_spl::token::{self, TokenAccount};
 use spl_token::instruction::AuthorityType;
 
 pub mod account;
+pub mod ata;
+pub mod book;
+pub mod claim;
+pub mod condition;
 pub mod error;
+pub mod fee_mint;
+pub mod history;
+pub mod hook;
+#[cfg(feature = "cpi")]
+pub mod interface;
+pub mod oracle;
+pub mod receipt;
+pub mod settle;
+pub mod split;
+pub mod stake;
+pub mod tier;
 
 use account::*;
+use book::*;
+use condition::*;
 use error::*;
+use fee_mint::*;
+use hook::*;
+use oracle::*;
+use split::*;
+use stake::*;
+use tier::*;
+use std::cmp::min;
 
 declare_id!("Hv4LktuBNs6T62X7LEkmqJZseGvVCxwCkAjqFq3pZqCb");
 
@@ -14,8 +39,6 @@ declare_id!("Hv4LktuBNs6T62X7LEkmqJZseGvVCxwCkAjqFq3pZqCb");
 pub mod scrt_escrow {
     use super::*;
 
-    const ESCROW_PDA_SEED: &[u8] = b"escrow";
-
     pub fn initialize_pda(ctx: Context<InitializePda>) -> ProgramResult {
         let (vault_fee_authority, _vault_authority_bump) = Pubkey::find_program_address(
             &[
@@ -40,6 +63,73 @@ pub mod scrt_escrow {
         Ok(())
     }
 
+    /// Sets up the program wide config. Only the upgrade authority can, so
+    /// whoever deployed the program picks the admin.
+    pub fn initialize_program_config(ctx: Context<InitializeProgramConfig>, admin: Pubkey) -> ProgramResult {
+        let authority = upgrade_authority(&ctx.accounts.program_data, ctx.program_id)?;
+        if authority != Some(*ctx.accounts.authority.key){
+            return Err(ScrtEscrowErrors::NotUpgradeAuthority.into());
+        }
+        ctx.accounts.program_config.admin = admin;
+        Ok(())
+    }
+
+    pub fn update_program_config(ctx: Context<UpdateProgramConfig>, admin: Pubkey) -> ProgramResult {
+        ctx.accounts.program_config.admin = admin;
+        Ok(())
+    }
+
+    /// Opens a pair for trading. Only the program config's admin can, so
+    /// a market's rules can't be squatted by whoever gets there first.
+    pub fn initialize_market(
+        ctx: Context<InitializeMarket>,
+        tick_size: u64,
+        lot_size: u64,
+        min_notional: u64,
+    ) -> ProgramResult {
+        if tick_size == 0 || lot_size == 0{
+            return Err(ScrtEscrowErrors::InvalidMarketConfig.into());
+        }
+        let market = &mut ctx.accounts.market;
+        market.base_mint = *ctx.accounts.base_mint.key;
+        market.quote_mint = *ctx.accounts.quote_mint.key;
+        market.tick_size = tick_size;
+        market.lot_size = lot_size;
+        market.min_notional = min_notional;
+        market.enabled = true;
+        Ok(())
+    }
+
+    pub fn update_market(
+        ctx: Context<UpdateMarket>,
+        tick_size: u64,
+        lot_size: u64,
+        min_notional: u64,
+        enabled: bool,
+    ) -> ProgramResult {
+        if tick_size == 0 || lot_size == 0{
+            return Err(ScrtEscrowErrors::InvalidMarketConfig.into());
+        }
+        let market = &mut ctx.accounts.market;
+        market.tick_size = tick_size;
+        market.lot_size = lot_size;
+        market.min_notional = min_notional;
+        market.enabled = enabled;
+        Ok(())
+    }
+
+    /// Creates the caller's open orders index, which `initialize`, `cancel`
+    /// and `exchange` keep up to date from then on. Holds up to `capacity`
+    /// live escrows, at most `MAX_OPEN_ORDERS_CAPACITY`.
+    pub fn initialize_open_orders(ctx: Context<InitializeOpenOrders>, capacity: u16) -> ProgramResult {
+        if capacity == 0 || capacity > MAX_OPEN_ORDERS_CAPACITY{
+            return Err(ScrtEscrowErrors::InvalidOpenOrdersCapacity.into());
+        }
+        ctx.accounts.open_orders.owner = *ctx.accounts.owner.key;
+        ctx.accounts.open_orders.capacity = capacity;
+        Ok(())
+    }
+
     #[event]
     pub struct InitializEvent {
         pub initializer: Pubkey,
@@ -51,15 +141,75 @@ pub mod scrt_escrow {
         pub receive_amount: u64,
         pub initializer_fee_amount: u64,
         pub taker_fee_amount: u64,
+        pub proceeds_recipient: Pubkey,
+        pub refund_recipient: Pubkey,
+        pub proceeds_split: Vec<ProceedsShare>,
     }
 
-    pub fn initialize(
-        ctx: Context<Initialize>,
+    /// Unless fees are deducted, the remaining accounts start with the
+    /// initializer's fee paying token account and the fee vault of its mint.
+    /// Split recipients follow in order, then the referrer's accounts if the
+    /// options name one.
+    #[allow(clippy::assign_op_pattern)]
+    pub fn initialize<'info>(
+        ctx: Context<'_, '_, '_, 'info, Initialize<'info>>,
         initializer_amount: u64,
         taker_amount: u64,
         fee_amount_initializer: u64,
         fee_amount_taker: u64,
+        options: EscrowOptions,
     ) -> ProgramResult {
+        if let Some(hook) = &options.hook{
+            hook.validate(ctx.program_id)?;
+        }
+        validate_split(&options.proceeds_split)?;
+        // users who deduct their fees needn't hold a fee account at all
+        let (fee_accounts, remaining_accounts) = if options.deduct_fees{
+            (None, ctx.remaining_accounts)
+        } else {
+            if ctx.remaining_accounts.len() < 2{
+                return Err(ScrtEscrowErrors::InvalidRemainingAccounts.into());
+            }
+            let (head, rest) = ctx.remaining_accounts.split_at(2);
+            let fee_paying: Account<TokenAccount> = Account::try_from(&head[0])?;
+            if fee_paying.owner != *ctx.accounts.initializer.key{
+                return Err(ScrtEscrowErrors::InvalidFeePayingAccount.into());
+            }
+            (Some((fee_paying, &head[1])), rest)
+        };
+        let proceeds_owners = validate_recipients(&options.proceeds_split, remaining_accounts, ctx.accounts.receive_token.key)?;
+        validate_conditions(&options.conditions)?;
+        // whoever referred the maker takes a cut of the initializer fee at fill
+        let referrer = match options.referrer{
+            Some(referrer) => {
+                let accounts = &remaining_accounts[options.proceeds_split.len()..];
+                if accounts.len() < 2 || *accounts[0].key != referrer{
+                    return Err(ScrtEscrowErrors::InvalidRemainingAccounts.into());
+                }
+                let (referrer_account, _) = load_referrer(&accounts[0], &accounts[1], ctx.accounts.fee_token.key)?;
+                if referrer_account.owner == *ctx.accounts.initializer.key{
+                    return Err(ScrtEscrowErrors::SelfReferral.into());
+                }
+                referrer
+            }
+            None => Pubkey::default(),
+        };
+        if options.canonical_receive && !ata::is_canonical(
+            ctx.accounts.initializer_receive_token_account.to_account_info().key,
+            ctx.accounts.initializer.key,
+            ctx.accounts.receive_token.key,
+        ){
+            return Err(ScrtEscrowErrors::InvalidAssociatedTokenAccount.into());
+        }
+
+        // once fees are distributed they all land in the revenue vault
+        let fee_revenue_vault = ctx.accounts.pda_account.fee_revenue_vault;
+        if fee_revenue_vault != Pubkey::default()
+            && fee_revenue_vault != *ctx.accounts.fee_collect_token_account.to_account_info().key
+        {
+            return Err(ScrtEscrowErrors::FeeCollectorMismatch.into());
+        }
+
         // check amounts
         if initializer_amount == 0{
             return Err(ScrtEscrowErrors::InvalidInitializerTokenAmount.into());
@@ -68,13 +218,102 @@ pub mod scrt_escrow {
             return Err(ScrtEscrowErrors::InvalidTakerTokenAmount.into());
         }
 
-        // check fee balance
-        let mut fee = fee_amount_initializer;
-        if *ctx.accounts.fee_token.key == *ctx.accounts.deposit_token.key{
-            fee = fee + initializer_amount;
+        // active stake discounts the maker fee
+        let stake_discount = discount_for(
+            &ctx.accounts.pda_account.stake_discounts,
+            active_stake(&ctx.accounts.maker_stake, ctx.accounts.initializer.key, ctx.accounts.fee_token.key)?,
+        );
+        let max_fee_initializer = fee_amount_initializer;
+        let mut fee_amount_initializer = apply_discount(fee_amount_initializer, stake_discount);
+        let mut fee_amount_taker = fee_amount_taker;
+        let mut fee_tiered = false;
+        let mut maker_rebate = 0;
+        let mut initializer_fee_mint = ctx.accounts.pda_account.fee_token;
+
+        if let Some((fee_paying, vault_fee_account)) = &fee_accounts{
+            initializer_fee_mint = fee_paying.mint;
+            if !ctx.accounts.pda_account.accepts(&initializer_fee_mint){
+                return Err(ScrtEscrowErrors::InvalidFeeMint.into());
+            }
+            if ctx.accounts.pda_account.fee_vault(&initializer_fee_mint) != Some(*vault_fee_account.key){
+                return Err(ScrtEscrowErrors::FeeVaultMismatch.into());
+            }
         }
-        if ctx.accounts.initializer_fee_paying_token_account.amount < fee{
-            return Err(ScrtEscrowErrors::InitializerFeeAmountInsufficient.into());
+
+        if options.deduct_fees{
+            // bps of the traded amounts, taken at fill; the fee vaults stay
+            // out of it
+            let pda = &ctx.accounts.pda_account;
+            if pda.deducted_fee_collector == Pubkey::default(){
+                return Err(ScrtEscrowErrors::FeeDeductionDisabled.into());
+            }
+            fee_amount_initializer = bps_of(initializer_amount, pda.deducted_maker_bps);
+            if fee_amount_initializer > max_fee_initializer{
+                return Err(ScrtEscrowErrors::InitializerFeeExceedsMax.into());
+            }
+            fee_amount_taker = bps_of(taker_amount, pda.deducted_taker_bps);
+        } else if let Some((fee_paying, _)) = &fee_accounts{
+            // volume tiers replace the flat fees when the trade can be priced,
+            // fee_amount_initializer then only caps the maker fee
+            let tiers = &ctx.accounts.pda_account.fee_tiers;
+            if let Some(notional) = fee_notional(
+                ctx.accounts.fee_token.key,
+                ctx.accounts.deposit_token.key,
+                initializer_amount,
+                ctx.accounts.receive_token.key,
+                taker_amount,
+            ){
+                let volume = load_user_stats(&ctx.accounts.maker_stats)?.map_or(0, |stats| stats.volume);
+                if let Some(tier) = tier_for(tiers, volume){
+                    let maker_fee = apply_discount(bps_of(notional, tier.maker_bps.max(0) as u16), stake_discount);
+                    if maker_fee > max_fee_initializer{
+                        return Err(ScrtEscrowErrors::InitializerFeeExceedsMax.into());
+                    }
+                    fee_amount_initializer = maker_fee;
+                    maker_rebate = bps_of(notional, tier.maker_bps.min(0).unsigned_abs());
+                    // the base rate, the taker pays their own tier at fill
+                    fee_amount_taker = bps_of(notional, tiers[0].taker_bps);
+                    fee_tiered = true;
+                }
+            }
+
+            // untiered fee token fees track a USD target
+            let usd_fees = ctx.accounts.pda_account.usd_fees;
+            if !fee_tiered && usd_fees.enabled() && initializer_fee_mint == *ctx.accounts.fee_token.key{
+                let (maker_fee, taker_fee) = usd_fees.fees(&ctx.accounts.price_feed, ctx.program_id)?;
+                let maker_fee = apply_discount(maker_fee, stake_discount);
+                if maker_fee > max_fee_initializer{
+                    return Err(ScrtEscrowErrors::InitializerFeeExceedsMax.into());
+                }
+                fee_amount_initializer = maker_fee;
+                fee_amount_taker = taker_fee;
+            }
+
+            // fees in another accepted mint follow that mint's own rate
+            if let Some(fee_mint) = ctx.accounts.pda_account.fee_mint(&initializer_fee_mint){
+                let notional = fee_notional(
+                    &initializer_fee_mint,
+                    ctx.accounts.deposit_token.key,
+                    initializer_amount,
+                    ctx.accounts.receive_token.key,
+                    taker_amount,
+                );
+                let maker_fee = apply_discount(fee_mint.fee(notional)?, stake_discount);
+                if maker_fee > max_fee_initializer{
+                    return Err(ScrtEscrowErrors::InitializerFeeExceedsMax.into());
+                }
+                fee_amount_initializer = maker_fee;
+                maker_rebate = 0;
+            }
+
+            // check fee balance
+            let mut fee = fee_amount_initializer;
+            if initializer_fee_mint == *ctx.accounts.deposit_token.key{
+                fee = fee + initializer_amount;
+            }
+            if fee_paying.amount < fee{
+                return Err(ScrtEscrowErrors::InitializerFeeAmountInsufficient.into());
+            }
         }
 
         // check token balance
@@ -82,6 +321,19 @@ pub mod scrt_escrow {
             return Err(ScrtEscrowErrors::InitializerTokenAmountInsufficient.into());
         }
 
+        // validate against the pair's market, if one exists
+        if let Some(mut market) = load_market(&ctx.accounts.market)?{
+            let (base_amount, quote_amount) = market.base_quote(
+                ctx.accounts.deposit_token.key,
+                initializer_amount,
+                taker_amount,
+            );
+            market.validate_order(base_amount, quote_amount)?;
+            market.open_orders += 1;
+            market.exit(ctx.program_id)?;
+            ctx.accounts.escrow_account.market = *ctx.accounts.market.key;
+        }
+
 
         //initializer
         ctx.accounts.escrow_account.initializer_key = *ctx.accounts.initializer.key;
@@ -106,20 +358,7 @@ pub mod scrt_escrow {
             *ctx.accounts.vault_account.to_account_info().key;
 
         //vault authority
-        let (vault_authority, _vault_authority_bump) = Pubkey::find_program_address(
-            &[
-                ESCROW_PDA_SEED,
-                ctx.accounts.escrow_account.to_account_info().key.as_ref(),
-            ],
-            ctx.program_id,
-        );
-
-        token::set_authority(
-            ctx.accounts.into_set_authority_context(),
-            AuthorityType::AccountOwner,
-            Some(vault_authority),
-        )?;        
-        ctx.accounts.escrow_account.vault_authority = vault_authority;
+        ctx.accounts.escrow_account.vault_authority = *ctx.accounts.vault_authority.key;
 
 
         //deposit token
@@ -140,14 +379,22 @@ pub mod scrt_escrow {
         )?;
 
         //deposit fee from initializer fee paying token
-        if fee_amount_initializer > 0{
-            token::transfer(
-                ctx.accounts.into_transfer_fee_to_vault_fee_context(),
-                fee_amount_initializer,
-            )?;    
+        if let Some((fee_paying, vault_fee_account)) = &fee_accounts{
+            if fee_amount_initializer > 0{
+                token::transfer(
+                    ctx.accounts.into_transfer_fee_to_vault_fee_context(fee_paying, vault_fee_account),
+                    fee_amount_initializer,
+                )?;
+            }
         }
 
-        ctx.accounts.escrow_account.initialized = 1;
+        ctx.accounts.escrow_account.status = EscrowStatus::Open;
+        let mut open_orders = load_open_orders(&ctx.accounts.open_orders, ctx.accounts.initializer.key, ctx.program_id)?;
+        ctx.accounts.escrow_account.order_epoch = order_epoch(&open_orders);
+        if let Some(open_orders) = open_orders.as_mut(){
+            open_orders.insert(*ctx.accounts.escrow_account.to_account_info().key)?;
+            open_orders.exit(ctx.program_id)?;
+        }
         //fee colecting account
         ctx.accounts.escrow_account.fee_collect_token_account =
             *ctx.accounts.fee_collect_token_account.to_account_info().key;
@@ -155,15 +402,27 @@ pub mod scrt_escrow {
         //fee amounts
         ctx.accounts.escrow_account.fee_amount_initializer = fee_amount_initializer;
         ctx.accounts.escrow_account.fee_amount_taker = fee_amount_taker;
+        ctx.accounts.escrow_account.hook = options.hook;
+        ctx.accounts.escrow_account.canonical_receive = options.canonical_receive;
+        ctx.accounts.escrow_account.refund_token_account =
+            *ctx.accounts.refund_token_account.to_account_info().key;
+        ctx.accounts.escrow_account.proceeds_split = options.proceeds_split;
+        ctx.accounts.escrow_account.proceeds_owners = proceeds_owners;
+        ctx.accounts.escrow_account.fee_tiered = fee_tiered;
+        ctx.accounts.escrow_account.maker_rebate = maker_rebate;
+        ctx.accounts.escrow_account.initializer_fee_mint = initializer_fee_mint;
+        ctx.accounts.escrow_account.fee_deducted = options.deduct_fees;
+        ctx.accounts.escrow_account.conditions = options.conditions;
+        ctx.accounts.escrow_account.referrer = referrer;
 
-        // initializer fee paying token account
+        // initializer fee paying token account, the deposit account stands
+        // in when fees are deducted so cancel still has somewhere to refund
         ctx.accounts
             .escrow_account
-            .initializer_fee_paying_token_account = *ctx
-            .accounts
-            .initializer_fee_paying_token_account
-            .to_account_info()
-            .key;
+            .initializer_fee_paying_token_account = match &fee_accounts{
+            Some((fee_paying, _)) => *fee_paying.to_account_info().key,
+            None => *ctx.accounts.initializer_deposit_token_account.to_account_info().key,
+        };
 
         emit!(InitializEvent {
             initializer: *ctx.accounts.initializer.key,
@@ -175,6 +434,9 @@ pub mod scrt_escrow {
             receive_amount: taker_amount,
             initializer_fee_amount: fee_amount_initializer,
             taker_fee_amount: fee_amount_taker,
+            proceeds_recipient: ctx.accounts.escrow_account.initializer_receive_token_account,
+            refund_recipient: ctx.accounts.escrow_account.refund_token_account,
+            proceeds_split: ctx.accounts.escrow_account.proceeds_split.clone(),
         });
 
         Ok(())
@@ -188,8 +450,22 @@ pub mod scrt_escrow {
         pub receive_token: Pubkey,
         pub deposit_amount: u64,
         pub receive_amount: u64,
+        pub refund_recipient: Pubkey,
+        pub status: EscrowStatus,
     }    
-    pub fn cancel(ctx: Context<Cancel>) -> ProgramResult {
+    /// Refunds a live escrow. Once its expiry has passed anyone may call it,
+    /// and the escrow is marked expired rather than cancelled.
+    #[allow(clippy::redundant_slicing)]
+    pub fn cancel<'info>(ctx: Context<'_, '_, '_, 'info, Cancel<'info>>) -> ProgramResult {
+        if !ctx.accounts.escrow_account.status.is_live(){
+            return Err(ScrtEscrowErrors::EscrowNotOpen.into());
+        }
+        let expired = expired(&ctx.accounts.escrow_account.conditions, Clock::get()?.unix_timestamp);
+        if !expired && !ctx.accounts.initializer.is_signer{
+            return Err(ScrtEscrowErrors::CancelUnauthorized.into());
+        }
+        check_cancel(&ctx.accounts.escrow_account.conditions)?;
+
         let (_vault_authority, vault_authority_bump) = Pubkey::find_program_address(
             &[
                 ESCROW_PDA_SEED,
@@ -225,7 +501,8 @@ pub mod scrt_escrow {
             &[vault_fee_authority_bump],
         ];
 
-        if ctx.accounts.escrow_account.fee_amount_initializer > 0{
+        // deducted fees were never prepaid
+        if !ctx.accounts.escrow_account.fee_deducted && ctx.accounts.escrow_account.fee_amount_initializer > 0{
             token::transfer(
                 ctx.accounts
                     .into_transfer_to_initializer_fee_paying_context()
@@ -234,7 +511,22 @@ pub mod scrt_escrow {
             )?;    
         }
 
-        ctx.accounts.escrow_account.initialized = 0;
+        ctx.accounts.escrow_account.status = if expired{
+            EscrowStatus::Expired
+        } else {
+            EscrowStatus::Cancelled
+        };
+        let escrow_key = *ctx.accounts.escrow_account.to_account_info().key;
+        if let Some(mut open_orders) = load_open_orders(&ctx.accounts.open_orders, ctx.accounts.initializer.key, ctx.program_id)?{
+            open_orders.remove(&escrow_key);
+            open_orders.exit(ctx.program_id)?;
+        }
+        if ctx.accounts.escrow_account.market == *ctx.accounts.market.key{
+            if let Some(mut market) = load_market(&ctx.accounts.market)?{
+                market.record_close(&ctx.accounts.escrow_account, false);
+                market.exit(ctx.program_id)?;
+            }
+        }
         //close
         token::close_account(
             ctx.accounts
@@ -242,12 +534,440 @@ pub mod scrt_escrow {
                 .with_signer(&[&authority_seeds[..]]),
         )?;
 
+        //optional receipt
+        if let Some(receipt) = ctx.remaining_accounts.first() {
+            let escrow = &ctx.accounts.escrow_account;
+            receipt::write_receipt(
+                receipt,
+                &ctx.accounts.initializer,
+                &ctx.accounts.system_program,
+                ctx.program_id,
+                &SettlementReceipt {
+                    owner: *ctx.accounts.initializer.key,
+                    payer: *ctx.accounts.initializer.key,
+                    escrow: *escrow.to_account_info().key,
+                    initializer: escrow.initializer_key,
+                    taker: Pubkey::default(),
+                    deposit_token: escrow.deposit_token,
+                    receive_token: escrow.receive_token,
+                    initializer_amount: escrow.initializer_amount,
+                    taker_amount: escrow.taker_amount,
+                    fee_amount_initializer: 0,
+                    fee_amount_taker: 0,
+                    status: escrow.status,
+                    slot: Clock::get()?.slot,
+                },
+            )?;
+        }
+
         emit!(CancelEvent {
             initializer: *ctx.accounts.initializer.key,
             deposit_token: ctx.accounts.escrow_account.deposit_token,
             receive_token: ctx.accounts.escrow_account.receive_token,
             deposit_amount: ctx.accounts.escrow_account.initializer_amount ,
             receive_amount: ctx.accounts.escrow_account.taker_amount,
+            refund_recipient: ctx.accounts.escrow_account.refund_token_account,
+            status: ctx.accounts.escrow_account.status,
+        });
+        Ok(())
+    }
+
+    /// Reclaims the rent of a settled escrow, which frees its address for
+    /// the initializer's next escrow of the pair.
+    pub fn close_escrow(ctx: Context<CloseEscrow>) -> ProgramResult {
+        if ctx.accounts.escrow_account.status.is_live(){
+            return Err(ScrtEscrowErrors::EscrowStillOpen.into());
+        }
+        Ok(())
+    }
+
+    /// Cancels every escrow passed in the remaining accounts. All escrows
+    /// must use the fee config in `pda_account`.
+    pub fn batch_cancel<'info>(ctx: Context<'_, '_, '_, 'info, BatchCancel<'info>>) -> ProgramResult {
+        if ctx.remaining_accounts.len().checked_rem(BATCH_CANCEL_ACCOUNTS) != Some(0){
+            return Err(ScrtEscrowErrors::InvalidRemainingAccounts.into());
+        }
+        let mut open_orders = load_open_orders(&ctx.accounts.open_orders, ctx.accounts.initializer.key, ctx.program_id)?;
+
+        let (_vault_fee_authority, vault_fee_authority_bump) = Pubkey::find_program_address(
+            &[
+                ctx.accounts.pda_account.to_account_info().key.as_ref(),
+                ESCROW_PDA_SEED
+            ],
+            ctx.program_id,
+        );
+        let fee_seeds = &[
+            ctx.accounts.pda_account.to_account_info().key.as_ref(),
+            ESCROW_PDA_SEED,
+            &[vault_fee_authority_bump],
+        ];
+
+        for group in ctx.remaining_accounts.chunks(BATCH_CANCEL_ACCOUNTS) {
+            let escrow_info = &group[0];
+            let vault = &group[1];
+            let vault_authority = &group[2];
+            let refund_token_account = &group[3];
+            let initializer_fee_paying_token_account = &group[4];
+            let market_info = &group[5];
+
+            let mut escrow: Account<EscrowAccount> = Account::try_from(escrow_info)?;
+            if escrow.initializer_key != *ctx.accounts.initializer.key
+                || escrow.fee_token != ctx.accounts.pda_account.fee_token
+                || escrow.vault_account != *vault.key
+                || escrow.vault_authority != *vault_authority.key
+                || escrow.refund_token_account != *refund_token_account.key
+                || escrow.initializer_fee_paying_token_account != *initializer_fee_paying_token_account.key
+            {
+                return Err(ScrtEscrowErrors::BatchEscrowMismatch.into());
+            }
+            if !escrow.status.is_live(){
+                return Err(ScrtEscrowErrors::EscrowNotOpen.into());
+            }
+            if escrow.initializer_fee_mint != escrow.fee_token{
+                return Err(ScrtEscrowErrors::FeeMintUnsupported.into());
+            }
+            check_cancel(&escrow.conditions)?;
+
+            let (_vault_authority, vault_authority_bump) = Pubkey::find_program_address(
+                &[ESCROW_PDA_SEED, escrow_info.key.as_ref()],
+                ctx.program_id,
+            );
+            let authority_seeds = &[
+                ESCROW_PDA_SEED,
+                escrow_info.key.as_ref(),
+                &[vault_authority_bump],
+            ];
+
+            //withdraw initilzier token
+            token::transfer(
+                ctx.accounts
+                    .into_transfer_context(
+                        vault.clone(),
+                        refund_token_account.clone(),
+                        vault_authority.clone(),
+                    )
+                    .with_signer(&[&authority_seeds[..]]),
+                escrow.initializer_amount,
+            )?;
+
+            //withdraw fee
+            if !escrow.fee_deducted && escrow.fee_amount_initializer > 0{
+                token::transfer(
+                    ctx.accounts
+                        .into_transfer_context(
+                            ctx.accounts.vault_fee_account.clone(),
+                            initializer_fee_paying_token_account.clone(),
+                            ctx.accounts.vault_fee_authority.clone(),
+                        )
+                        .with_signer(&[&fee_seeds[..]]),
+                    escrow.fee_amount_initializer,
+                )?;
+            }
+
+            //close vault
+            token::close_account(
+                ctx.accounts
+                    .into_close_vault_context(vault.clone(), vault_authority.clone())
+                    .with_signer(&[&authority_seeds[..]]),
+            )?;
+
+            escrow.status = if expired(&escrow.conditions, Clock::get()?.unix_timestamp){
+                EscrowStatus::Expired
+            } else {
+                EscrowStatus::Cancelled
+            };
+            if let Some(open_orders) = open_orders.as_mut(){
+                open_orders.remove(escrow_info.key);
+            }
+            if escrow.market == *market_info.key{
+                if let Some(mut market) = load_market(market_info)?{
+                    market.record_close(&escrow, false);
+                    market.exit(ctx.program_id)?;
+                }
+            }
+
+            emit!(CancelEvent {
+                initializer: escrow.initializer_key,
+                deposit_token: escrow.deposit_token,
+                receive_token: escrow.receive_token,
+                deposit_amount: escrow.initializer_amount,
+                receive_amount: escrow.taker_amount,
+                refund_recipient: escrow.refund_token_account,
+                status: escrow.status,
+            });
+            escrow.exit(ctx.program_id)?;
+        }
+        if let Some(open_orders) = open_orders.as_ref(){
+            open_orders.exit(ctx.program_id)?;
+        }
+        Ok(())
+    }
+
+    #[event]
+    pub struct RingTradeEvent {
+        pub cranker: Pubkey,
+        pub legs: u32,
+    }
+
+    /// Settles a cycle of escrows where each leg's deposit covers the next
+    /// leg's ask. Each leg pays its prepaid initializer fee, and its taker
+    /// fee in the deposit mint out of its surplus, the rest of which goes
+    /// back to its maker. The cranker pays no fees.
+    pub fn ring_trade<'info>(ctx: Context<'_, '_, '_, 'info, RingTrade<'info>>) -> ProgramResult {
+        if ctx.remaining_accounts.len().checked_rem(RING_TRADE_ACCOUNTS) != Some(0){
+            return Err(ScrtEscrowErrors::InvalidRemainingAccounts.into());
+        }
+        let groups: Vec<&[AccountInfo<'info>]> =
+            ctx.remaining_accounts.chunks(RING_TRADE_ACCOUNTS).collect();
+        let legs = groups.len();
+        if legs < 2{
+            return Err(ScrtEscrowErrors::RingTooShort.into());
+        }
+
+        //load and check every leg before moving any tokens
+        let mut escrows: Vec<Account<EscrowAccount>> = Vec::with_capacity(legs);
+        for group in groups.iter() {
+            let escrow: Account<EscrowAccount> = Account::try_from(&group[0])?;
+            let open_orders = load_open_orders(&group[2], group[1].key, ctx.program_id)?;
+            if !escrow.status.is_live(){
+                return Err(ScrtEscrowErrors::EscrowNotOpen.into());
+            }
+            if escrow.order_epoch != order_epoch(&open_orders){
+                return Err(ScrtEscrowErrors::StaleOrderEpoch.into());
+            }
+            if escrow.fee_token != ctx.accounts.pda_account.fee_token
+                || escrow.initializer_key != *group[1].key
+                || escrow.vault_account != *group[3].key
+                || escrow.vault_authority != *group[4].key
+                || escrow.initializer_receive_token_account != *group[5].key
+                || escrow.refund_token_account != *group[6].key
+                || ctx.accounts.pda_account.fee_collector(&escrow.fee_token, &escrow.fee_collect_token_account) != Some(*group[7].key)
+                || escrow.receive_token != *group[9].key
+                || escrows.iter().any(|other| other.to_account_info().key == group[0].key)
+            {
+                return Err(ScrtEscrowErrors::BatchEscrowMismatch.into());
+            }
+            if !escrow.proceeds_split.is_empty(){
+                return Err(ScrtEscrowErrors::ProceedsSplitUnsupported.into());
+            }
+            if escrow.fee_tiered{
+                return Err(ScrtEscrowErrors::TieredFeesUnsupported.into());
+            }
+            if escrow.initializer_fee_mint != escrow.fee_token{
+                return Err(ScrtEscrowErrors::FeeMintUnsupported.into());
+            }
+            if escrow.fee_deducted{
+                return Err(ScrtEscrowErrors::FeeDeductionUnsupported.into());
+            }
+            if !escrow.conditions.is_empty(){
+                return Err(ScrtEscrowErrors::ConditionsUnsupported.into());
+            }
+            if escrow.hook.is_some(){
+                return Err(ScrtEscrowErrors::HookUnsupported.into());
+            }
+            if escrow.referrer != Pubkey::default(){
+                return Err(ScrtEscrowErrors::ReferrerUnsupported.into());
+            }
+            escrows.push(escrow);
+        }
+        let mut taker_fees = Vec::with_capacity(legs);
+        for (i, group) in groups.iter().enumerate() {
+            let escrow = &escrows[i];
+            let next = &escrows[(i + 1) % legs];
+            if escrow.deposit_token != next.receive_token{
+                return Err(ScrtEscrowErrors::RingMintMismatch.into());
+            }
+            if escrow.initializer_amount < next.taker_amount{
+                return Err(ScrtEscrowErrors::RingAmountUnsatisfiable.into());
+            }
+            // the next maker takes this leg, but its taker fee comes out of
+            // this leg's surplus, so it's charged at the deposit mint's rate
+            let pda = &ctx.accounts.pda_account;
+            let taker_fee = settle::taker_fee(
+                pda,
+                escrow,
+                &settle::TakerFeeInputs {
+                    taker: &next.initializer_key,
+                    fee_mint: &escrow.deposit_token,
+                    stats: None,
+                    stake: None,
+                    price_feed: Some(&ctx.accounts.price_feed),
+                },
+                ctx.program_id,
+            )?;
+            if taker_fee > 0{
+                if !pda.accepts(&escrow.deposit_token){
+                    return Err(ScrtEscrowErrors::RingFeeUnpayable.into());
+                }
+                if escrow.initializer_amount - next.taker_amount < taker_fee{
+                    return Err(ScrtEscrowErrors::RingAmountUnsatisfiable.into());
+                }
+                if pda.fee_collector(&escrow.deposit_token, &escrow.fee_collect_token_account) != Some(*group[11].key){
+                    return Err(ScrtEscrowErrors::BatchEscrowMismatch.into());
+                }
+            }
+            taker_fees.push(taker_fee);
+        }
+
+        let (_vault_fee_authority, vault_fee_authority_bump) = Pubkey::find_program_address(
+            &[
+                ctx.accounts.pda_account.to_account_info().key.as_ref(),
+                ESCROW_PDA_SEED
+            ],
+            ctx.program_id,
+        );
+        let fee_seeds = &[
+            ctx.accounts.pda_account.to_account_info().key.as_ref(),
+            ESCROW_PDA_SEED,
+            &[vault_fee_authority_bump],
+        ];
+
+        for (i, group) in groups.iter().enumerate() {
+            let next_group = groups[(i + 1) % legs];
+            let next_taker_amount = escrows[(i + 1) % legs].taker_amount;
+            let escrow = &mut escrows[i];
+            let initializer = &group[1];
+            let vault = &group[3];
+            let vault_authority = &group[4];
+            let taker_fee = taker_fees[i];
+            let surplus = escrow.initializer_amount - next_taker_amount - taker_fee;
+
+            let (_vault_authority, vault_authority_bump) = Pubkey::find_program_address(
+                &[ESCROW_PDA_SEED, group[0].key.as_ref()],
+                ctx.program_id,
+            );
+            let authority_seeds = &[
+                ESCROW_PDA_SEED,
+                group[0].key.as_ref(),
+                &[vault_authority_bump],
+            ];
+
+            if taker_fee > 0{
+                token::transfer(
+                    ctx.accounts
+                        .into_transfer_context(vault.clone(), group[11].clone(), vault_authority.clone())
+                        .with_signer(&[&authority_seeds[..]]),
+                    taker_fee,
+                )?;
+            }
+
+            // take initializer fee from valut
+            if escrow.fee_amount_initializer > 0{
+                token::transfer(
+                    ctx.accounts
+                        .into_transfer_context(
+                            ctx.accounts.vault_fee_account.clone(),
+                            group[7].clone(),
+                            ctx.accounts.vault_fee_authority.clone(),
+                        )
+                        .with_signer(&[&fee_seeds[..]]),
+                    escrow.fee_amount_initializer,
+                )?;
+            }
+
+            //pay the next leg's maker and refund any surplus
+            let next_recipient = settle::pay_proceeds(
+                &settle::ProceedsSource {
+                    from: vault.clone(),
+                    authority: vault_authority.clone(),
+                    signer_seeds: &[&authority_seeds[..]],
+                    mint: &next_group[9],
+                    payer: &ctx.accounts.cranker,
+                    system_program: &ctx.accounts.system_program,
+                    token_program: &ctx.accounts.token_program,
+                    rent: &ctx.accounts.rent.to_account_info(),
+                },
+                &[settle::Payout {
+                    recipient: next_group[5].clone(),
+                    amount: next_taker_amount,
+                    owner: *next_group[1].key,
+                    claim: next_group[10].clone(),
+                }],
+                ctx.program_id,
+            )?;
+            if surplus > 0{
+                token::transfer(
+                    ctx.accounts
+                        .into_transfer_context(vault.clone(), group[6].clone(), vault_authority.clone())
+                        .with_signer(&[&authority_seeds[..]]),
+                    surplus,
+                )?;
+            }
+
+            //close vault
+            token::close_account(
+                ctx.accounts
+                    .into_close_vault_context(vault.clone(), initializer.clone(), vault_authority.clone())
+                    .with_signer(&[&authority_seeds[..]]),
+            )?;
+
+            escrow.status = EscrowStatus::Filled;
+            if let Some(mut open_orders) = load_open_orders(&group[2], group[1].key, ctx.program_id)?{
+                open_orders.remove(group[0].key);
+                open_orders.exit(ctx.program_id)?;
+            }
+            if escrow.market == *group[8].key{
+                if let Some(mut market) = load_market(&group[8])?{
+                    market.record_close(escrow, false);
+                    market.record_volume(&escrow.deposit_token, next_taker_amount, escrow.taker_amount);
+                    market.exit(ctx.program_id)?;
+                }
+            }
+            settle::record_fill(
+                &group[12],
+                &escrow.deposit_token,
+                &escrow.receive_token,
+                next_taker_amount,
+                escrow.taker_amount,
+                ctx.program_id,
+            )?;
+
+            emit!(ExchangeEvent {
+                initializer: escrow.initializer_key,
+                taker: *next_group[1].key,
+                deposit_token: escrow.deposit_token,
+                receive_token: escrow.receive_token,
+                deposit_amount: next_taker_amount,
+                receive_amount: escrow.taker_amount,
+                proceeds_recipient: *group[5].key,
+                taker_recipient: next_recipient,
+                proceeds_split: escrow.proceeds_split.clone(),
+                referrer: Pubkey::default(),
+                referral_amount: 0,
+                maker_referrer: Pubkey::default(),
+                maker_referral_amount: 0,
+            });
+        }
+
+        for escrow in escrows.iter() {
+            escrow.exit(ctx.program_id)?;
+        }
+
+        emit!(RingTradeEvent {
+            cranker: *ctx.accounts.cranker.key,
+            legs: legs as u32,
+        });
+        Ok(())
+    }
+
+    #[event]
+    pub struct OrderEpochEvent {
+        pub owner: Pubkey,
+        pub order_epoch: u64,
+    }
+
+    /// Invalidates every escrow the owner created before this call. Their
+    /// deposits stay reclaimable through `cancel`.
+    pub fn bump_order_epoch(ctx: Context<BumpOrderEpoch>) -> ProgramResult {
+        let open_orders = &mut ctx.accounts.open_orders;
+        open_orders.order_epoch = open_orders
+            .order_epoch
+            .checked_add(1)
+            .ok_or(ScrtEscrowErrors::AmountOverflow)?;
+
+        emit!(OrderEpochEvent {
+            owner: *ctx.accounts.owner.key,
+            order_epoch: open_orders.order_epoch,
         });
         Ok(())
     }
@@ -260,17 +980,155 @@ pub mod scrt_escrow {
         pub receive_token: Pubkey,        
         pub deposit_amount: u64,
         pub receive_amount: u64,
+        pub proceeds_recipient: Pubkey,
+        pub taker_recipient: Pubkey,
+        pub proceeds_split: Vec<ProceedsShare>,
+        pub referrer: Pubkey,
+        pub referral_amount: u64,
+        pub maker_referrer: Pubkey,
+        pub maker_referral_amount: u64,
     }
 
-    pub fn exchange(ctx: Context<Exchange>) -> ProgramResult {
+    #[allow(clippy::assign_op_pattern, clippy::redundant_slicing)]
+    pub fn exchange<'info>(
+        ctx: Context<'_, '_, '_, 'info, Exchange<'info>>,
+        expected_taker_amount: u64,
+        expected_initializer_amount: u64,
+        max_taker_fee: u64,
+        preimages: Vec<Vec<u8>>,
+        extras: ExchangeExtras,
+    ) -> ProgramResult {
+        if !ctx.accounts.escrow_account.status.is_live(){
+            return Err(ScrtEscrowErrors::EscrowNotOpen.into());
+        }
+        let mut open_orders = load_open_orders(&ctx.accounts.open_orders, ctx.accounts.initializer.key, ctx.program_id)?;
+        if ctx.accounts.escrow_account.order_epoch != order_epoch(&open_orders){
+            return Err(ScrtEscrowErrors::StaleOrderEpoch.into());
+        }
 
-        // check fee balance
-        let mut fee = ctx.accounts.escrow_account.fee_amount_taker;
-        if ctx.accounts.escrow_account.fee_token == ctx.accounts.escrow_account.receive_token{
-            fee = fee + ctx.accounts.escrow_account.fee_amount_taker;
+        // check the terms the taker signed for
+        if ctx.accounts.escrow_account.taker_amount != expected_taker_amount{
+            return Err(ScrtEscrowErrors::TakerAmountMismatch.into());
+        }
+        if ctx.accounts.escrow_account.initializer_amount != expected_initializer_amount{
+            return Err(ScrtEscrowErrors::InitializerAmountMismatch.into());
+        }
+
+        //the optional accounts come first, then the split recipients and
+        //their owners' claim accounts, then the condition accounts
+        let (extra_accounts, remaining_accounts) = extras.take(ctx.remaining_accounts)?;
+        let split_len = ctx.accounts.escrow_account.proceeds_split.len() * 2;
+        let conditions_len = condition_accounts_len(&ctx.accounts.escrow_account.conditions);
+        if remaining_accounts.len() < split_len + conditions_len{
+            return Err(ScrtEscrowErrors::InvalidRemainingAccounts.into());
+        }
+        check_fill(
+            &ctx.accounts.escrow_account.conditions,
+            &FillWitness {
+                taker: ctx.accounts.taker.key,
+                preimages: &preimages,
+                accounts: &remaining_accounts[split_len..split_len + conditions_len],
+            },
+            ctx.program_id,
+        )?;
+
+        let deducted = ctx.accounts.escrow_account.fee_deducted;
+
+        let fee_token = ctx.accounts.escrow_account.fee_token;
+        // with tiers on both sides' volume must be tracked, so neither stats
+        // account may be left out
+        if !ctx.accounts.pda_account.fee_tiers.is_empty()
+            && (extra_accounts.maker_stats.is_none() || extra_accounts.taker_stats.is_none())
+        {
+            return Err(ScrtEscrowErrors::UserStatsMissing.into());
+        }
+        let maker_stats = match extra_accounts.maker_stats{
+            Some(info) => load_stats_of(info, ctx.accounts.initializer.key, &fee_token, ctx.program_id)?,
+            None => None,
+        };
+        let taker_stats = match extra_accounts.taker_stats{
+            Some(info) => load_stats_of(info, ctx.accounts.taker.key, &fee_token, ctx.program_id)?,
+            None => None,
+        };
+        let notional = settle::notional(&ctx.accounts.escrow_account, &ctx.accounts.escrow_account.fee_token);
+        // deducted fees come out of the traded amounts, so only other takers
+        // need a fee paying account
+        let taker_fee_paying = match extra_accounts.taker_fee_paying{
+            _ if deducted => None,
+            Some(info) => {
+                let account: Account<TokenAccount> = Account::try_from(info)?;
+                if account.owner != *ctx.accounts.taker.key{
+                    return Err(ScrtEscrowErrors::InvalidFeePayingAccount.into());
+                }
+                if !ctx.accounts.pda_account.accepts(&account.mint){
+                    return Err(ScrtEscrowErrors::InvalidFeeMint.into());
+                }
+                let collector = ctx.accounts.pda_account.fee_collector(&account.mint, &ctx.accounts.escrow_account.fee_collect_token_account);
+                if collector != Some(*ctx.accounts.taker_fee_collect_token_account.key){
+                    return Err(ScrtEscrowErrors::InvalidFeeCollector.into());
+                }
+                Some(account)
+            }
+            None => return Err(ScrtEscrowErrors::TakerFeeAccountMissing.into()),
+        };
+        // takers paying in another accepted mint pay that mint's own rate
+        let taker_fee_mint = taker_fee_paying.as_ref().map_or(ctx.accounts.escrow_account.receive_token, |account| account.mint);
+        let taker_pays_fee_token = !deducted && taker_fee_mint == ctx.accounts.escrow_account.fee_token;
+        let taker_fee = settle::taker_fee(
+            &ctx.accounts.pda_account,
+            &ctx.accounts.escrow_account,
+            &settle::TakerFeeInputs {
+                taker: ctx.accounts.taker.key,
+                fee_mint: &taker_fee_mint,
+                stats: taker_stats.as_deref(),
+                stake: extra_accounts.taker_stake,
+                price_feed: extra_accounts.price_feed,
+            },
+            ctx.program_id,
+        )?;
+        if taker_fee > max_taker_fee{
+            return Err(ScrtEscrowErrors::TakerFeeExceedsMax.into());
         }
-        if ctx.accounts.taker_fee_paying_token_account.amount < fee{
-            return Err(ScrtEscrowErrors::TakerFeeAmountInsufficient.into());
+        // the maker rebate is paid out of protocol revenue, so only once
+        // the fee config collects it in its revenue vault
+        let fee_revenue_vault = ctx.accounts.pda_account.fee_revenue_vault;
+        let rebate_vault = match extra_accounts.fee_revenue_vault{
+            _ if ctx.accounts.escrow_account.maker_rebate == 0 || fee_revenue_vault == Pubkey::default() => None,
+            Some(info) if *info.key == fee_revenue_vault => Some(info),
+            Some(_) => return Err(ScrtEscrowErrors::FeeCollectorMismatch.into()),
+            None => return Err(ScrtEscrowErrors::FeeRevenueVaultMissing.into()),
+        };
+
+        // check fee balance
+        if deducted{
+            // deducted fees go to the configured collector's accounts for
+            // the traded mints, or to their revenue vaults once fees are
+            // distributed
+            let pda = &ctx.accounts.pda_account;
+            let collector = pda.deducted_fee_collector;
+            let escrow = &ctx.accounts.escrow_account;
+            for (info, mint) in [
+                (&ctx.accounts.initializer_fee_collect_token_account, &escrow.deposit_token),
+                (&ctx.accounts.taker_fee_collect_token_account, &escrow.receive_token),
+            ]{
+                let account: Account<TokenAccount> = Account::try_from(info)?;
+                let valid = if pda.distributes_fees(){
+                    *info.key == pda.revenue_vault(mint)
+                } else {
+                    account.owner == collector
+                };
+                if !valid || account.mint != *mint{
+                    return Err(ScrtEscrowErrors::InvalidFeeCollector.into());
+                }
+            }
+        } else if let Some(taker_fee_paying) = &taker_fee_paying{
+            let mut fee = taker_fee;
+            if taker_fee_mint == ctx.accounts.escrow_account.receive_token{
+                fee = fee + taker_fee;
+            }
+            if taker_fee_paying.amount < fee{
+                return Err(ScrtEscrowErrors::TakerFeeAmountInsufficient.into());
+            }
         }
 
         // check token balance
@@ -292,12 +1150,59 @@ pub mod scrt_escrow {
             &[vault_authority_bump],
         ];
 
+        // a referrer, if the taker names one, takes a cut of the taker's fee
+        let mut referrer_stats = None;
+        if let Some((referrer_token_account, referrer_stats_account)) = extra_accounts.referrer{
+            let (referrer, stats) = load_referrer(referrer_token_account, referrer_stats_account, &fee_token)?;
+            if referrer.owner == *ctx.accounts.taker.key{
+                return Err(ScrtEscrowErrors::SelfReferral.into());
+            }
+            referrer_stats = Some(stats);
+        }
+        // referrers are paid in the fee token only
+        let mut taker_cut = 0;
+        if referrer_stats.is_some() && !deducted && taker_pays_fee_token{
+            taker_cut = ctx.accounts.pda_account.referral_cut(taker_fee);
+        }
+        // whoever referred the maker takes a cut of the initializer fee,
+        // nothing if their account is gone
+        let maker_referrer = match extra_accounts.maker_referrer{
+            _ if ctx.accounts.escrow_account.referrer == Pubkey::default() => None,
+            Some((token_account, stats_account)) if *token_account.key == ctx.accounts.escrow_account.referrer => {
+                Some((token_account, stats_account))
+            }
+            Some(_) => return Err(ScrtEscrowErrors::InvalidReferrer.into()),
+            None => return Err(ScrtEscrowErrors::ReferrerMissing.into()),
+        };
+        let mut maker_cut = 0;
+        if let Some((token_account, _)) = maker_referrer{
+            if !deducted
+                && ctx.accounts.escrow_account.initializer_fee_mint == fee_token
+                && claim::can_receive(token_account, &fee_token)
+            {
+                maker_cut = ctx.accounts.pda_account.referral_cut(ctx.accounts.escrow_account.fee_amount_initializer);
+            }
+        }
+
         // take taker fee
-        if ctx.accounts.escrow_account.fee_amount_taker > 0{
+        if let Some(taker_fee_paying) = &taker_fee_paying{
+            if taker_fee > taker_cut{
+                token::transfer(
+                    ctx.accounts.into_transfer_fee_from_taker_context(taker_fee_paying),
+                    taker_fee - taker_cut,
+                )?;
+            }
+            if let Some((referrer_token_account, _)) = extra_accounts.referrer.filter(|_| taker_cut > 0){
+                token::transfer(
+                    ctx.accounts.into_transfer_referral_from_taker_context(taker_fee_paying, referrer_token_account),
+                    taker_cut,
+                )?;
+            }
+        } else if taker_fee > 0{
             token::transfer(
-                ctx.accounts.into_transfer_fee_from_taker_context(),
-                ctx.accounts.escrow_account.fee_amount_taker,
-            )?;    
+                ctx.accounts.into_transfer_deducted_fee_from_taker_context(),
+                taker_fee,
+            )?;
         }
 
         let (_vault_fee_authority, vault_fee_authority_bump) = Pubkey::find_program_address(
@@ -314,26 +1219,169 @@ pub mod scrt_escrow {
         ];
 
         // take initializer fee from valut
-        if ctx.accounts.escrow_account.fee_amount_initializer > 0{
+        if deducted{
+            if ctx.accounts.escrow_account.fee_amount_initializer > 0{
+                token::transfer(
+                    ctx.accounts
+                        .into_transfer_deducted_fee_from_vault_context()
+                        .with_signer(&[&authority_seeds[..]]),
+                    ctx.accounts.escrow_account.fee_amount_initializer,
+                )?;
+            }
+        } else if ctx.accounts.escrow_account.fee_amount_initializer > maker_cut{
             token::transfer(
                 ctx.accounts
                     .into_transfer_fee_from_vault_context()
                     .with_signer(&[&authority_seeds1[..]]),
-                ctx.accounts.escrow_account.fee_amount_initializer,
+                ctx.accounts.escrow_account.fee_amount_initializer - maker_cut,
             )?;    
         }
+        if let Some((referrer_token_account, _)) = maker_referrer.filter(|_| maker_cut > 0){
+            token::transfer(
+                ctx.accounts
+                    .into_transfer_referral_from_vault_context(referrer_token_account)
+                    .with_signer(&[&authority_seeds1[..]]),
+                maker_cut,
+            )?;
+        }
+        // as far as the revenue goes, nothing if the maker's fee account is
+        // gone
+        if let Some(vault) = rebate_vault{
+            let rebate = if claim::can_receive(&ctx.accounts.initializer_fee_paying_token_account, &fee_token){
+                let revenue: Account<TokenAccount> = Account::try_from(vault)?;
+                min(ctx.accounts.escrow_account.maker_rebate, revenue.amount)
+            } else {
+                0
+            };
+            if rebate > 0{
+                token::transfer(
+                    ctx.accounts
+                        .into_transfer_rebate_context(vault)
+                        .with_signer(&[&authority_seeds1[..]]),
+                    rebate,
+                )?;
+            }
+        }
+        let referral_amount = taker_cut;
+        let mut referrer = Pubkey::default();
+        if let Some(mut stats) = referrer_stats{
+            stats.fills += 1;
+            stats.fee_earned += referral_amount;
+            stats.exit(ctx.program_id)?;
+            referrer = stats.owner;
+        }
+        // loaded only now, both sides may have the same referrer
+        let mut maker_referrer_key = Pubkey::default();
+        if let Some((token_account, stats_account)) = maker_referrer.filter(|_| maker_cut > 0){
+            let (_, mut stats) = load_referrer(token_account, stats_account, &fee_token)?;
+            stats.fills += 1;
+            stats.fee_earned += maker_cut;
+            stats.exit(ctx.program_id)?;
+            maker_referrer_key = stats.owner;
+        }
 
-        //exchange tokens
-        token::transfer(
-            ctx.accounts.into_transfer_to_initializer_context(),
-            ctx.accounts.escrow_account.taker_amount,
+        // both sides' volume counts towards their tiers
+        if let Some(notional) = notional{
+            let same_user = ctx.accounts.initializer.key == ctx.accounts.taker.key;
+            for mut stats in maker_stats.into_iter().chain(taker_stats.filter(|_| !same_user)){
+                stats.volume = stats.volume.saturating_add(notional);
+                stats.fills += 1;
+                stats.exit(ctx.program_id)?;
+            }
+        }
+
+        //create missing associated token accounts, paid by the taker
+        ata::create_if_missing(
+            &ctx.accounts.taker_receive_token_account,
+            &ctx.accounts.taker,
+            &ctx.accounts.deposit_token,
+            &ctx.accounts.taker,
+            &ctx.accounts.system_program,
+            &ctx.accounts.token_program,
+            &ctx.accounts.rent.to_account_info(),
+            &ctx.accounts.associated_token_program,
+        )?;
+        ata::check_token_account(
+            &ctx.accounts.taker_receive_token_account,
+            &ctx.accounts.escrow_account.deposit_token,
+        )?;
+        // what each side receives after deducted fees
+        let (maker_proceeds, taker_proceeds) = if deducted{
+            (
+                ctx.accounts.escrow_account.taker_amount - taker_fee,
+                ctx.accounts.escrow_account.initializer_amount - ctx.accounts.escrow_account.fee_amount_initializer,
+            )
+        } else {
+            (ctx.accounts.escrow_account.taker_amount, ctx.accounts.escrow_account.initializer_amount)
+        };
+
+        //split recipients lead the remaining accounts
+        let (split_accounts, remaining) = remaining_accounts.split_at(split_len);
+        let remaining = &remaining[conditions_len..];
+        let payouts: Vec<settle::Payout<'info>> = if split_len == 0{
+            if ctx.accounts.initializer_receive_token_account.data_is_empty() && ata::is_canonical(
+                ctx.accounts.initializer_receive_token_account.key,
+                ctx.accounts.initializer.key,
+                ctx.accounts.receive_token.key,
+            ){
+                ata::create_if_missing(
+                    &ctx.accounts.initializer_receive_token_account,
+                    &ctx.accounts.initializer,
+                    &ctx.accounts.receive_token,
+                    &ctx.accounts.taker,
+                    &ctx.accounts.system_program,
+                    &ctx.accounts.token_program,
+                    &ctx.accounts.rent.to_account_info(),
+                    &ctx.accounts.associated_token_program,
+                )?;
+            }
+            vec![settle::Payout {
+                recipient: ctx.accounts.initializer_receive_token_account.clone(),
+                amount: maker_proceeds,
+                owner: *ctx.accounts.initializer.key,
+                claim: ctx.accounts.claim_account.clone(),
+            }]
+        } else {
+            let escrow = &ctx.accounts.escrow_account;
+            let (recipients, claims) = split_accounts.split_at(escrow.proceeds_split.len());
+            if recipients.iter().zip(escrow.proceeds_split.iter()).any(|(info, share)| *info.key != share.recipient){
+                return Err(ScrtEscrowErrors::ProceedsSplitMismatch.into());
+            }
+            recipients
+                .iter()
+                .zip(claims.iter())
+                .zip(split_amount(maker_proceeds, &escrow.proceeds_split))
+                .zip(escrow.proceeds_owners.iter())
+                .map(|(((recipient, claim), amount), owner)| settle::Payout {
+                    recipient: recipient.clone(),
+                    amount,
+                    owner: *owner,
+                    claim: claim.clone(),
+                })
+                .collect()
+        };
+
+        //pay the maker's side, parking what an account can't take
+        let proceeds_recipient = settle::pay_proceeds(
+            &settle::ProceedsSource {
+                from: ctx.accounts.taker_deposit_token_account.to_account_info(),
+                authority: ctx.accounts.taker.clone(),
+                signer_seeds: &[],
+                mint: &ctx.accounts.receive_token,
+                payer: &ctx.accounts.taker,
+                system_program: &ctx.accounts.system_program,
+                token_program: &ctx.accounts.token_program,
+                rent: &ctx.accounts.rent.to_account_info(),
+            },
+            &payouts,
+            ctx.program_id,
         )?;
 
         token::transfer(
             ctx.accounts
                 .into_transfer_to_taker_context()
                 .with_signer(&[&authority_seeds[..]]),
-            ctx.accounts.escrow_account.initializer_amount,
+            taker_proceeds,
         )?;
 
         //close account
@@ -342,7 +1390,73 @@ pub mod scrt_escrow {
                 .into_close_vault_context()
                 .with_signer(&[&authority_seeds[..]]),
         )?;
-        ctx.accounts.escrow_account.initialized = 0;
+        ctx.accounts.escrow_account.status = EscrowStatus::Filled;
+        let escrow_key = *ctx.accounts.escrow_account.to_account_info().key;
+        if let Some(open_orders) = open_orders.as_mut(){
+            open_orders.remove(&escrow_key);
+            open_orders.exit(ctx.program_id)?;
+        }
+        if ctx.accounts.escrow_account.market == *ctx.accounts.market.key{
+            if let Some(mut market) = load_market(&ctx.accounts.market)?{
+                market.record_close(&ctx.accounts.escrow_account, true);
+                market.exit(ctx.program_id)?;
+            }
+        }
+        let escrow = &ctx.accounts.escrow_account;
+        settle::record_fill(
+            &ctx.accounts.price_history,
+            &escrow.deposit_token,
+            &escrow.receive_token,
+            escrow.initializer_amount,
+            escrow.taker_amount,
+            ctx.program_id,
+        )?;
+
+        //notify the maker once everything has settled
+        if let Some(hook) = &ctx.accounts.escrow_account.hook{
+            let escrow = &ctx.accounts.escrow_account;
+            hook.notify(remaining, &FillNotification {
+                escrow: *escrow.to_account_info().key,
+                initializer: escrow.initializer_key,
+                taker: *ctx.accounts.taker.key,
+                deposit_token: escrow.deposit_token,
+                receive_token: escrow.receive_token,
+                deposit_amount: escrow.initializer_amount,
+                receive_amount: escrow.taker_amount,
+            })?;
+        }
+
+        //optional receipts, one per side
+        let receipts = [
+            (extra_accounts.taker_receipt, *ctx.accounts.taker.key),
+            (extra_accounts.maker_receipt, ctx.accounts.escrow_account.initializer_key),
+        ];
+        for (receipt, owner) in receipts.iter() {
+            if let Some(receipt) = receipt {
+                let escrow = &ctx.accounts.escrow_account;
+                receipt::write_receipt(
+                    receipt,
+                    &ctx.accounts.taker,
+                    &ctx.accounts.system_program,
+                    ctx.program_id,
+                    &SettlementReceipt {
+                        owner: *owner,
+                        payer: *ctx.accounts.taker.key,
+                        escrow: *escrow.to_account_info().key,
+                        initializer: escrow.initializer_key,
+                        taker: *ctx.accounts.taker.key,
+                        deposit_token: escrow.deposit_token,
+                        receive_token: escrow.receive_token,
+                        initializer_amount: escrow.initializer_amount,
+                        taker_amount: escrow.taker_amount,
+                        fee_amount_initializer: escrow.fee_amount_initializer,
+                        fee_amount_taker: taker_fee,
+                        status: escrow.status,
+                        slot: Clock::get()?.slot,
+                    },
+                )?;
+            }
+        }
 
         emit!(ExchangeEvent {
             initializer: ctx.accounts.escrow_account.initializer_key,
@@ -351,8 +1465,1270 @@ pub mod scrt_escrow {
             receive_token: ctx.accounts.escrow_account.receive_token,
             deposit_amount: ctx.accounts.escrow_account.initializer_amount ,
             receive_amount: ctx.accounts.escrow_account.taker_amount,
+            proceeds_recipient,
+            taker_recipient: *ctx.accounts.taker_receive_token_account.key,
+            proceeds_split: ctx.accounts.escrow_account.proceeds_split.clone(),
+            referrer,
+            referral_amount,
+            maker_referrer: maker_referrer_key,
+            maker_referral_amount: maker_cut,
         });
 
         Ok(())
     }
+
+    #[event]
+    pub struct ClaimCreditedEvent {
+        pub owner: Pubkey,
+        pub mint: Pubkey,
+        pub amount: u64,
+    }
+
+    /// Withdraws everything credited to the caller's claim account for
+    /// `mint` and closes it.
+    pub fn claim(ctx: Context<Claim>) -> ProgramResult {
+        let amount = token::accessor::amount(&ctx.accounts.claim_account)?;
+        if amount == 0{
+            return Err(ScrtEscrowErrors::NothingToClaim.into());
+        }
+        let (_claim_account, claim_bump) = Pubkey::find_program_address(
+            &[
+                ctx.accounts.owner.key.as_ref(),
+                ctx.accounts.mint.key.as_ref(),
+                CLAIM_SEED,
+            ],
+            ctx.program_id,
+        );
+        let claim_seeds = &[
+            ctx.accounts.owner.key.as_ref(),
+            ctx.accounts.mint.key.as_ref(),
+            CLAIM_SEED,
+            &[claim_bump],
+        ];
+        token::transfer(
+            ctx.accounts
+                .into_transfer_context()
+                .with_signer(&[&claim_seeds[..]]),
+            amount,
+        )?;
+        token::close_account(
+            ctx.accounts
+                .into_close_context()
+                .with_signer(&[&claim_seeds[..]]),
+        )
+    }
+
+    pub fn update_receive_account(ctx: Context<UpdateReceiveAccount>) -> ProgramResult {
+        if !ctx.accounts.escrow_account.status.is_live(){
+            return Err(ScrtEscrowErrors::EscrowNotOpen.into());
+        }
+        if ctx.accounts.escrow_account.canonical_receive && !ata::is_canonical(
+            ctx.accounts.initializer_receive_token_account.to_account_info().key,
+            ctx.accounts.initializer.key,
+            &ctx.accounts.escrow_account.receive_token,
+        ){
+            return Err(ScrtEscrowErrors::InvalidAssociatedTokenAccount.into());
+        }
+        ctx.accounts.escrow_account.initializer_receive_token_account =
+            *ctx.accounts.initializer_receive_token_account.to_account_info().key;
+        Ok(())
+    }
+
+    /// Sends the vaulted deposit to the taker first, then calls
+    /// `callback_program` with `callback_data` and the remaining accounts.
+    /// The callback must leave `taker_amount` in the taker's deposit
+    /// account, which then pays the maker like `exchange` does, otherwise
+    /// the whole transaction fails.
+    pub fn flash_exchange<'info>(
+        ctx: Context<'_, '_, '_, 'info, FlashExchange<'info>>,
+        expected_taker_amount: u64,
+        expected_initializer_amount: u64,
+        max_taker_fee: u64,
+        callback_data: Vec<u8>,
+    ) -> ProgramResult {
+        if !ctx.accounts.escrow_account.status.is_live(){
+            return Err(ScrtEscrowErrors::EscrowNotOpen.into());
+        }
+        let mut open_orders = load_open_orders(&ctx.accounts.open_orders, ctx.accounts.initializer.key, ctx.program_id)?;
+        if ctx.accounts.escrow_account.order_epoch != order_epoch(&open_orders){
+            return Err(ScrtEscrowErrors::StaleOrderEpoch.into());
+        }
+        if ctx.accounts.escrow_account.taker_amount != expected_taker_amount{
+            return Err(ScrtEscrowErrors::TakerAmountMismatch.into());
+        }
+        if ctx.accounts.escrow_account.initializer_amount != expected_initializer_amount{
+            return Err(ScrtEscrowErrors::InitializerAmountMismatch.into());
+        }
+        if !ctx.accounts.escrow_account.proceeds_split.is_empty(){
+            return Err(ScrtEscrowErrors::ProceedsSplitUnsupported.into());
+        }
+        if ctx.accounts.escrow_account.fee_tiered{
+            return Err(ScrtEscrowErrors::TieredFeesUnsupported.into());
+        }
+        if ctx.accounts.escrow_account.initializer_fee_mint != ctx.accounts.escrow_account.fee_token{
+            return Err(ScrtEscrowErrors::FeeMintUnsupported.into());
+        }
+        if ctx.accounts.escrow_account.fee_deducted{
+            return Err(ScrtEscrowErrors::FeeDeductionUnsupported.into());
+        }
+        if !ctx.accounts.escrow_account.conditions.is_empty(){
+            return Err(ScrtEscrowErrors::ConditionsUnsupported.into());
+        }
+        if ctx.accounts.escrow_account.hook.is_some(){
+            return Err(ScrtEscrowErrors::HookUnsupported.into());
+        }
+        if ctx.accounts.escrow_account.referrer != Pubkey::default(){
+            return Err(ScrtEscrowErrors::ReferrerUnsupported.into());
+        }
+        // a callback into this program could settle the same escrow twice
+        if ctx.accounts.callback_program.key == ctx.program_id{
+            return Err(ScrtEscrowErrors::InvalidFlashCallback.into());
+        }
+        let taker_fee = settle::taker_fee(
+            &ctx.accounts.pda_account,
+            &ctx.accounts.escrow_account,
+            &settle::TakerFeeInputs {
+                taker: ctx.accounts.taker.key,
+                fee_mint: &ctx.accounts.taker_fee_paying_token_account.mint,
+                stats: None,
+                stake: Some(&ctx.accounts.taker_stake),
+                price_feed: Some(&ctx.accounts.price_feed),
+            },
+            ctx.program_id,
+        )?;
+        if taker_fee > max_taker_fee{
+            return Err(ScrtEscrowErrors::TakerFeeExceedsMax.into());
+        }
+
+        let (_vault_authority, vault_authority_bump) = Pubkey::find_program_address(
+            &[
+                ESCROW_PDA_SEED,
+                ctx.accounts.escrow_account.to_account_info().key.as_ref(),
+            ],
+            ctx.program_id,
+        );
+        let authority_seeds = &[
+            ESCROW_PDA_SEED,
+            ctx.accounts.escrow_account.to_account_info().key.as_ref(),
+            &[vault_authority_bump],
+        ];
+
+        //release the deposit up front
+        token::transfer(
+            ctx.accounts
+                .into_transfer_to_taker_context()
+                .with_signer(&[&authority_seeds[..]]),
+            ctx.accounts.escrow_account.initializer_amount,
+        )?;
+
+        //let the taker's program produce the payment
+        let callback_accounts: Vec<AccountMeta> = ctx
+            .remaining_accounts
+            .iter()
+            .map(|info| {
+                if info.is_writable {
+                    AccountMeta::new(*info.key, info.is_signer)
+                } else {
+                    AccountMeta::new_readonly(*info.key, info.is_signer)
+                }
+            })
+            .collect();
+        let mut callback_infos = ctx.remaining_accounts.to_vec();
+        callback_infos.push(ctx.accounts.callback_program.clone());
+        invoke(
+            &Instruction {
+                program_id: *ctx.accounts.callback_program.key,
+                accounts: callback_accounts,
+                data: callback_data,
+            },
+            &callback_infos,
+        )?;
+
+        let paid = token::accessor::amount(&ctx.accounts.taker_deposit_token_account.to_account_info())?;
+        if paid < ctx.accounts.escrow_account.taker_amount{
+            return Err(ScrtEscrowErrors::FlashPaymentMissing.into());
+        }
+        let proceeds_recipient = settle::pay_proceeds(
+            &settle::ProceedsSource {
+                from: ctx.accounts.taker_deposit_token_account.to_account_info(),
+                authority: ctx.accounts.taker.clone(),
+                signer_seeds: &[],
+                mint: &ctx.accounts.receive_token,
+                payer: &ctx.accounts.taker,
+                system_program: &ctx.accounts.system_program,
+                token_program: &ctx.accounts.token_program,
+                rent: &ctx.accounts.rent.to_account_info(),
+            },
+            &[settle::Payout {
+                recipient: ctx.accounts.initializer_receive_token_account.clone(),
+                amount: ctx.accounts.escrow_account.taker_amount,
+                owner: *ctx.accounts.initializer.key,
+                claim: ctx.accounts.claim_account.clone(),
+            }],
+            ctx.program_id,
+        )?;
+
+        // take taker fee
+        if taker_fee > 0{
+            token::transfer(
+                ctx.accounts.into_transfer_fee_from_taker_context(),
+                taker_fee,
+            )?;
+        }
+
+        // take initializer fee from valut
+        if ctx.accounts.escrow_account.fee_amount_initializer > 0{
+            let (_vault_fee_authority, vault_fee_authority_bump) = Pubkey::find_program_address(
+                &[
+                    ctx.accounts.pda_account.to_account_info().key.as_ref(),
+                    ESCROW_PDA_SEED
+                ],
+                ctx.program_id,
+            );
+            let authority_seeds1 = &[
+                ctx.accounts.pda_account.to_account_info().key.as_ref(),
+                ESCROW_PDA_SEED,
+                &[vault_fee_authority_bump],
+            ];
+            token::transfer(
+                ctx.accounts
+                    .into_transfer_fee_from_vault_context()
+                    .with_signer(&[&authority_seeds1[..]]),
+                ctx.accounts.escrow_account.fee_amount_initializer,
+            )?;
+        }
+
+        //close account
+        token::close_account(
+            ctx.accounts
+                .into_close_vault_context()
+                .with_signer(&[&authority_seeds[..]]),
+        )?;
+
+        ctx.accounts.escrow_account.status = EscrowStatus::Filled;
+        let escrow_key = *ctx.accounts.escrow_account.to_account_info().key;
+        if let Some(open_orders) = open_orders.as_mut(){
+            open_orders.remove(&escrow_key);
+            open_orders.exit(ctx.program_id)?;
+        }
+        if ctx.accounts.escrow_account.market == *ctx.accounts.market.key{
+            if let Some(mut market) = load_market(&ctx.accounts.market)?{
+                market.record_close(&ctx.accounts.escrow_account, true);
+                market.exit(ctx.program_id)?;
+            }
+        }
+        let escrow = &ctx.accounts.escrow_account;
+        settle::record_fill(
+            &ctx.accounts.price_history,
+            &escrow.deposit_token,
+            &escrow.receive_token,
+            escrow.initializer_amount,
+            escrow.taker_amount,
+            ctx.program_id,
+        )?;
+
+        emit!(ExchangeEvent {
+            initializer: ctx.accounts.escrow_account.initializer_key,
+            taker: *ctx.accounts.taker.key,
+            deposit_token: ctx.accounts.escrow_account.deposit_token,
+            receive_token: ctx.accounts.escrow_account.receive_token,
+            deposit_amount: ctx.accounts.escrow_account.initializer_amount,
+            receive_amount: ctx.accounts.escrow_account.taker_amount,
+            proceeds_recipient,
+            taker_recipient: *ctx.accounts.taker_receive_token_account.to_account_info().key,
+            proceeds_split: ctx.accounts.escrow_account.proceeds_split.clone(),
+            referrer: Pubkey::default(),
+            referral_amount: 0,
+            maker_referrer: Pubkey::default(),
+            maker_referral_amount: 0,
+        });
+        Ok(())
+    }
+
+    #[event]
+    pub struct BatchExchangeEvent {
+        pub taker: Pubkey,
+        pub deposit_token: Pubkey,
+        pub receive_token: Pubkey,
+        pub fills: u32,
+        pub total_spent: u64,
+        pub total_received: u64,
+    }
+
+    /// Takes escrows of one pair in the order given until the next one would
+    /// exceed `max_total_spend`. Fails as a whole if any fill fails or the
+    /// taker receives less than `min_total_receive`.
+    pub fn batch_exchange<'info>(
+        ctx: Context<'_, '_, '_, 'info, BatchExchange<'info>>,
+        max_total_spend: u64,
+        min_total_receive: u64,
+    ) -> ProgramResult {
+        if ctx.remaining_accounts.len().checked_rem(BATCH_EXCHANGE_ACCOUNTS) != Some(0){
+            return Err(ScrtEscrowErrors::InvalidRemainingAccounts.into());
+        }
+
+        let (_vault_fee_authority, vault_fee_authority_bump) = Pubkey::find_program_address(
+            &[
+                ctx.accounts.pda_account.to_account_info().key.as_ref(),
+                ESCROW_PDA_SEED
+            ],
+            ctx.program_id,
+        );
+        let fee_seeds = &[
+            ctx.accounts.pda_account.to_account_info().key.as_ref(),
+            ESCROW_PDA_SEED,
+            &[vault_fee_authority_bump],
+        ];
+
+        let taker = ctx.accounts.taker.clone();
+        let mut market = load_market(&ctx.accounts.market)?;
+        let mut total_spent: u64 = 0;
+        let mut total_received: u64 = 0;
+        let mut fills: u32 = 0;
+        for group in ctx.remaining_accounts.chunks(BATCH_EXCHANGE_ACCOUNTS) {
+            let escrow_info = &group[0];
+            let initializer = &group[1];
+            let vault = &group[3];
+            let vault_authority = &group[4];
+            let initializer_receive_token_account = &group[5];
+            let fee_collect_token_account = &group[6];
+            let claim_account = &group[7];
+
+            let mut escrow: Account<EscrowAccount> = Account::try_from(escrow_info)?;
+            let mut open_orders = load_open_orders(&group[2], initializer.key, ctx.program_id)?;
+            if !escrow.status.is_live(){
+                return Err(ScrtEscrowErrors::EscrowNotOpen.into());
+            }
+            if escrow.order_epoch != order_epoch(&open_orders){
+                return Err(ScrtEscrowErrors::StaleOrderEpoch.into());
+            }
+            if escrow.deposit_token != *ctx.accounts.deposit_token.key
+                || escrow.receive_token != *ctx.accounts.receive_token.key
+                || escrow.fee_token != ctx.accounts.pda_account.fee_token
+                || escrow.initializer_key != *initializer.key
+                || escrow.vault_account != *vault.key
+                || escrow.vault_authority != *vault_authority.key
+                || escrow.initializer_receive_token_account != *initializer_receive_token_account.key
+                || ctx.accounts.pda_account.fee_collector(&escrow.fee_token, &escrow.fee_collect_token_account) != Some(*fee_collect_token_account.key)
+            {
+                return Err(ScrtEscrowErrors::BatchEscrowMismatch.into());
+            }
+            if !escrow.proceeds_split.is_empty(){
+                return Err(ScrtEscrowErrors::ProceedsSplitUnsupported.into());
+            }
+            if escrow.fee_tiered{
+                return Err(ScrtEscrowErrors::TieredFeesUnsupported.into());
+            }
+            if escrow.initializer_fee_mint != escrow.fee_token{
+                return Err(ScrtEscrowErrors::FeeMintUnsupported.into());
+            }
+            if escrow.fee_deducted{
+                return Err(ScrtEscrowErrors::FeeDeductionUnsupported.into());
+            }
+            if !escrow.conditions.is_empty(){
+                return Err(ScrtEscrowErrors::ConditionsUnsupported.into());
+            }
+            if escrow.hook.is_some(){
+                return Err(ScrtEscrowErrors::HookUnsupported.into());
+            }
+            if escrow.referrer != Pubkey::default(){
+                return Err(ScrtEscrowErrors::ReferrerUnsupported.into());
+            }
+
+            let spent = total_spent
+                .checked_add(escrow.taker_amount)
+                .ok_or(ScrtEscrowErrors::AmountOverflow)?;
+            if spent > max_total_spend{
+                break;
+            }
+
+            let (_vault_authority, vault_authority_bump) = Pubkey::find_program_address(
+                &[ESCROW_PDA_SEED, escrow_info.key.as_ref()],
+                ctx.program_id,
+            );
+            let authority_seeds = &[
+                ESCROW_PDA_SEED,
+                escrow_info.key.as_ref(),
+                &[vault_authority_bump],
+            ];
+
+            // take taker fee
+            let taker_fee = settle::taker_fee(
+                &ctx.accounts.pda_account,
+                &escrow,
+                &settle::TakerFeeInputs {
+                    taker: taker.key,
+                    fee_mint: &ctx.accounts.taker_fee_paying_token_account.mint,
+                    stats: None,
+                    stake: Some(&ctx.accounts.taker_stake),
+                    price_feed: Some(&ctx.accounts.price_feed),
+                },
+                ctx.program_id,
+            )?;
+            if taker_fee > 0{
+                token::transfer(
+                    ctx.accounts.into_transfer_context(
+                        ctx.accounts.taker_fee_paying_token_account.to_account_info(),
+                        fee_collect_token_account.clone(),
+                        taker.clone(),
+                    ),
+                    taker_fee,
+                )?;
+            }
+
+            // take initializer fee from valut
+            if escrow.fee_amount_initializer > 0{
+                token::transfer(
+                    ctx.accounts
+                        .into_transfer_context(
+                            ctx.accounts.vault_fee_account.clone(),
+                            fee_collect_token_account.clone(),
+                            ctx.accounts.vault_fee_authority.clone(),
+                        )
+                        .with_signer(&[&fee_seeds[..]]),
+                    escrow.fee_amount_initializer,
+                )?;
+            }
+
+            //exchange tokens
+            let proceeds_recipient = settle::pay_proceeds(
+                &settle::ProceedsSource {
+                    from: ctx.accounts.taker_deposit_token_account.to_account_info(),
+                    authority: taker.clone(),
+                    signer_seeds: &[],
+                    mint: &ctx.accounts.receive_token,
+                    payer: &taker,
+                    system_program: &ctx.accounts.system_program,
+                    token_program: &ctx.accounts.token_program,
+                    rent: &ctx.accounts.rent.to_account_info(),
+                },
+                &[settle::Payout {
+                    recipient: initializer_receive_token_account.clone(),
+                    amount: escrow.taker_amount,
+                    owner: *initializer.key,
+                    claim: claim_account.clone(),
+                }],
+                ctx.program_id,
+            )?;
+            token::transfer(
+                ctx.accounts
+                    .into_transfer_context(
+                        vault.clone(),
+                        ctx.accounts.taker_receive_token_account.to_account_info(),
+                        vault_authority.clone(),
+                    )
+                    .with_signer(&[&authority_seeds[..]]),
+                escrow.initializer_amount,
+            )?;
+
+            //close vault
+            token::close_account(
+                ctx.accounts
+                    .into_close_vault_context(vault.clone(), initializer.clone(), vault_authority.clone())
+                    .with_signer(&[&authority_seeds[..]]),
+            )?;
+
+            escrow.status = EscrowStatus::Filled;
+            if let Some(open_orders) = open_orders.as_mut(){
+                open_orders.remove(escrow_info.key);
+                open_orders.exit(ctx.program_id)?;
+            }
+            if escrow.market == *ctx.accounts.market.key{
+                if let Some(market) = market.as_mut(){
+                    market.record_close(&escrow, true);
+                }
+            }
+            settle::record_fill(
+                &ctx.accounts.price_history,
+                &escrow.deposit_token,
+                &escrow.receive_token,
+                escrow.initializer_amount,
+                escrow.taker_amount,
+                ctx.program_id,
+            )?;
+
+            emit!(ExchangeEvent {
+                initializer: escrow.initializer_key,
+                taker: *taker.key,
+                deposit_token: escrow.deposit_token,
+                receive_token: escrow.receive_token,
+                deposit_amount: escrow.initializer_amount,
+                receive_amount: escrow.taker_amount,
+                proceeds_recipient,
+                taker_recipient: *ctx.accounts.taker_receive_token_account.to_account_info().key,
+                proceeds_split: escrow.proceeds_split.clone(),
+                referrer: Pubkey::default(),
+                referral_amount: 0,
+                maker_referrer: Pubkey::default(),
+                maker_referral_amount: 0,
+            });
+
+            total_spent = spent;
+            total_received = total_received
+                .checked_add(escrow.initializer_amount)
+                .ok_or(ScrtEscrowErrors::AmountOverflow)?;
+            fills += 1;
+            escrow.exit(ctx.program_id)?;
+        }
+
+        if total_received < min_total_receive{
+            return Err(ScrtEscrowErrors::BatchMinReceiveNotMet.into());
+        }
+        if let Some(market) = market{
+            market.exit(ctx.program_id)?;
+        }
+
+        emit!(BatchExchangeEvent {
+            taker: *taker.key,
+            deposit_token: *ctx.accounts.deposit_token.key,
+            receive_token: *ctx.accounts.receive_token.key,
+            fills,
+            total_spent,
+            total_received,
+        });
+        Ok(())
+    }
+
+    pub fn close_receipt(_ctx: Context<CloseReceipt>) -> ProgramResult {
+        Ok(())
+    }
+
+    pub fn update_book_fees(
+        ctx: Context<UpdateBookFees>,
+        book_maker_fee: u64,
+        book_taker_fee: u64,
+    ) -> ProgramResult {
+        ctx.accounts.pda_account.book_maker_fee = book_maker_fee;
+        ctx.accounts.pda_account.book_taker_fee = book_taker_fee;
+        Ok(())
+    }
+
+    pub fn update_referral_share(
+        ctx: Context<UpdateReferralShare>,
+        referral_share_bps: u16,
+    ) -> ProgramResult {
+        if referral_share_bps > TOTAL_BPS{
+            return Err(ScrtEscrowErrors::InvalidReferralShare.into());
+        }
+        ctx.accounts.pda_account.referral_share_bps = referral_share_bps;
+        Ok(())
+    }
+
+    pub fn initialize_referrer(ctx: Context<InitializeReferrer>) -> ProgramResult {
+        ctx.accounts.referrer_stats.owner = *ctx.accounts.owner.key;
+        ctx.accounts.referrer_stats.fee_token = *ctx.accounts.fee_token.key;
+        Ok(())
+    }
+
+    pub fn update_fee_tiers(ctx: Context<UpdateFeeTiers>, fee_tiers: Vec<FeeTier>) -> ProgramResult {
+        validate_tiers(&fee_tiers)?;
+        ctx.accounts.pda_account.fee_tiers = fee_tiers;
+        Ok(())
+    }
+
+    pub fn initialize_user_stats(ctx: Context<InitializeUserStats>) -> ProgramResult {
+        ctx.accounts.user_stats.owner = *ctx.accounts.owner.key;
+        ctx.accounts.user_stats.fee_token = *ctx.accounts.fee_token.key;
+        Ok(())
+    }
+
+    pub fn initialize_stake_vault(
+        ctx: Context<InitializeStakeVault>,
+        unbonding_period: i64,
+        stake_discounts: Vec<StakeDiscount>,
+    ) -> ProgramResult {
+        if unbonding_period < 0{
+            return Err(ScrtEscrowErrors::InvalidUnbondingPeriod.into());
+        }
+        validate_discounts(&stake_discounts)?;
+        ctx.accounts.pda_account.stake_vault = *ctx.accounts.stake_vault.to_account_info().key;
+        ctx.accounts.pda_account.unbonding_period = unbonding_period;
+        ctx.accounts.pda_account.stake_discounts = stake_discounts;
+        Ok(())
+    }
+
+    pub fn update_stake_config(
+        ctx: Context<UpdateStakeConfig>,
+        unbonding_period: i64,
+        stake_discounts: Vec<StakeDiscount>,
+    ) -> ProgramResult {
+        if unbonding_period < 0{
+            return Err(ScrtEscrowErrors::InvalidUnbondingPeriod.into());
+        }
+        validate_discounts(&stake_discounts)?;
+        ctx.accounts.pda_account.unbonding_period = unbonding_period;
+        ctx.accounts.pda_account.stake_discounts = stake_discounts;
+        Ok(())
+    }
+
+    pub fn initialize_stake_account(ctx: Context<InitializeStakeAccount>) -> ProgramResult {
+        ctx.accounts.stake_account.owner = *ctx.accounts.owner.key;
+        ctx.accounts.stake_account.fee_token = *ctx.accounts.fee_token.key;
+        Ok(())
+    }
+
+    pub fn stake(ctx: Context<Stake>, amount: u64) -> ProgramResult {
+        token::transfer(ctx.accounts.into_transfer_to_stake_vault_context(), amount)?;
+        let stake = &mut ctx.accounts.stake_account;
+        stake.active = stake.active.checked_add(amount).ok_or(ScrtEscrowErrors::AmountOverflow)?;
+        Ok(())
+    }
+
+    /// Stops `amount` earning a discount and starts its unbonding period.
+    /// Unstaking again restarts the period for everything still unbonding.
+    pub fn unstake(ctx: Context<Unstake>, amount: u64) -> ProgramResult {
+        let stake = &mut ctx.accounts.stake_account;
+        if amount > stake.active{
+            return Err(ScrtEscrowErrors::InsufficientStake.into());
+        }
+        stake.active -= amount;
+        stake.unbonding += amount;
+        stake.unbonding_ends = Clock::get()?.unix_timestamp + ctx.accounts.pda_account.unbonding_period;
+        Ok(())
+    }
+
+    pub fn withdraw_stake(ctx: Context<WithdrawStake>) -> ProgramResult {
+        let amount = ctx.accounts.stake_account.unbonding;
+        if amount == 0{
+            return Err(ScrtEscrowErrors::NothingToWithdraw.into());
+        }
+        if Clock::get()?.unix_timestamp < ctx.accounts.stake_account.unbonding_ends{
+            return Err(ScrtEscrowErrors::StakeStillUnbonding.into());
+        }
+
+        let (_vault_fee_authority, vault_fee_authority_bump) = Pubkey::find_program_address(
+            &[
+                ctx.accounts.pda_account.to_account_info().key.as_ref(),
+                ESCROW_PDA_SEED
+            ],
+            ctx.program_id,
+        );
+        let authority_seeds = &[
+            ctx.accounts.pda_account.to_account_info().key.as_ref(),
+            ESCROW_PDA_SEED,
+            &[vault_fee_authority_bump],
+        ];
+        token::transfer(
+            ctx.accounts
+                .into_transfer_from_stake_vault_context()
+                .with_signer(&[&authority_seeds[..]]),
+            amount,
+        )?;
+        ctx.accounts.stake_account.unbonding = 0;
+        Ok(())
+    }
+
+    pub fn initialize_fee_distribution(
+        ctx: Context<InitializeFeeDistribution>,
+        fee_distribution: Vec<ProceedsShare>,
+    ) -> ProgramResult {
+        if fee_distribution.is_empty() || validate_split(&fee_distribution).is_err(){
+            return Err(ScrtEscrowErrors::InvalidFeeDistribution.into());
+        }
+        ctx.accounts.pda_account.fee_revenue_vault =
+            *ctx.accounts.fee_revenue_vault.to_account_info().key;
+        ctx.accounts.pda_account.fee_distribution = fee_distribution;
+        Ok(())
+    }
+
+    pub fn update_fee_distribution(
+        ctx: Context<UpdateFeeDistribution>,
+        fee_distribution: Vec<ProceedsShare>,
+    ) -> ProgramResult {
+        if fee_distribution.is_empty() || validate_split(&fee_distribution).is_err(){
+            return Err(ScrtEscrowErrors::InvalidFeeDistribution.into());
+        }
+        ctx.accounts.pda_account.fee_distribution = fee_distribution;
+        Ok(())
+    }
+
+    #[event]
+    pub struct FeeDistributionEvent {
+        pub fee_token: Pubkey,
+        pub mint: Pubkey,
+        pub amount: u64,
+        pub shares: Vec<ProceedsShare>,
+        pub amounts: Vec<u64>,
+    }
+
+    /// Pays out the whole revenue vault by the distribution table.
+    pub fn distribute_fees<'info>(ctx: Context<'_, '_, '_, 'info, DistributeFees<'info>>) -> ProgramResult {
+        let shares = ctx.accounts.pda_account.fee_distribution.clone();
+        let mint = ctx.accounts.fee_revenue_vault.mint;
+        let in_fee_token = mint == ctx.accounts.pda_account.fee_token;
+        let accounts_len = if in_fee_token { shares.len() } else { shares.len() * 2 };
+        if shares.is_empty() || ctx.remaining_accounts.len() != accounts_len
+            || shares.iter().zip(ctx.remaining_accounts).any(|(share, info)| share.recipient != *info.key)
+        {
+            return Err(ScrtEscrowErrors::FeeDistributionMismatch.into());
+        }
+        // recipients hold the fee token, other mints go to their owners'
+        // associated accounts
+        let (recipients, destinations) = ctx.remaining_accounts.split_at(shares.len());
+        let destinations = if in_fee_token { recipients } else { destinations };
+        if !in_fee_token{
+            for (recipient, destination) in recipients.iter().zip(destinations){
+                let recipient: Account<TokenAccount> = Account::try_from(recipient)?;
+                if !ata::is_canonical(destination.key, &recipient.owner, &mint){
+                    return Err(ScrtEscrowErrors::FeeDistributionMismatch.into());
+                }
+            }
+        }
+        let amount = ctx.accounts.fee_revenue_vault.amount;
+        if amount == 0{
+            return Err(ScrtEscrowErrors::NothingToDistribute.into());
+        }
+
+        let (_vault_fee_authority, vault_fee_authority_bump) = Pubkey::find_program_address(
+            &[
+                ctx.accounts.pda_account.to_account_info().key.as_ref(),
+                ESCROW_PDA_SEED
+            ],
+            ctx.program_id,
+        );
+        let authority_seeds = &[
+            ctx.accounts.pda_account.to_account_info().key.as_ref(),
+            ESCROW_PDA_SEED,
+            &[vault_fee_authority_bump],
+        ];
+        let amounts = split_amount(amount, &shares);
+        for (info, share_amount) in destinations.iter().zip(&amounts){
+            if *share_amount > 0{
+                token::transfer(
+                    ctx.accounts
+                        .into_transfer_context(info.clone())
+                        .with_signer(&[&authority_seeds[..]]),
+                    *share_amount,
+                )?;
+            }
+        }
+
+        emit!(FeeDistributionEvent {
+            fee_token: ctx.accounts.pda_account.fee_token,
+            mint,
+            amount,
+            shares,
+            amounts,
+        });
+        Ok(())
+    }
+
+    /// Accepts fees in `mint`, charged at `fee_bps` of the trade's leg in
+    /// that mint or `flat_fee` when `fee_bps` is zero.
+    pub fn add_fee_mint(ctx: Context<AddFeeMint>, flat_fee: u64, fee_bps: u16) -> ProgramResult {
+        let fee_mint = AcceptedFeeMint {
+            mint: *ctx.accounts.mint.key,
+            vault: *ctx.accounts.fee_vault.to_account_info().key,
+            collector: *ctx.accounts.collector.to_account_info().key,
+            flat_fee,
+            fee_bps,
+        };
+        fee_mint.validate()?;
+        let pda = &mut ctx.accounts.pda_account;
+        if pda.accepts(&fee_mint.mint) || pda.accepted_fee_mints.len() >= MAX_FEE_MINTS{
+            return Err(ScrtEscrowErrors::InvalidFeeMint.into());
+        }
+        pda.accepted_fee_mints.push(fee_mint);
+        Ok(())
+    }
+
+    pub fn update_fee_mint(
+        ctx: Context<UpdateFeeMint>,
+        mint: Pubkey,
+        flat_fee: u64,
+        fee_bps: u16,
+    ) -> ProgramResult {
+        let fee_mint = ctx
+            .accounts
+            .pda_account
+            .accepted_fee_mints
+            .iter_mut()
+            .find(|fee_mint| fee_mint.mint == mint)
+            .ok_or(ScrtEscrowErrors::InvalidFeeMint)?;
+        fee_mint.flat_fee = flat_fee;
+        fee_mint.fee_bps = fee_bps;
+        fee_mint.validate()
+    }
+
+    /// Sets who collects fees deducted from traded amounts and at what
+    /// rates. A default `collector` turns the mode off for new escrows.
+    pub fn update_deducted_fees(
+        ctx: Context<UpdateDeductedFees>,
+        collector: Pubkey,
+        maker_bps: u16,
+        taker_bps: u16,
+    ) -> ProgramResult {
+        if maker_bps > TOTAL_BPS || taker_bps > TOTAL_BPS{
+            return Err(ScrtEscrowErrors::InvalidDeductedFees.into());
+        }
+        ctx.accounts.pda_account.deducted_fee_collector = collector;
+        ctx.accounts.pda_account.deducted_maker_bps = maker_bps;
+        ctx.accounts.pda_account.deducted_taker_bps = taker_bps;
+        Ok(())
+    }
+
+    /// Prices fee token fees in USD off `price_feed`. A default feed turns
+    /// USD fees off.
+    pub fn update_usd_fees(ctx: Context<UpdateUsdFees>, usd_fees: UsdFees) -> ProgramResult {
+        // decimals come from the mint, not the caller
+        let usd_fees = UsdFees {
+            fee_decimals: ctx.accounts.fee_token.decimals,
+            ..usd_fees
+        };
+        if usd_fees.enabled(){
+            usd_fees.validate()?;
+        }
+        ctx.accounts.pda_account.usd_fees = usd_fees;
+        Ok(())
+    }
+
+    /// Creates a local price feed, `test-oracle` builds only.
+    pub fn initialize_price_account(ctx: Context<InitializePriceAccount>, expo: i32) -> ProgramResult {
+        if !cfg!(feature = "test-oracle"){
+            return Err(ScrtEscrowErrors::TestOracleDisabled.into());
+        }
+        ctx.accounts.price_account.authority = *ctx.accounts.authority.key;
+        ctx.accounts.price_account.product = *ctx.accounts.product.key;
+        ctx.accounts.price_account.expo = expo;
+        Ok(())
+    }
+
+    /// Publishes a price to a local price account, stamped with the
+    /// current time. `test-oracle` builds only.
+    pub fn update_price(ctx: Context<UpdatePrice>, price: i64, conf: u64) -> ProgramResult {
+        if !cfg!(feature = "test-oracle"){
+            return Err(ScrtEscrowErrors::TestOracleDisabled.into());
+        }
+        ctx.accounts.price_account.price = price;
+        ctx.accounts.price_account.conf = conf;
+        ctx.accounts.price_account.publish_time = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    /// Starts recording a pair's fill prices, as quote per base. Anyone
+    /// may create it.
+    pub fn initialize_price_history(ctx: Context<InitializePriceHistory>) -> ProgramResult {
+        let mut history = ctx.accounts.price_history.load_init()?;
+        history.base_mint = *ctx.accounts.base_mint.key;
+        history.quote_mint = *ctx.accounts.quote_mint.key;
+        Ok(())
+    }
+
+    pub fn initialize_order_book(ctx: Context<InitializeOrderBook>) -> ProgramResult {
+        let mut book = ctx.accounts.order_book.load_init()?;
+        book.market = *ctx.accounts.market.to_account_info().key;
+        book.fee_config = *ctx.accounts.pda_account.to_account_info().key;
+        book.base_vault = *ctx.accounts.base_vault.to_account_info().key;
+        book.quote_vault = *ctx.accounts.quote_vault.to_account_info().key;
+        book.fee_collect_token_account =
+            *ctx.accounts.fee_collect_token_account.to_account_info().key;
+
+        ctx.accounts.market.order_book = *ctx.accounts.order_book.to_account_info().key;
+        Ok(())
+    }
+
+    #[event]
+    pub struct OrderPlacedEvent {
+        pub market: Pubkey,
+        pub owner: Pubkey,
+        pub side: Side,
+        pub price: u64,
+        pub quantity: u64,
+        pub seq: u64,
+    }
+
+    #[event]
+    pub struct BookFillEvent {
+        pub market: Pubkey,
+        pub maker: Pubkey,
+        pub taker: Pubkey,
+        pub taker_side: Side,
+        pub maker_seq: u64,
+        pub price: u64,
+        pub quantity: u64,
+        pub quote_amount: u64,
+    }
+
+    #[event]
+    pub struct OrderCancelledEvent {
+        pub market: Pubkey,
+        pub owner: Pubkey,
+        pub side: Side,
+        pub price: u64,
+        pub quantity: u64,
+        pub seq: u64,
+    }
+
+    /// Matches an order against the opposite side of the book, best price
+    /// first, and rests any remainder worth at least the market's minimum
+    /// notional. Each matched maker's receive account and their claim
+    /// account for the mint they receive are passed in fill order as
+    /// remaining accounts. If resting evicts the worst order of a full side,
+    /// its refund account, claim account, fee account and fee token claim
+    /// account follow.
+    pub fn place_order<'info>(
+        ctx: Context<'_, '_, '_, 'info, PlaceOrder<'info>>,
+        side: Side,
+        price: u64,
+        quantity: u64,
+    ) -> ProgramResult {
+        let lot_size = ctx.accounts.market.lot_size;
+        ctx.accounts
+            .market
+            .validate_order(quantity, book::quote_amount(quantity, price, lot_size)?)?;
+
+        let market_key = *ctx.accounts.market.to_account_info().key;
+        let book_key = *ctx.accounts.order_book.to_account_info().key;
+        let (_book_authority, book_authority_bump) = Pubkey::find_program_address(
+            &[book_key.as_ref(), ORDER_BOOK_SEED],
+            ctx.program_id,
+        );
+        let book_seeds = &[book_key.as_ref(), ORDER_BOOK_SEED, &[book_authority_bump]];
+
+        let (_vault_fee_authority, vault_fee_authority_bump) = Pubkey::find_program_address(
+            &[
+                ctx.accounts.pda_account.to_account_info().key.as_ref(),
+                ESCROW_PDA_SEED
+            ],
+            ctx.program_id,
+        );
+        let fee_seeds = &[
+            ctx.accounts.pda_account.to_account_info().key.as_ref(),
+            ESCROW_PDA_SEED,
+            &[vault_fee_authority_bump],
+        ];
+
+        let owner = ctx.accounts.owner.clone();
+        let owner_base = ctx.accounts.owner_base_token_account.to_account_info();
+        let owner_quote = ctx.accounts.owner_quote_token_account.to_account_info();
+        let (pay_from, vault, receive_to, maker_mint) = match side {
+            Side::Bid => (owner_quote.clone(), ctx.accounts.base_vault.clone(), owner_base.clone(), &ctx.accounts.quote_mint),
+            Side::Ask => (owner_base.clone(), ctx.accounts.quote_vault.clone(), owner_quote.clone(), &ctx.accounts.base_mint),
+        };
+        let rent = ctx.accounts.rent.to_account_info();
+
+        let mut remaining = quantity;
+        let mut maker_accounts = ctx.remaining_accounts.iter();
+        while remaining > 0 {
+            let best = ctx.accounts.order_book.load()?.best(side.opposite());
+            let resting = match best {
+                Some(resting) if side.crosses(price, resting.price) => resting,
+                _ => break,
+            };
+            let resting_quantity = resting.quantity;
+            let fill = min(remaining, resting_quantity);
+            let quote = book::quote_amount(fill, resting.price, lot_size)?;
+            let (pay_amount, receive_amount) = match side {
+                Side::Bid => (quote, fill),
+                Side::Ask => (fill, quote),
+            };
+
+            let (maker_account, maker_claim) = match (maker_accounts.next(), maker_accounts.next()){
+                (Some(maker_account), Some(maker_claim)) => (maker_account, maker_claim),
+                _ => return Err(ScrtEscrowErrors::MakerAccountMissing.into()),
+            };
+            if *maker_account.key != resting.receive_account{
+                return Err(ScrtEscrowErrors::OrderAccountMismatch.into());
+            }
+
+            //pay the maker, to their claim account if they can't take it,
+            //then release the maker's deposit
+            settle::pay_proceeds(
+                &settle::ProceedsSource {
+                    from: pay_from.clone(),
+                    authority: owner.clone(),
+                    signer_seeds: &[],
+                    mint: maker_mint,
+                    payer: &owner,
+                    system_program: &ctx.accounts.system_program,
+                    token_program: &ctx.accounts.token_program,
+                    rent: &rent,
+                },
+                &[settle::Payout {
+                    recipient: maker_account.clone(),
+                    amount: pay_amount,
+                    owner: resting.owner,
+                    claim: maker_claim.clone(),
+                }],
+                ctx.program_id,
+            )?;
+            token::transfer(
+                ctx.accounts
+                    .into_transfer_context(vault.clone(), receive_to.clone(), ctx.accounts.book_authority.clone())
+                    .with_signer(&[&book_seeds[..]]),
+                receive_amount,
+            )?;
+
+            //taker fee per fill
+            if ctx.accounts.pda_account.book_taker_fee > 0{
+                token::transfer(
+                    ctx.accounts.into_transfer_context(
+                        ctx.accounts.owner_fee_paying_token_account.to_account_info(),
+                        ctx.accounts.fee_collect_token_account.clone(),
+                        owner.clone(),
+                    ),
+                    ctx.accounts.pda_account.book_taker_fee,
+                )?;
+            }
+
+            //the maker fee is charged pro rata to each fill
+            let filled_out = resting_quantity == fill;
+            let maker_fee = resting.maker_fee_for(fill);
+            {
+                let mut book = ctx.accounts.order_book.load_mut()?;
+                if filled_out{
+                    book.remove(side.opposite(), 0);
+                } else {
+                    book.fill(side.opposite(), 0, fill, maker_fee);
+                }
+            }
+            if maker_fee > 0{
+                token::transfer(
+                    ctx.accounts
+                        .into_transfer_context(
+                            ctx.accounts.vault_fee_account.clone(),
+                            ctx.accounts.fee_collect_token_account.clone(),
+                            ctx.accounts.vault_fee_authority.clone(),
+                        )
+                        .with_signer(&[&fee_seeds[..]]),
+                    maker_fee,
+                )?;
+            }
+            if filled_out{
+                ctx.accounts.market.open_orders = ctx.accounts.market.open_orders.saturating_sub(1);
+            }
+            ctx.accounts.market.base_volume = ctx.accounts.market.base_volume.saturating_add(fill);
+            ctx.accounts.market.quote_volume = ctx.accounts.market.quote_volume.saturating_add(quote);
+            settle::record_fill(
+                &ctx.accounts.price_history,
+                ctx.accounts.base_mint.key,
+                ctx.accounts.quote_mint.key,
+                fill,
+                quote,
+                ctx.program_id,
+            )?;
+
+            emit!(BookFillEvent {
+                market: market_key,
+                maker: resting.owner,
+                taker: *owner.key,
+                taker_side: side,
+                maker_seq: resting.seq,
+                price: resting.price,
+                quantity: fill,
+                quote_amount: quote,
+            });
+            remaining -= fill;
+        }
+
+        //a remainder below the minimum notional isn't rested
+        if remaining == 0 || book::quote_amount(remaining, price, lot_size)? < ctx.accounts.market.min_notional{
+            return Ok(());
+        }
+
+        //rest the remainder
+        let (deposit_from, deposit_vault, deposit_amount, receive_account, refund_account, deposit_mint) = match side {
+            Side::Bid => (
+                owner_quote.clone(),
+                ctx.accounts.quote_vault.clone(),
+                book::quote_amount(remaining, price, lot_size)?,
+                *owner_base.key,
+                *owner_quote.key,
+                &ctx.accounts.quote_mint,
+            ),
+            Side::Ask => (
+                owner_base.clone(),
+                ctx.accounts.base_vault.clone(),
+                remaining,
+                *owner_quote.key,
+                *owner_base.key,
+                &ctx.accounts.base_mint,
+            ),
+        };
+        token::transfer(
+            ctx.accounts.into_transfer_context(deposit_from, deposit_vault.clone(), owner.clone()),
+            deposit_amount,
+        )?;
+        let maker_fee = ctx.accounts.pda_account.book_maker_fee;
+        if maker_fee > 0{
+            token::transfer(
+                ctx.accounts.into_transfer_context(
+                    ctx.accounts.owner_fee_paying_token_account.to_account_info(),
+                    ctx.accounts.vault_fee_account.clone(),
+                    owner.clone(),
+                ),
+                maker_fee,
+            )?;
+        }
+
+        let (seq, evicted) = {
+            let mut book = ctx.accounts.order_book.load_mut()?;
+            let seq = book.next_seq;
+            book.next_seq = seq + 1;
+            let evicted = book.insert(side, BookOrder {
+                owner: *owner.key,
+                receive_account,
+                refund_account,
+                fee_account: *ctx.accounts.owner_fee_paying_token_account.to_account_info().key,
+                price,
+                quantity: remaining,
+                seq,
+                maker_fee,
+            })?;
+            (seq, evicted)
+        };
+        ctx.accounts.market.open_orders += 1;
+
+        //refund the evicted order's deposit and unused maker fee
+        if let Some(evicted) = evicted{
+            let accounts: Vec<&AccountInfo<'info>> = maker_accounts.by_ref().take(4).collect();
+            if accounts.len() < 4{
+                return Err(ScrtEscrowErrors::MakerAccountMissing.into());
+            }
+            if *accounts[0].key != evicted.refund_account || *accounts[2].key != evicted.fee_account{
+                return Err(ScrtEscrowErrors::OrderAccountMismatch.into());
+            }
+            let refund_amount = match side {
+                Side::Bid => book::quote_amount(evicted.quantity, evicted.price, lot_size)?,
+                Side::Ask => evicted.quantity,
+            };
+            settle::pay_proceeds(
+                &settle::ProceedsSource {
+                    from: deposit_vault,
+                    authority: ctx.accounts.book_authority.clone(),
+                    signer_seeds: &[&book_seeds[..]],
+                    mint: deposit_mint,
+                    payer: &owner,
+                    system_program: &ctx.accounts.system_program,
+                    token_program: &ctx.accounts.token_program,
+                    rent: &rent,
+                },
+                &[settle::Payout {
+                    recipient: accounts[0].clone(),
+                    amount: refund_amount,
+                    owner: evicted.owner,
+                    claim: accounts[1].clone(),
+                }],
+                ctx.program_id,
+            )?;
+            settle::pay_proceeds(
+                &settle::ProceedsSource {
+                    from: ctx.accounts.vault_fee_account.clone(),
+                    authority: ctx.accounts.vault_fee_authority.clone(),
+                    signer_seeds: &[&fee_seeds[..]],
+                    mint: &ctx.accounts.fee_token,
+                    payer: &owner,
+                    system_program: &ctx.accounts.system_program,
+                    token_program: &ctx.accounts.token_program,
+                    rent: &rent,
+                },
+                &[settle::Payout {
+                    recipient: accounts[2].clone(),
+                    amount: evicted.maker_fee,
+                    owner: evicted.owner,
+                    claim: accounts[3].clone(),
+                }],
+                ctx.program_id,
+            )?;
+            ctx.accounts.market.open_orders = ctx.accounts.market.open_orders.saturating_sub(1);
+
+            emit!(OrderCancelledEvent {
+                market: market_key,
+                owner: evicted.owner,
+                side,
+                price: evicted.price,
+                quantity: evicted.quantity,
+                seq: evicted.seq,
+            });
+        }
+
+        emit!(OrderPlacedEvent {
+            market: market_key,
+            owner: *owner.key,
+            side,
+            price,
+            quantity: remaining,
+            seq,
+        });
+        Ok(())
+    }
+
+    pub fn cancel_order(ctx: Context<CancelOrder>, side: Side, seq: u64) -> ProgramResult {
+        let (index, order) = {
+            let book = ctx.accounts.order_book.load()?;
+            let index = book.find(side, seq).ok_or(ScrtEscrowErrors::OrderNotFound)?;
+            (index, book.get(side, index))
+        };
+        if order.owner != *ctx.accounts.owner.key{
+            return Err(ScrtEscrowErrors::OrderOwnerMismatch.into());
+        }
+        if order.refund_account != *ctx.accounts.refund_token_account.key{
+            return Err(ScrtEscrowErrors::OrderAccountMismatch.into());
+        }
+
+        let book_key = *ctx.accounts.order_book.to_account_info().key;
+        let (_book_authority, book_authority_bump) = Pubkey::find_program_address(
+            &[book_key.as_ref(), ORDER_BOOK_SEED],
+            ctx.program_id,
+        );
+        let book_seeds = &[book_key.as_ref(), ORDER_BOOK_SEED, &[book_authority_bump]];
+
+        //withdraw the remaining deposit
+        let (vault, refund_amount) = match side {
+            Side::Bid => (
+                ctx.accounts.quote_vault.clone(),
+                book::quote_amount(order.quantity, order.price, ctx.accounts.market.lot_size)?,
+            ),
+            Side::Ask => (ctx.accounts.base_vault.clone(), order.quantity),
+        };
+        token::transfer(
+            ctx.accounts
+                .into_transfer_context(
+                    vault,
+                    ctx.accounts.refund_token_account.clone(),
+                    ctx.accounts.book_authority.clone(),
+                )
+                .with_signer(&[&book_seeds[..]]),
+            refund_amount,
+        )?;
+
+        //withdraw the part of the prepaid maker fee not yet charged
+        if order.maker_fee > 0{
+            let (_vault_fee_authority, vault_fee_authority_bump) = Pubkey::find_program_address(
+                &[
+                    ctx.accounts.pda_account.to_account_info().key.as_ref(),
+                    ESCROW_PDA_SEED
+                ],
+                ctx.program_id,
+            );
+            let fee_seeds = &[
+                ctx.accounts.pda_account.to_account_info().key.as_ref(),
+                ESCROW_PDA_SEED,
+                &[vault_fee_authority_bump],
+            ];
+            token::transfer(
+                ctx.accounts
+                    .into_transfer_context(
+                        ctx.accounts.vault_fee_account.clone(),
+                        ctx.accounts.owner_fee_paying_token_account.to_account_info(),
+                        ctx.accounts.vault_fee_authority.clone(),
+                    )
+                    .with_signer(&[&fee_seeds[..]]),
+                order.maker_fee,
+            )?;
+        }
+
+        ctx.accounts.order_book.load_mut()?.remove(side, index);
+        ctx.accounts.market.open_orders = ctx.accounts.market.open_orders.saturating_sub(1);
+
+        emit!(OrderCancelledEvent {
+            market: *ctx.accounts.market.to_account_info().key,
+            owner: order.owner,
+            side,
+            price: order.price,
+            quantity: order.quantity,
+            seq,
+        });
+        Ok(())
+    }
 }
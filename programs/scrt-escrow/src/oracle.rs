@@ -0,0 +1,200 @@
+//! Prices for USD fees, read from a Pyth price account or, in
+//! `test-oracle` builds, from a `PriceAccount` owned by this program,
+//! which stands in for one locally.
+
+use anchor_lang::prelude::*;
+use std::convert::TryInto;
+
+#[cfg(feature = "test-oracle")]
+use crate::account::PriceAccount;
+use crate::error::ScrtEscrowErrors;
+use crate::split::TOTAL_BPS;
+
+/// USD amounts in the fee config are in millionths of a dollar.
+pub const USD_DECIMALS: u32 = 6;
+pub const USD_FEES_SPACE: usize = 32 + 8 * 2 + 8 + 2 + 8 * 2 + 1;
+
+/// Pyth's oracle program, which owns every real price feed.
+pub mod pyth_program {
+    anchor_lang::declare_id!("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH");
+}
+
+// pyth v2 price account layout
+const PYTH_MAGIC: u32 = 0xa1b2_c3d4;
+const PYTH_PRICE_TYPE: u32 = 3;
+const PYTH_TRADING: u32 = 1;
+const PYTH_EXPO: usize = 20;
+const PYTH_TIMESTAMP: usize = 96;
+const PYTH_AGG_PRICE: usize = 208;
+const PYTH_AGG_CONF: usize = 216;
+const PYTH_AGG_STATUS: usize = 224;
+const PYTH_PRICE_LEN: usize = 240;
+
+/// A price of `price * 10^expo` USD per whole token, give or take `conf`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OraclePrice {
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: i64,
+    /// Pyth stops publishing an aggregate while a product isn't trading.
+    pub trading: bool,
+}
+
+impl OraclePrice {
+    /// Whether the price is positive, published within `max_staleness`
+    /// seconds of `now` and has a confidence interval of at most
+    /// `max_conf_bps` of the price.
+    pub fn usable(&self, now: i64, max_staleness: i64, max_conf_bps: u16) -> bool {
+        self.trading
+            && self.price > 0
+            && now.saturating_sub(self.publish_time) <= max_staleness
+            && self.conf as u128 * TOTAL_BPS as u128 <= self.price as u128 * max_conf_bps as u128
+    }
+}
+
+#[cfg_attr(not(feature = "test-oracle"), allow(unused_variables))]
+pub fn read_price(info: &AccountInfo, program_id: &Pubkey) -> Result<OraclePrice, ProgramError> {
+    #[cfg(feature = "test-oracle")]
+    if info.owner == program_id {
+        let account: Account<PriceAccount> = Account::try_from(info)?;
+        return Ok(OraclePrice {
+            price: account.price,
+            conf: account.conf,
+            expo: account.expo,
+            publish_time: account.publish_time,
+            trading: true,
+        });
+    }
+
+    if !pyth_program::check_id(info.owner) {
+        return Err(ScrtEscrowErrors::InvalidPriceFeed.into());
+    }
+    let data = info.try_borrow_data()?;
+    if data.len() < PYTH_PRICE_LEN
+        || read_u32(&data, 0) != PYTH_MAGIC
+        || read_u32(&data, 8) != PYTH_PRICE_TYPE
+    {
+        return Err(ScrtEscrowErrors::InvalidPriceFeed.into());
+    }
+    Ok(OraclePrice {
+        price: read_u64(&data, PYTH_AGG_PRICE) as i64,
+        conf: read_u64(&data, PYTH_AGG_CONF),
+        expo: read_u32(&data, PYTH_EXPO) as i32,
+        publish_time: read_u64(&data, PYTH_TIMESTAMP) as i64,
+        trading: read_u32(&data, PYTH_AGG_STATUS) == PYTH_TRADING,
+    })
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+/// Fee token fees targeting a USD value, priced off `price_feed`. While
+/// the feed is unusable the flat fallback fees apply instead.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UsdFees {
+    /// Price of the fee token, unset while USD fees are off.
+    pub price_feed: Pubkey,
+    pub maker_usd: u64,
+    pub taker_usd: u64,
+    /// Seconds a price stays fresh.
+    pub max_staleness: i64,
+    pub max_conf_bps: u16,
+    pub fallback_maker_fee: u64,
+    pub fallback_taker_fee: u64,
+    pub fee_decimals: u8,
+}
+
+impl UsdFees {
+    pub fn enabled(&self) -> bool {
+        self.price_feed != Pubkey::default()
+    }
+
+    pub fn validate(&self) -> ProgramResult {
+        if self.max_staleness <= 0 || self.max_conf_bps > TOTAL_BPS {
+            return Err(ScrtEscrowErrors::InvalidUsdFees.into());
+        }
+        Ok(())
+    }
+
+    /// Maker and taker fees in fee token units at the feed's current price.
+    pub fn fees(&self, price_feed: &AccountInfo, program_id: &Pubkey) -> Result<(u64, u64), ProgramError> {
+        let price = read_price(price_feed, program_id)?;
+        if !price.usable(Clock::get()?.unix_timestamp, self.max_staleness, self.max_conf_bps) {
+            return Ok((self.fallback_maker_fee, self.fallback_taker_fee));
+        }
+        Ok((
+            usd_to_tokens(self.maker_usd, &price, self.fee_decimals)?,
+            usd_to_tokens(self.taker_usd, &price, self.fee_decimals)?,
+        ))
+    }
+}
+
+/// `usd`, in millionths, as base units of a token with `decimals` priced
+/// at `price`. Rounds down.
+pub fn usd_to_tokens(usd: u64, price: &OraclePrice, decimals: u8) -> Result<u64, ProgramError> {
+    let pow = |exp: u32| 10u128.checked_pow(exp).ok_or(ScrtEscrowErrors::PriceOverflow);
+    let mut numerator = (usd as u128)
+        .checked_mul(pow(decimals as u32)?)
+        .ok_or(ScrtEscrowErrors::PriceOverflow)?;
+    let mut denominator = price.price as u128 * pow(USD_DECIMALS)?;
+    if price.expo < 0 {
+        numerator = numerator
+            .checked_mul(pow(price.expo.unsigned_abs())?)
+            .ok_or(ScrtEscrowErrors::PriceOverflow)?;
+    } else {
+        denominator = denominator
+            .checked_mul(pow(price.expo as u32)?)
+            .ok_or(ScrtEscrowErrors::PriceOverflow)?;
+    }
+    Ok((numerator / denominator)
+        .try_into()
+        .map_err(|_| ScrtEscrowErrors::PriceOverflow)?)
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriggerDirection {
+    Above,
+    Below,
+}
+
+/// Holds while `oracle`'s price is at or beyond `threshold`, for
+/// stop-loss and take-profit orders. `threshold` is in the feed's own
+/// units, before its exponent.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PriceTrigger {
+    pub oracle: Pubkey,
+    pub direction: TriggerDirection,
+    pub threshold: i64,
+    /// Seconds a price stays fresh.
+    pub max_staleness: i64,
+}
+
+impl PriceTrigger {
+    pub fn validate(&self) -> ProgramResult {
+        if self.threshold <= 0 || self.max_staleness <= 0 {
+            return Err(ScrtEscrowErrors::InvalidPriceTrigger.into());
+        }
+        Ok(())
+    }
+
+    pub fn check(&self, oracle: &AccountInfo, program_id: &Pubkey) -> ProgramResult {
+        let price = read_price(oracle, program_id)?;
+        if !price.trading || Clock::get()?.unix_timestamp.saturating_sub(price.publish_time) > self.max_staleness {
+            return Err(ScrtEscrowErrors::StalePrice.into());
+        }
+        let met = match self.direction {
+            TriggerDirection::Above => price.price >= self.threshold,
+            TriggerDirection::Below => price.price <= self.threshold,
+        };
+        if !met {
+            return Err(ScrtEscrowErrors::PriceTriggerNotMet.into());
+        }
+        Ok(())
+    }
+}
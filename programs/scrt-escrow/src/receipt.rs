@@ -0,0 +1,31 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{program::invoke, system_instruction};
+
+use crate::account::{SettlementReceipt, SETTLEMENT_RECEIPT_SPACE};
+
+/// Creates a settlement receipt at `receipt`, which must be a fresh keypair
+/// signing the transaction. `payer` funds the rent.
+pub fn write_receipt<'info>(
+    receipt: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    program_id: &Pubkey,
+    data: &SettlementReceipt,
+) -> ProgramResult {
+    let lamports = Rent::get()?.minimum_balance(SETTLEMENT_RECEIPT_SPACE);
+    invoke(
+        &system_instruction::create_account(
+            payer.key,
+            receipt.key,
+            lamports,
+            SETTLEMENT_RECEIPT_SPACE as u64,
+            program_id,
+        ),
+        &[payer.clone(), receipt.clone(), system_program.clone()],
+    )?;
+
+    let mut buf = receipt.try_borrow_mut_data()?;
+    let dst: &mut [u8] = &mut buf;
+    let mut cursor = std::io::Cursor::new(dst);
+    data.try_serialize(&mut cursor)
+}
@@ -0,0 +1,194 @@
+//! Settlement steps shared by `exchange`, `flash_exchange`,
+//! `batch_exchange`, `ring_trade` and `place_order`, so every way of
+//! filling an escrow prices the taker's fee, pays the maker and records
+//! the fill the same way.
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Transfer};
+
+use crate::account::{
+    active_stake, load_price_history, price_history_address, EscrowAccount, PdaAccount, UserStats, CLAIM_SEED,
+};
+use crate::claim;
+use crate::error::ScrtEscrowErrors;
+use crate::scrt_escrow::ClaimCreditedEvent;
+use crate::stake::{apply_discount, discount_for};
+use crate::tier::{bps_of, fee_notional, tier_for};
+
+/// What prices a taker's fee besides the escrow and its fee config.
+pub struct TakerFeeInputs<'a, 'info> {
+    pub taker: &'a Pubkey,
+    /// Mint the taker pays the fee in.
+    pub fee_mint: &'a Pubkey,
+    pub stats: Option<&'a UserStats>,
+    /// None or empty if the taker doesn't stake.
+    pub stake: Option<&'a AccountInfo<'info>>,
+    /// The fee config's price feed, only needed while USD fees are on.
+    pub price_feed: Option<&'a AccountInfo<'info>>,
+}
+
+/// The fill's size in `mint`, if either side trades it.
+pub fn notional(escrow: &EscrowAccount, mint: &Pubkey) -> Option<u64> {
+    fee_notional(
+        mint,
+        &escrow.deposit_token,
+        escrow.initializer_amount,
+        &escrow.receive_token,
+        escrow.taker_amount,
+    )
+}
+
+/// The taker's fee for filling `escrow`. Tiered escrows charge the
+/// taker's tier, untiered fee token fees are repriced in USD while that's
+/// on, other accepted mints pay their own rate, and the taker's stake
+/// discount comes off the result. Deducted fee escrows only take the tier.
+pub fn taker_fee(
+    pda: &PdaAccount,
+    escrow: &EscrowAccount,
+    inputs: &TakerFeeInputs,
+    program_id: &Pubkey,
+) -> Result<u64, ProgramError> {
+    let mut fee = escrow.fee_amount_taker;
+    if escrow.fee_tiered {
+        let volume = inputs.stats.map_or(0, |stats| stats.volume);
+        if let (Some(notional), Some(tier)) = (notional(escrow, &escrow.fee_token), tier_for(&pda.fee_tiers, volume)) {
+            fee = bps_of(notional, tier.taker_bps);
+        }
+    }
+    if escrow.fee_deducted {
+        return Ok(fee);
+    }
+    let usd_fees = pda.usd_fees;
+    if *inputs.fee_mint == escrow.fee_token && !escrow.fee_tiered && usd_fees.enabled() {
+        let price_feed = inputs
+            .price_feed
+            .filter(|price_feed| *price_feed.key == usd_fees.price_feed)
+            .ok_or(ScrtEscrowErrors::InvalidPriceFeed)?;
+        fee = usd_fees.fees(price_feed, program_id)?.1;
+    }
+    if let Some(fee_mint) = pda.fee_mint(inputs.fee_mint) {
+        fee = fee_mint.fee(notional(escrow, inputs.fee_mint))?;
+    }
+    let stake = match inputs.stake {
+        Some(stake) => active_stake(stake, inputs.taker, &escrow.fee_token)?,
+        None => 0,
+    };
+    Ok(apply_discount(fee, discount_for(&pda.stake_discounts, stake)))
+}
+
+/// Where a fill's proceeds come from.
+pub struct ProceedsSource<'a, 'info> {
+    pub from: AccountInfo<'info>,
+    pub authority: AccountInfo<'info>,
+    /// Seeds `authority` signs with, empty when it signed the transaction.
+    pub signer_seeds: &'a [&'a [&'a [u8]]],
+    pub mint: &'a AccountInfo<'info>,
+    /// Pays for claim accounts that have to be opened.
+    pub payer: &'a AccountInfo<'info>,
+    pub system_program: &'a AccountInfo<'info>,
+    pub token_program: &'a AccountInfo<'info>,
+    pub rent: &'a AccountInfo<'info>,
+}
+
+/// One recipient's part of a fill's proceeds.
+pub struct Payout<'info> {
+    pub recipient: AccountInfo<'info>,
+    pub amount: u64,
+    /// Owns `claim`, which takes the amount if `recipient` can't.
+    pub owner: Pubkey,
+    pub claim: AccountInfo<'info>,
+}
+
+/// Pays each of `payouts`, parking what a recipient can't take in its
+/// owner's claim account. Returns where the first payout went.
+pub fn pay_proceeds<'info>(
+    source: &ProceedsSource<'_, 'info>,
+    payouts: &[Payout<'info>],
+    program_id: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    let transfer = |to: AccountInfo<'info>, amount: u64| {
+        token::transfer(
+            CpiContext::new_with_signer(
+                source.token_program.clone(),
+                Transfer {
+                    from: source.from.clone(),
+                    to,
+                    authority: source.authority.clone(),
+                },
+                source.signer_seeds,
+            ),
+            amount,
+        )
+    };
+
+    let mut destinations = Vec::with_capacity(payouts.len());
+    for payout in payouts.iter() {
+        if payout.amount == 0 || claim::can_receive(&payout.recipient, source.mint.key) {
+            if payout.amount > 0 {
+                transfer(payout.recipient.clone(), payout.amount)?;
+            }
+            destinations.push(*payout.recipient.key);
+            continue;
+        }
+
+        let (claim_account, claim_bump) = Pubkey::find_program_address(
+            &[payout.owner.as_ref(), source.mint.key.as_ref(), CLAIM_SEED],
+            program_id,
+        );
+        if *payout.claim.key != claim_account {
+            return Err(ScrtEscrowErrors::ClaimAccountMismatch.into());
+        }
+        let claim_seeds = &[
+            payout.owner.as_ref(),
+            source.mint.key.as_ref(),
+            CLAIM_SEED,
+            &[claim_bump],
+        ];
+        claim::open_claim_account(
+            &payout.claim,
+            source.mint,
+            source.payer,
+            source.system_program,
+            source.token_program,
+            source.rent,
+            claim_seeds,
+        )?;
+        transfer(payout.claim.clone(), payout.amount)?;
+        emit!(ClaimCreditedEvent {
+            owner: payout.owner,
+            mint: *source.mint.key,
+            amount: payout.amount,
+        });
+        destinations.push(claim_account);
+    }
+    Ok(destinations.first().copied().unwrap_or_default())
+}
+
+/// Records a fill of `deposit_amount` of `deposit_token` for
+/// `receive_amount` of `receive_token` in the pair's price history.
+/// `price_history` has to be the pair's history address whether or not
+/// it exists yet, so no fill can skip it once someone has created it.
+pub fn record_fill(
+    price_history: &AccountInfo,
+    deposit_token: &Pubkey,
+    receive_token: &Pubkey,
+    deposit_amount: u64,
+    receive_amount: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    if price_history.data_is_empty() {
+        if *price_history.key != price_history_address(deposit_token, receive_token, program_id) {
+            return Err(ScrtEscrowErrors::InvalidPriceHistory.into());
+        }
+        return Ok(());
+    }
+    load_price_history(price_history, deposit_token, receive_token, program_id)?
+        .load_mut()?
+        .record_fill(
+            deposit_token,
+            receive_token,
+            deposit_amount,
+            receive_amount,
+            Clock::get()?.unix_timestamp,
+        )
+}
@@ -0,0 +1,68 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::TokenAccount;
+
+use crate::error::ScrtEscrowErrors;
+
+pub const MAX_PROCEEDS_SHARES: usize = 4;
+pub const TOTAL_BPS: u16 = 10_000;
+pub const PROCEEDS_SPLIT_SPACE: usize = 4 + (32 + 2) * MAX_PROCEEDS_SHARES;
+pub const PROCEEDS_OWNERS_SPACE: usize = 4 + 32 * MAX_PROCEEDS_SHARES;
+
+/// One recipient of an escrow's proceeds, in basis points of `taker_amount`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProceedsShare {
+    pub recipient: Pubkey,
+    pub bps: u16,
+}
+
+/// An empty split means everything goes to the receive account.
+pub fn validate_split(shares: &[ProceedsShare]) -> ProgramResult {
+    if shares.is_empty() {
+        return Ok(());
+    }
+    let total: u32 = shares.iter().map(|share| share.bps as u32).sum();
+    if shares.len() > MAX_PROCEEDS_SHARES
+        || shares.iter().any(|share| share.bps == 0)
+        || total != TOTAL_BPS as u32
+    {
+        return Err(ScrtEscrowErrors::InvalidProceedsSplit.into());
+    }
+    Ok(())
+}
+
+/// Checks that `accounts` are the split's recipients in order, each a
+/// token account of `mint`, so proceeds can't be pointed at something that
+/// will never take them. Returns their owners, whose claim accounts take a
+/// share a recipient can no longer receive.
+pub fn validate_recipients(shares: &[ProceedsShare], accounts: &[AccountInfo], mint: &Pubkey) -> Result<Vec<Pubkey>, ProgramError> {
+    if accounts.len() < shares.len() {
+        return Err(ScrtEscrowErrors::ProceedsSplitMismatch.into());
+    }
+    let mut owners = Vec::with_capacity(shares.len());
+    for (share, info) in shares.iter().zip(accounts.iter()) {
+        if *info.key != share.recipient {
+            return Err(ScrtEscrowErrors::ProceedsSplitMismatch.into());
+        }
+        let account: Account<TokenAccount> =
+            Account::try_from(info).map_err(|_| ScrtEscrowErrors::InvalidSplitRecipient)?;
+        if account.mint != *mint {
+            return Err(ScrtEscrowErrors::InvalidSplitRecipient.into());
+        }
+        owners.push(account.owner);
+    }
+    Ok(owners)
+}
+
+/// Splits `amount` by `shares`, rounding down each share and giving the
+/// dust to the first recipient.
+pub fn split_amount(amount: u64, shares: &[ProceedsShare]) -> Vec<u64> {
+    let mut amounts: Vec<u64> = shares
+        .iter()
+        .map(|share| (amount as u128 * share.bps as u128 / TOTAL_BPS as u128) as u64)
+        .collect();
+    let paid: u64 = amounts.iter().sum();
+    if let Some(first) = amounts.first_mut() {
+        *first += amount - paid;
+    }
+    amounts
+}
@@ -0,0 +1,43 @@
+use anchor_lang::prelude::*;
+
+use crate::error::ScrtEscrowErrors;
+use crate::split::TOTAL_BPS;
+use crate::tier::bps_of;
+
+pub const MAX_STAKE_DISCOUNTS: usize = 8;
+pub const STAKE_DISCOUNTS_SPACE: usize = 4 + (8 + 2) * MAX_STAKE_DISCOUNTS;
+
+/// Fee discount for users with at least `min_stake` of the fee token
+/// actively staked.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StakeDiscount {
+    pub min_stake: u64,
+    pub discount_bps: u16,
+}
+
+/// The curve is a step function: thresholds and discounts both strictly
+/// increase, so more stake never costs more.
+pub fn validate_discounts(discounts: &[StakeDiscount]) -> ProgramResult {
+    if discounts.len() > MAX_STAKE_DISCOUNTS
+        || discounts.iter().any(|step| step.min_stake == 0 || step.discount_bps > TOTAL_BPS)
+        || discounts.windows(2).any(|pair| {
+            pair[0].min_stake >= pair[1].min_stake || pair[0].discount_bps >= pair[1].discount_bps
+        })
+    {
+        return Err(ScrtEscrowErrors::InvalidStakeDiscounts.into());
+    }
+    Ok(())
+}
+
+/// The discount `stake` earns, zero below the first step.
+pub fn discount_for(discounts: &[StakeDiscount], stake: u64) -> u16 {
+    discounts
+        .iter()
+        .rev()
+        .find(|step| step.min_stake <= stake)
+        .map_or(0, |step| step.discount_bps)
+}
+
+pub fn apply_discount(fee: u64, discount_bps: u16) -> u64 {
+    fee - bps_of(fee, discount_bps)
+}
@@ -0,0 +1,62 @@
+use anchor_lang::prelude::*;
+
+use crate::error::ScrtEscrowErrors;
+use crate::split::TOTAL_BPS;
+
+pub const MAX_FEE_TIERS: usize = 8;
+pub const FEE_TIERS_SPACE: usize = 4 + (8 + 2 + 2) * MAX_FEE_TIERS;
+
+/// Fee rates for users whose filled volume, in the fee token, has reached
+/// `min_volume`. A negative `maker_bps` is a rebate, paid out of the fee
+/// revenue vault.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FeeTier {
+    pub min_volume: u64,
+    pub maker_bps: i16,
+    pub taker_bps: u16,
+}
+
+/// An empty table turns tiered fees off. Otherwise the first tier starts
+/// at zero volume and thresholds strictly increase.
+pub fn validate_tiers(tiers: &[FeeTier]) -> ProgramResult {
+    if tiers.is_empty() {
+        return Ok(());
+    }
+    if tiers.len() > MAX_FEE_TIERS
+        || tiers[0].min_volume != 0
+        || tiers.windows(2).any(|pair| pair[0].min_volume >= pair[1].min_volume)
+        || tiers.iter().any(|tier| {
+            tier.maker_bps.unsigned_abs() > TOTAL_BPS || tier.taker_bps > TOTAL_BPS
+        })
+    {
+        return Err(ScrtEscrowErrors::InvalidFeeTiers.into());
+    }
+    Ok(())
+}
+
+/// The highest tier `volume` qualifies for.
+pub fn tier_for(tiers: &[FeeTier], volume: u64) -> Option<&FeeTier> {
+    tiers.iter().rev().find(|tier| tier.min_volume <= volume)
+}
+
+/// Size of a trade in the fee token, if either leg is the fee token.
+/// Trades between two other mints can't be priced and keep flat fees.
+pub fn fee_notional(
+    fee_token: &Pubkey,
+    deposit_token: &Pubkey,
+    deposit_amount: u64,
+    receive_token: &Pubkey,
+    receive_amount: u64,
+) -> Option<u64> {
+    if deposit_token == fee_token {
+        Some(deposit_amount)
+    } else if receive_token == fee_token {
+        Some(receive_amount)
+    } else {
+        None
+    }
+}
+
+pub fn bps_of(amount: u64, bps: u16) -> u64 {
+    (amount as u128 * bps as u128 / TOTAL_BPS as u128) as u64
+}
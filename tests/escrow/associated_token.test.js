@@ -0,0 +1,55 @@
+const utils = require('../../lib/utils');
+
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+describe('Associated token accounts', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+    });
+
+    it('creates the taker\'s missing receive account', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        f.takerReceive = await utils.getAssociatedTokenAddress(f.mintA.publicKey, f.taker.publicKey);
+        await f.take();
+        assert(await f.balance(f.takerReceive) == 1000_000);
+    });
+
+    it('only creates the canonical account', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        f.takerReceive = anchor.web3.Keypair.generate().publicKey;
+        await lib.assertProgramError(program, f.take(), 'InvalidAssociatedTokenAccount');
+    });
+
+    it('requires the maker\'s associated account when asked to', async () => {
+        await lib.assertProgramError(
+            program,
+            f.make(1000_000, 6000_000, 10_000, 20_000, { canonicalReceive: true }),
+            'InvalidAssociatedTokenAccount');
+    });
+
+    it('recreates the maker\'s closed associated account', async () => {
+        f.makerReceive = await f.mintB.createAssociatedTokenAccount(f.maker.publicKey);
+        await f.make(1000_000, 6000_000, 10_000, 20_000, { canonicalReceive: true });
+        await f.mintB.closeAccount(f.makerReceive, f.maker.publicKey, f.maker, []);
+
+        await f.take();
+        assert(await f.balance(f.makerReceive) == 6000_000);
+        const claimAccount = await lib.getClaimAddress(program, f.maker.publicKey, f.mintB.publicKey);
+        assert(await provider.connection.getAccountInfo(claimAccount) == null);
+    });
+});
@@ -0,0 +1,81 @@
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+describe('Batch cancel and order epochs', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+    let escrows;
+
+    // the maker sells mint A for mint B and for the fee mint
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        await lib.initialize(
+            program,
+            2000_000,
+            5000_000,
+            f.mintA.publicKey,
+            f.makerDeposit,
+            f.mintC.publicKey,
+            await f.funded(f.mintC, f.maker, 0),
+            f.mintC.publicKey,
+            f.feeCollect,
+            10_000,
+            20_000,
+            f.makerFeePay,
+            f.maker,
+        );
+        const [second] = await anchor.web3.PublicKey.findProgramAddress(
+            [f.maker.publicKey.toBuffer(), f.mintA.publicKey.toBuffer(), f.mintC.publicKey.toBuffer()], program.programId);
+        escrows = [await f.escrow(), second];
+    });
+
+    it('cancels every escrow and refunds deposits and fees', async () => {
+        await lib.batchCancel(program, escrows, f.mintC.publicKey, f.maker);
+
+        for(const escrow of escrows){
+            assert('cancelled' in (await program.account.escrowAccount.fetch(escrow)).status);
+        }
+        assert(await f.balance(f.makerDeposit) == 100_000_000);
+        assert(await f.balance(f.makerFeePay) == 10_000_000);
+        assert((await lib.getOpenOrders(program, f.maker.publicKey)).escrows.length == 0);
+    });
+
+    it('only cancels the signer\'s escrows', async () => {
+        await lib.initializeOpenOrders(program, 1, f.taker);
+        await lib.assertProgramError(
+            program, lib.batchCancel(program, escrows, f.mintC.publicKey, f.taker), 'BatchEscrowMismatch');
+        assert(await f.balance(f.makerDeposit) == 100_000_000 - 3000_000);
+    });
+
+    it('fails as a whole on a settled escrow', async () => {
+        await f.take();
+        await lib.assertProgramError(
+            program, lib.batchCancel(program, [escrows[1], escrows[0]], f.mintC.publicKey, f.maker), 'EscrowNotOpen');
+        assert('open' in (await program.account.escrowAccount.fetch(escrows[1])).status);
+    });
+
+    it('stops older escrows from being taken once the epoch is bumped', async () => {
+        const args = await f.takeArgs();
+        await lib.bumpOrderEpoch(program, f.maker);
+        await lib.assertProgramError(program, program.rpc.exchange(...args), 'StaleOrderEpoch');
+
+        // the deposit can still be reclaimed, and new escrows trade
+        await f.cancel();
+        assert(await f.balance(f.makerDeposit) == 100_000_000 - 2000_000);
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        await f.take();
+        assert(await f.balance(f.takerReceive) == 1000_000);
+    });
+});
@@ -0,0 +1,107 @@
+const utils = require('../../lib/utils');
+
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+describe('Batch exchange', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+    let escrows;
+
+    // another maker's escrow of the fixture's pair
+    const makeAs = async (takerAmount) => {
+        const wallet = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, wallet.publicKey, 1000_000_000);
+        await lib.initialize(
+            program,
+            1000_000,
+            takerAmount,
+            f.mintA.publicKey,
+            await f.funded(f.mintA, wallet, 1000_000),
+            f.mintB.publicKey,
+            await f.funded(f.mintB, wallet, 0),
+            f.mintC.publicKey,
+            f.feeCollect,
+            10_000,
+            20_000,
+            await f.funded(f.mintC, wallet, 10_000),
+            wallet,
+        );
+        const [escrow] = await anchor.web3.PublicKey.findProgramAddress(
+            [wallet.publicKey.toBuffer(), f.mintA.publicKey.toBuffer(), f.mintB.publicKey.toBuffer()], program.programId);
+        return escrow;
+    };
+
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        escrows = [await f.escrow(), await makeAs(6500_000), await makeAs(7000_000)];
+    });
+
+    const sweep = (list, maxTotalSpend, minTotalReceive) => lib.batchExchange(
+        program,
+        list,
+        f.mintA.publicKey,
+        f.mintB.publicKey,
+        f.takerDeposit,
+        f.takerReceive,
+        f.takerFeePay,
+        f.mintC.publicKey,
+        maxTotalSpend,
+        minTotalReceive,
+        f.taker,
+    );
+
+    it('fills in order until the next escrow would exceed the spend', async () => {
+        await sweep(escrows, 13_000_000, 2000_000);
+
+        assert(await f.balance(f.takerReceive) == 2000_000);
+        assert(await f.balance(f.takerDeposit) == 100_000_000 - 12_500_000);
+        assert(await f.balance(f.takerFeePay) == 10_000_000 - 40_000);
+        assert(await f.balance(f.feeCollect) == 60_000);
+        assert(await f.balance(f.makerReceive) == 6000_000);
+        assert('filled' in (await utils.getEscrowAccount(program, escrows[1])).status);
+        assert('open' in (await utils.getEscrowAccount(program, escrows[2])).status);
+    });
+
+    it('fails as a whole below the minimum receive', async () => {
+        await lib.assertProgramError(program, sweep(escrows, 13_000_000, 3000_000), 'BatchMinReceiveNotMet');
+        assert(await f.balance(f.takerReceive) == 0);
+        assert(await f.balance(f.takerDeposit) == 100_000_000);
+        assert('open' in (await f.escrowData()).status);
+    });
+
+    it('fails as a whole on a settled escrow', async () => {
+        const [first, ...rest] = escrows;
+        await f.take();
+        await lib.assertProgramError(program, sweep([...rest, first], 100_000_000, 0), 'EscrowNotOpen');
+        assert(await f.balance(f.takerReceive) == 1000_000);
+    });
+
+    it('rejects an escrow of another pair', async () => {
+        await lib.assertProgramError(program, lib.batchExchange(
+            program,
+            escrows,
+            f.mintA.publicKey,
+            f.mintC.publicKey,
+            f.takerFeePay,
+            f.takerReceive,
+            f.takerFeePay,
+            f.mintC.publicKey,
+            100_000_000,
+            0,
+            f.taker,
+        ), 'BatchEscrowMismatch');
+    });
+});
@@ -0,0 +1,65 @@
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+// anchor's error for a failed `constraint = ...`
+const CONSTRAINT_RAW = 143;
+
+describe('Claimable proceeds', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+    });
+
+    it('parks proceeds when the receive account is gone and lets the maker claim them', async () => {
+        await f.mintB.closeAccount(f.makerReceive, f.maker.publicKey, f.maker, []);
+        await f.take();
+
+        const claimAccount = await lib.getClaimAddress(program, f.maker.publicKey, f.mintB.publicKey);
+        assert(await f.balance(claimAccount) == 6000_000);
+        assert(await f.balance(f.takerReceive) == 1000_000);
+
+        const destination = await f.funded(f.mintB, f.maker, 0);
+        await lib.claim(program, f.mintB.publicKey, destination, f.maker);
+        assert(await f.balance(destination) == 6000_000);
+        assert(await provider.connection.getAccountInfo(claimAccount) == null);
+    });
+
+    it('refuses a claim without parked proceeds', async () => {
+        await f.take();
+        await assert.rejects(lib.claim(program, f.mintB.publicKey, f.makerReceive, f.maker));
+        assert(await f.balance(f.makerReceive) == 6000_000);
+    });
+
+    it('pays a receive account the maker switched to', async () => {
+        const replacement = await f.funded(f.mintB, f.maker, 0);
+        await lib.updateReceiveAccount(program, await f.escrow(), replacement, f.maker);
+        await f.take();
+
+        assert(await f.balance(replacement) == 6000_000);
+        assert(await f.balance(f.makerReceive) == 0);
+    });
+
+    it('only switches to an account of the receive mint on an open escrow', async () => {
+        await lib.assertProgramError(
+            program, lib.updateReceiveAccount(program, await f.escrow(), f.makerFeePay, f.maker), CONSTRAINT_RAW);
+
+        await f.take();
+        const replacement = await f.funded(f.mintB, f.maker, 0);
+        await lib.assertProgramError(
+            program, lib.updateReceiveAccount(program, await f.escrow(), replacement, f.maker), 'EscrowNotOpen');
+    });
+});
@@ -0,0 +1,108 @@
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const crypto = require('crypto');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+describe('Fill conditions', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    const now = () => Math.floor(Date.now() / 1000);
+    const preimage = Buffer.from('open sesame');
+    const hashlock = { hashlock: { digest: [...crypto.createHash('sha256').update(preimage).digest()] } };
+    let f;
+    let approver;
+
+    const makeWith = (...conditions) => f.make(1000_000, 6000_000, 10_000, 20_000, { conditions });
+
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+        approver = anchor.web3.Keypair.generate();
+    });
+
+    it('fills once every condition holds', async () => {
+        await makeWith(
+            { expiry: { ends: new anchor.BN(now() + 3600) } },
+            { taker: { taker: f.taker.publicKey } },
+            hashlock,
+            { approval: { approver: approver.publicKey } },
+        );
+        await f.take({ preimages: [preimage], approvers: [approver] });
+        assert(await f.balance(f.takerReceive) == 1000_000);
+    });
+
+    it('won\'t fill past its expiry', async () => {
+        await makeWith({ expiry: { ends: new anchor.BN(now() - 1) } });
+        await lib.assertProgramError(program, f.take(), 'EscrowExpired');
+    });
+
+    it('only lets the named taker fill', async () => {
+        await makeWith({ taker: { taker: f.maker.publicKey } });
+        await lib.assertProgramError(program, f.take(), 'TakerNotAllowed');
+    });
+
+    it('needs the hashlock\'s preimage', async () => {
+        await makeWith(hashlock);
+        await lib.assertProgramError(program, f.take(), 'HashlockNotMet');
+        await lib.assertProgramError(program, f.take({ preimages: [Buffer.from('open barley')] }), 'HashlockNotMet');
+    });
+
+    it('needs the approver\'s signature', async () => {
+        await makeWith({ approval: { approver: approver.publicKey } });
+        const args = await f.takeArgs();
+        args[5].remainingAccounts = args[5].remainingAccounts
+            .map((meta) => meta.pubkey.equals(approver.publicKey) ? { ...meta, isSigner: false } : meta);
+        await lib.assertProgramError(program, program.rpc.exchange(...args), 'ApprovalMissing');
+    });
+
+    it('needs an account for each condition that takes one', async () => {
+        await makeWith({ approval: { approver: approver.publicKey } });
+        const args = await f.takeArgs();
+        args[5].remainingAccounts = args[5].remainingAccounts
+            .filter((meta) => !meta.pubkey.equals(approver.publicKey));
+        await lib.assertProgramError(program, program.rpc.exchange(...args), 'ConditionAccountsMissing');
+    });
+
+    it('locks cancels until the unlock time', async () => {
+        await makeWith({ cancelAfter: { unlocks: new anchor.BN(now() + 3600) } });
+        await lib.assertProgramError(program, f.cancel(), 'CancelLocked');
+        await f.take();
+        assert(await f.balance(f.makerReceive) == 6000_000);
+    });
+
+    it('cancels once unlocked', async () => {
+        await makeWith({ cancelAfter: { unlocks: new anchor.BN(now() - 1) } });
+        await f.cancel();
+        assert(await f.balance(f.makerDeposit) == 100_000_000);
+    });
+
+    it('rejects more than four conditions', async () => {
+        const conditions = [0, 1, 2, 3, 4].map(() => ({ taker: { taker: f.taker.publicKey } }));
+        await lib.assertProgramError(program, makeWith(...conditions), 'InvalidConditions');
+    });
+
+    it('can\'t be filled in a batch', async () => {
+        await makeWith({ taker: { taker: f.taker.publicKey } });
+        await lib.assertProgramError(program, lib.batchExchange(
+            program,
+            [await f.escrow()],
+            f.mintA.publicKey,
+            f.mintB.publicKey,
+            f.takerDeposit,
+            f.takerReceive,
+            f.takerFeePay,
+            f.mintC.publicKey,
+            6000_000,
+            1000_000,
+            f.taker,
+        ), 'ConditionsUnsupported');
+    });
+});
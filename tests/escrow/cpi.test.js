@@ -0,0 +1,298 @@
+const utils = require('../../lib/utils');
+
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const splToken = require('@solana/spl-token');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+
+describe('Escrow CPI tests', () => {
+    const homedir = os.homedir();
+    process.env.ANCHOR_WALLET = `${homedir}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    // companion program trading through the escrow with a PDA signer,
+    // loaded at genesis under its declared id (see Anchor.toml)
+    const clientIdl = JSON.parse(fs.readFileSync('./target/idl/escrow_client.json', 'utf8'));
+    const client = new anchor.Program(clientIdl, new anchor.web3.PublicKey('HxrvP2B5Fm3pZZBjSDUcMDuGek51KGBUWHXHYDYxxJQa'));
+
+    const mintAuthority = anchor.web3.Keypair.generate();
+
+    let mintA;
+    let mintB;
+    let mintC;
+    let walletB;
+    let walletFeeCollector;
+    let authority;
+    let feeCollectTokenAccount;
+    let pdaDepositTokenAccount;
+    let pdaReceiveTokenAccount;
+    let pdaFeePayTokenAccount;
+    let takerDepositTokenAccount;
+    let takerReceiveTokenAccount;
+    let takerFeePayTokenAccount;
+
+    async function escrowAccounts(initializer) {
+        const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
+            [program.programId.toBuffer(), mintC.publicKey.toBuffer(), Buffer.from("ser-escrow")], program.programId);
+        const pdaData = await utils.getPdaAccount(program, pdaAccount);
+        const [escrowAccount] = await anchor.web3.PublicKey.findProgramAddress(
+            [initializer.toBuffer(), mintA.publicKey.toBuffer(), mintB.publicKey.toBuffer()], program.programId);
+        const [vaultAccount] = await anchor.web3.PublicKey.findProgramAddress(
+            [escrowAccount.toBuffer()], program.programId);
+        const [vaultAuthority] = await anchor.web3.PublicKey.findProgramAddress(
+            [Buffer.from("escrow"), escrowAccount.toBuffer()], program.programId);
+        return {
+            pdaAccount,
+            escrowAccount,
+            openOrders: await lib.getOpenOrdersAddress(program, initializer),
+            market: await lib.getMarketAddress(program, mintA.publicKey, mintB.publicKey),
+            vaultAccount,
+            vaultAuthority,
+            vaultFeeAccount: pdaData.vaultFeeAccount,
+            vaultFeeAuthority: pdaData.vaultFeeAuthority,
+        };
+    }
+
+    async function make() {
+        const acc = await escrowAccounts(authority);
+        await client.rpc.make(
+            new anchor.BN(1000),
+            new anchor.BN(2000),
+            new anchor.BN(10),
+            new anchor.BN(20),
+            {
+                accounts: {
+                    authority,
+                    feeToken: mintC.publicKey,
+                    pdaAccount: acc.pdaAccount,
+                    depositToken: mintA.publicKey,
+                    escrowAccount: acc.escrowAccount,
+                    market: acc.market,
+                    makerStats: await lib.getUserStatsAddress(program, authority, mintC.publicKey),
+                    makerStake: await lib.getStakeAddress(program, authority, mintC.publicKey),
+                    priceFeed: acc.pdaAccount,
+                    openOrders: acc.openOrders,
+                    vaultAuthority: acc.vaultAuthority,
+                    vaultAccount: acc.vaultAccount,
+                    depositTokenAccount: pdaDepositTokenAccount,
+                    receiveToken: mintB.publicKey,
+                    receiveTokenAccount: pdaReceiveTokenAccount,
+                    feeCollectTokenAccount,
+                    vaultFeeAccount: acc.vaultFeeAccount,
+                    feePayingTokenAccount: pdaFeePayTokenAccount,
+                    tokenProgram: splToken.TOKEN_PROGRAM_ID,
+                    systemProgram: anchor.web3.SystemProgram.programId,
+                    rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+                    escrowProgram: program.programId,
+                },
+            },
+        );
+        return acc;
+    }
+
+    beforeEach(async () => {
+        walletB = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, walletB.publicKey, 1000_000_000);
+        walletFeeCollector = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, walletFeeCollector.publicKey, 1000_000_000);
+
+        [authority] = await anchor.web3.PublicKey.findProgramAddress(
+            [Buffer.from("authority")], client.programId);
+        await utils.createWallet(provider.connection, authority, 1000_000_000);
+
+        mintA = await splToken.Token.createMint(
+            provider.connection, walletB, mintAuthority.publicKey, null, 0, splToken.TOKEN_PROGRAM_ID);
+        mintB = await splToken.Token.createMint(
+            provider.connection, walletB, mintAuthority.publicKey, null, 0, splToken.TOKEN_PROGRAM_ID);
+        mintC = await splToken.Token.createMint(
+            provider.connection, walletB, mintAuthority.publicKey, null, 0, splToken.TOKEN_PROGRAM_ID);
+
+        // PDA initializer
+        pdaDepositTokenAccount = await mintA.createAccount(authority);
+        await mintA.mintTo(pdaDepositTokenAccount, mintAuthority.publicKey, [mintAuthority], 100_000);
+        pdaReceiveTokenAccount = await mintB.createAccount(authority);
+        pdaFeePayTokenAccount = await mintC.createAccount(authority);
+        await mintC.mintTo(pdaFeePayTokenAccount, mintAuthority.publicKey, [mintAuthority], 100_000);
+
+        // keypair taker
+        takerDepositTokenAccount = await mintB.createAccount(walletB.publicKey);
+        await mintB.mintTo(takerDepositTokenAccount, mintAuthority.publicKey, [mintAuthority], 100_000);
+        takerReceiveTokenAccount = await mintA.createAccount(walletB.publicKey);
+        takerFeePayTokenAccount = await mintC.createAccount(walletB.publicKey);
+        await mintC.mintTo(takerFeePayTokenAccount, mintAuthority.publicKey, [mintAuthority], 100_000);
+
+        feeCollectTokenAccount = await mintC.createAccount(walletFeeCollector.publicKey);
+        await lib.initializePda(program, mintC.publicKey, walletFeeCollector);
+
+        if(await lib.getOpenOrders(program, authority) == null){
+            await client.rpc.openOrders(32, {
+                accounts: {
+                    authority,
+                    openOrders: await lib.getOpenOrdersAddress(program, authority),
+                    systemProgram: anchor.web3.SystemProgram.programId,
+                    escrowProgram: program.programId,
+                },
+            });
+        }
+    });
+
+    it('PDA initializer creates and cancels an escrow', async () => {
+        const acc = await make();
+
+        const escrowData = await utils.getEscrowAccount(program, acc.escrowAccount);
+        assert(escrowData.initializerKey.equals(authority));
+        assert(escrowData.vaultAuthority.equals(acc.vaultAuthority));
+        assert(await utils.getTokenAccountBalance(provider.connection, pdaDepositTokenAccount) == 99_000);
+
+        await client.rpc.cancel({
+            accounts: {
+                authority,
+                pdaAccount: acc.pdaAccount,
+                escrowAccount: acc.escrowAccount,
+                openOrders: acc.openOrders,
+                market: acc.market,
+                vaultAccount: acc.vaultAccount,
+                vaultAuthority: acc.vaultAuthority,
+                depositTokenAccount: pdaDepositTokenAccount,
+                feePayingTokenAccount: pdaFeePayTokenAccount,
+                vaultFeeAccount: acc.vaultFeeAccount,
+                vaultFeeAuthority: acc.vaultFeeAuthority,
+                tokenProgram: splToken.TOKEN_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+                escrowProgram: program.programId,
+            },
+        });
+
+        assert('cancelled' in (await utils.getEscrowAccount(program, acc.escrowAccount)).status);
+        assert(await utils.getTokenAccountBalance(provider.connection, pdaDepositTokenAccount) == 100_000);
+
+        await client.rpc.close({
+            accounts: {
+                authority,
+                escrowAccount: acc.escrowAccount,
+                escrowProgram: program.programId,
+            },
+        });
+        assert(await utils.getEscrowAccount(program, acc.escrowAccount) == null);
+    });
+
+    it('PDA initializer can\'t close an open escrow', async () => {
+        const acc = await make();
+        await lib.assertProgramError(program, client.rpc.close({
+            accounts: {
+                authority,
+                escrowAccount: acc.escrowAccount,
+                escrowProgram: program.programId,
+            },
+        }), 'EscrowStillOpen');
+        assert('open' in (await utils.getEscrowAccount(program, acc.escrowAccount)).status);
+    });
+
+    it('keypair taker fills a PDA escrow', async () => {
+        await make();
+
+        const res = await lib.exchange(
+            program,
+            authority,
+            mintA.publicKey,
+            mintB.publicKey,
+            takerDepositTokenAccount,
+            takerReceiveTokenAccount,
+            takerFeePayTokenAccount,
+            mintC.publicKey,
+            walletB,
+        );
+        assert(res == 'ok');
+        assert(await utils.getTokenAccountBalance(provider.connection, pdaReceiveTokenAccount) == 2000);
+        assert(await utils.getTokenAccountBalance(provider.connection, takerReceiveTokenAccount) == 1000);
+    });
+
+    it('PDA taker fills a keypair escrow', async () => {
+        // swap roles: walletB makes A for B, the PDA takes it
+        const makerDeposit = await mintA.createAccount(walletB.publicKey);
+        await mintA.mintTo(makerDeposit, mintAuthority.publicKey, [mintAuthority], 1000);
+        await lib.initialize(
+            program,
+            1000,
+            2000,
+            mintA.publicKey,
+            makerDeposit,
+            mintB.publicKey,
+            takerDepositTokenAccount,
+            mintC.publicKey,
+            feeCollectTokenAccount,
+            10,
+            20,
+            takerFeePayTokenAccount,
+            walletB,
+        );
+
+        const pdaPayTokenAccount = await mintB.createAccount(authority);
+        await mintB.mintTo(pdaPayTokenAccount, mintAuthority.publicKey, [mintAuthority], 2000);
+
+        const acc = await escrowAccounts(walletB.publicKey);
+        const escrowData = await utils.getEscrowAccount(program, acc.escrowAccount);
+        await client.rpc.take(
+            new anchor.BN(2000),
+            new anchor.BN(1000),
+            new anchor.BN(20),
+            [],
+            {
+                takerFeePaying: true,
+                referrer: false,
+                makerReferrer: false,
+                makerStats: false,
+                takerStats: false,
+                takerStake: false,
+                priceFeed: false,
+                takerReceipt: false,
+                makerReceipt: false,
+                feeRevenueVault: false,
+            },
+            {
+                accounts: {
+                    authority,
+                    pdaAccount: acc.pdaAccount,
+                    initializer: walletB.publicKey,
+                    escrowAccount: acc.escrowAccount,
+                    openOrders: acc.openOrders,
+                    market: acc.market,
+                    priceHistory: await lib.getPriceHistoryAddress(program, mintA.publicKey, mintB.publicKey),
+                    depositTokenAccount: pdaPayTokenAccount,
+                    receiveTokenAccount: pdaDepositTokenAccount,
+                    depositToken: mintA.publicKey,
+                    initializerReceiveTokenAccount: escrowData.initializerReceiveTokenAccount,
+                    receiveToken: mintB.publicKey,
+                    claimAccount: await lib.getClaimAddress(program, walletB.publicKey, mintB.publicKey),
+                    vaultAccount: escrowData.vaultAccount,
+                    vaultAuthority: escrowData.vaultAuthority,
+                    vaultFeeAccount: acc.vaultFeeAccount,
+                    vaultFeeAuthority: acc.vaultFeeAuthority,
+                    initializerFeeCollectTokenAccount: feeCollectTokenAccount,
+                    takerFeeCollectTokenAccount: feeCollectTokenAccount,
+                    initializerFeePayingTokenAccount: escrowData.initializerFeePayingTokenAccount,
+                    tokenProgram: splToken.TOKEN_PROGRAM_ID,
+                    systemProgram: anchor.web3.SystemProgram.programId,
+                    rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+                    associatedTokenProgram: splToken.ASSOCIATED_TOKEN_PROGRAM_ID,
+                    escrowProgram: program.programId,
+                },
+                remainingAccounts: [
+                    { pubkey: pdaFeePayTokenAccount, isSigner: false, isWritable: true },
+                ],
+            },
+        );
+
+        assert(await utils.getTokenAccountBalance(provider.connection, pdaPayTokenAccount) == 0);
+        assert(await utils.getTokenAccountBalance(provider.connection, pdaDepositTokenAccount) == 101_000);
+    });
+});
@@ -0,0 +1,120 @@
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+const CONSTRAINT_RAW = 143;
+
+describe('Deducted fees', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+    let collectA;
+    let collectB;
+
+    // feeAdmin collects 20 bps of the deposit and 30 bps of the payment
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+        collectA = (await f.mintA.getOrCreateAssociatedAccountInfo(f.feeAdmin.publicKey)).address;
+        collectB = (await f.mintB.getOrCreateAssociatedAccountInfo(f.feeAdmin.publicKey)).address;
+        await lib.updateDeductedFees(program, f.mintC.publicKey, f.feeAdmin.publicKey, 20, 30, f.feeAdmin);
+    });
+
+    it('takes the fees out of the traded amounts', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 0, { deductFees: true });
+        assert(await f.balance(f.makerFeePay) == 10_000_000);
+        await f.take();
+
+        assert(await f.balance(f.takerReceive) == 1000_000 - 2_000);
+        assert(await f.balance(f.makerReceive) == 6000_000 - 18_000);
+        assert(await f.balance(f.takerDeposit) == 100_000_000 - 6000_000);
+        assert(await f.balance(collectA) == 2_000);
+        assert(await f.balance(collectB) == 18_000);
+        assert(await f.balance(f.takerFeePay) == 10_000_000);
+        assert(await f.balance(f.feeCollect) == 0);
+    });
+
+    it('needs no fee token account on either side', async () => {
+        f.makerFeePay = null;
+        f.takerFeePay = null;
+        await f.make(1000_000, 6000_000, 10_000, 0, { deductFees: true });
+        const escrowData = await f.escrowData();
+        assert(escrowData.initializerFeePayingTokenAccount.equals(f.makerDeposit));
+        await f.take();
+
+        assert(await f.balance(f.takerReceive) == 1000_000 - 2_000);
+        assert(await f.balance(f.makerReceive) == 6000_000 - 18_000);
+        assert(await f.balance(collectA) == 2_000);
+        assert(await f.balance(collectB) == 18_000);
+    });
+
+    it('still needs the taker\'s fee account for other escrows', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 100);
+        const args = await f.takeArgs();
+        // the fee paying account leads the extras
+        args[4].takerFeePaying = false;
+        args[5].remainingAccounts.shift();
+        await lib.assertProgramError(program, program.rpc.exchange(...args), 'TakerFeeAccountMissing');
+    });
+
+    it('holds the taker to their maximum fee', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 0, { deductFees: true });
+        await lib.assertProgramError(program, f.take({ maxTakerFee: 17_999 }), 'TakerFeeExceedsMax');
+    });
+
+    it('holds the maker to their fee cap', async () => {
+        await lib.assertProgramError(
+            program, f.make(1000_000, 6000_000, 1_999, 0, { deductFees: true }), 'InitializerFeeExceedsMax');
+    });
+
+    it('refunds only the deposit on cancel', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 0, { deductFees: true });
+        await f.cancel();
+        assert(await f.balance(f.makerDeposit) == 100_000_000);
+        assert(await f.balance(f.makerFeePay) == 10_000_000);
+        assert(await f.balance(collectA) == 0);
+    });
+
+    it('can only be filled by exchange', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 0, { deductFees: true });
+        await lib.assertProgramError(program, lib.batchExchange(
+            program,
+            [await f.escrow()],
+            f.mintA.publicKey,
+            f.mintB.publicKey,
+            f.takerDeposit,
+            f.takerReceive,
+            f.takerFeePay,
+            f.mintC.publicKey,
+            6000_000,
+            1000_000,
+            f.taker,
+        ), 'FeeDeductionUnsupported');
+    });
+
+    it('is off without a collector', async () => {
+        await lib.updateDeductedFees(program, f.mintC.publicKey, null, 20, 30, f.feeAdmin);
+        await lib.assertProgramError(
+            program, f.make(1000_000, 6000_000, 10_000, 0, { deductFees: true }), 'FeeDeductionDisabled');
+    });
+
+    it('rejects a rate above 10000 bps', async () => {
+        await lib.assertProgramError(
+            program, lib.updateDeductedFees(program, f.mintC.publicKey, f.feeAdmin.publicKey, 20, 10_001, f.feeAdmin),
+            'InvalidDeductedFees');
+    });
+
+    it('only lets the fee admin set the rates', async () => {
+        await lib.assertProgramError(
+            program, lib.updateDeductedFees(program, f.mintC.publicKey, f.taker.publicKey, 0, 0, f.taker),
+            CONSTRAINT_RAW);
+    });
+});
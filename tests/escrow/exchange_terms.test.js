@@ -0,0 +1,49 @@
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+describe('Exchange terms', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+    });
+
+    it('rejects a taker amount other than the escrow\'s', async () => {
+        const args = await f.takeArgs();
+        args[0] = new anchor.BN(5000_000);
+        await lib.assertProgramError(program, program.rpc.exchange(...args), 'TakerAmountMismatch');
+        assert(await f.balance(f.takerDeposit) == 100_000_000);
+    });
+
+    it('rejects an initializer amount other than the escrow\'s', async () => {
+        const args = await f.takeArgs();
+        args[1] = new anchor.BN(1000_001);
+        await lib.assertProgramError(program, program.rpc.exchange(...args), 'InitializerAmountMismatch');
+        assert(await f.balance(f.takerReceive) == 0);
+    });
+
+    it('rejects a taker fee above the taker\'s maximum', async () => {
+        await lib.assertProgramError(program, f.take({ maxTakerFee: 19_999 }), 'TakerFeeExceedsMax');
+        assert(await f.balance(f.takerFeePay) == 10_000_000);
+    });
+
+    it('fills at exactly the maximum fee', async () => {
+        await f.take({ maxTakerFee: 20_000 });
+        assert(await f.balance(f.takerReceive) == 1000_000);
+        assert(await f.balance(f.takerFeePay) == 10_000_000 - 20_000);
+        assert(await f.balance(f.feeCollect) == 30_000);
+    });
+});
@@ -0,0 +1,111 @@
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const splToken = require('@solana/spl-token');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+describe('Fee distribution', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+    // treasury, insurance fund and buyback
+    let destinations;
+    let feeCollect;
+
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+        destinations = [];
+        for(let i = 0; i < 3; i++){
+            destinations.push(await f.funded(f.mintC, anchor.web3.Keypair.generate(), 0));
+        }
+        feeCollect = f.feeCollect;
+        f.feeCollect = await lib.initializeFeeDistribution(program, f.mintC.publicKey, [
+            { recipient: destinations[0], bps: 5000 },
+            { recipient: destinations[1], bps: 3000 },
+            { recipient: destinations[2], bps: 2000 },
+        ], f.feeAdmin);
+    });
+
+    it('pays out the revenue vault by share to anyone\'s call', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        await f.take();
+        assert(await f.balance(f.feeCollect) == 30_000);
+
+        await lib.distributeFees(program, f.mintC.publicKey);
+        assert(await f.balance(destinations[0]) == 15_000);
+        assert(await f.balance(destinations[1]) == 9_000);
+        assert(await f.balance(destinations[2]) == 6_000);
+        assert(await f.balance(f.feeCollect) == 0);
+    });
+
+    // each destination's owner is paid other mints in their associated account
+    const openDestinations = async (mint) => {
+        const accounts = [];
+        for(const destination of destinations){
+            const owner = (await f.mintC.getAccountInfo(destination)).owner;
+            accounts.push((await mint.getOrCreateAssociatedAccountInfo(owner)).address);
+        }
+        return accounts;
+    };
+
+    it('collects fees in an accepted mint in that mint\'s revenue vault', async () => {
+        const mintD = await splToken.Token.createMint(
+            provider.connection, f.maker, f.mintAuthority.publicKey, null, 6, splToken.TOKEN_PROGRAM_ID);
+        await lib.addFeeMint(program, f.mintC.publicKey, mintD.publicKey, await f.funded(mintD, f.feeAdmin, 0), 7_000, 0, f.feeAdmin);
+        const revenueD = await lib.openRevenueVault(program, f.mintC.publicKey, mintD.publicKey);
+        f.makerFeePay = await f.funded(mintD, f.maker, 1000_000);
+        f.takerFeePay = await f.funded(mintD, f.taker, 1000_000);
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        await f.take();
+        assert(await f.balance(revenueD) == 14_000);
+
+        const paid = await openDestinations(mintD);
+        await lib.distributeFees(program, f.mintC.publicKey, mintD.publicKey);
+        assert(await f.balance(paid[0]) == 7_000);
+        assert(await f.balance(paid[1]) == 4_200);
+        assert(await f.balance(paid[2]) == 2_800);
+        assert(await f.balance(revenueD) == 0);
+    });
+
+    it('collects deducted fees in the traded mints\' revenue vaults', async () => {
+        await lib.updateDeductedFees(program, f.mintC.publicKey, f.feeAdmin.publicKey, 20, 30, f.feeAdmin);
+        const revenueA = await lib.openRevenueVault(program, f.mintC.publicKey, f.mintA.publicKey);
+        const revenueB = await lib.openRevenueVault(program, f.mintC.publicKey, f.mintB.publicKey);
+        await f.make(1000_000, 6000_000, 10_000, 0, { deductFees: true });
+        await f.take();
+        assert(await f.balance(revenueA) == 2_000);
+        assert(await f.balance(revenueB) == 18_000);
+
+        const paid = await openDestinations(f.mintB);
+        await lib.distributeFees(program, f.mintC.publicKey, f.mintB.publicKey);
+        assert(await f.balance(paid[0]) == 9_000);
+        assert(await f.balance(paid[1]) == 5_400);
+        assert(await f.balance(paid[2]) == 3_600);
+    });
+
+    it('has nothing to distribute from an empty vault', async () => {
+        await lib.assertProgramError(program, lib.distributeFees(program, f.mintC.publicKey), 'NothingToDistribute');
+    });
+
+    it('sends new escrows\' fees to the revenue vault only', async () => {
+        f.feeCollect = feeCollect;
+        await lib.assertProgramError(program, f.make(1000_000, 6000_000, 10_000, 20_000), 'FeeCollectorMismatch');
+    });
+
+    it('rejects a table that doesn\'t add up to 10000 bps', async () => {
+        await lib.assertProgramError(program, lib.updateFeeDistribution(program, f.mintC.publicKey, [
+            { recipient: destinations[0], bps: 5000 },
+            { recipient: destinations[1], bps: 4999 },
+        ], f.feeAdmin), 'InvalidFeeDistribution');
+        await lib.assertProgramError(
+            program, lib.updateFeeDistribution(program, f.mintC.publicKey, [], f.feeAdmin), 'InvalidFeeDistribution');
+    });
+});
@@ -0,0 +1,83 @@
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const splToken = require('@solana/spl-token');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+describe('Accepted fee mints', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+    let mintD;
+    let collectorD;
+
+    // fees may also be paid in mint D, at a flat 7000
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+        mintD = await splToken.Token.createMint(
+            provider.connection, f.maker, f.mintAuthority.publicKey, null, 6, splToken.TOKEN_PROGRAM_ID);
+        collectorD = await f.funded(mintD, f.feeAdmin, 0);
+        await lib.addFeeMint(program, f.mintC.publicKey, mintD.publicKey, collectorD, 7_000, 0, f.feeAdmin);
+    });
+
+    it('lets both sides pay in the other mint', async () => {
+        f.makerFeePay = await f.funded(mintD, f.maker, 1000_000);
+        f.takerFeePay = await f.funded(mintD, f.taker, 1000_000);
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        await f.take();
+
+        assert(await f.balance(f.makerFeePay) == 1000_000 - 7_000);
+        assert(await f.balance(f.takerFeePay) == 1000_000 - 7_000);
+        assert(await f.balance(collectorD) == 14_000);
+        assert(await f.balance(f.feeCollect) == 0);
+    });
+
+    it('sends each side\'s fee to its mint\'s collector', async () => {
+        f.takerFeePay = await f.funded(mintD, f.taker, 1000_000);
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        await f.take();
+
+        assert(await f.balance(f.feeCollect) == 10_000);
+        assert(await f.balance(collectorD) == 7_000);
+    });
+
+    it('refunds a cancelled maker fee to its own mint', async () => {
+        f.makerFeePay = await f.funded(mintD, f.maker, 1000_000);
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        await f.cancel();
+        assert(await f.balance(f.makerFeePay) == 1000_000);
+    });
+
+    it('prices a bps fee off the trade\'s leg in that mint', async () => {
+        const collectorB = await f.funded(f.mintB, f.feeAdmin, 0);
+        await lib.addFeeMint(program, f.mintC.publicKey, f.mintB.publicKey, collectorB, 0, 50, f.feeAdmin);
+        f.takerFeePay = await f.funded(f.mintB, f.taker, 1000_000);
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+
+        // 50 bps of the 6000000 B leg
+        await lib.assertProgramError(program, f.take({ maxTakerFee: 29_999 }), 'TakerFeeExceedsMax');
+        await f.take({ maxTakerFee: 30_000 });
+        assert(await f.balance(collectorB) == 30_000);
+        assert(await f.balance(f.takerFeePay) == 1000_000 - 30_000);
+    });
+
+    it('can\'t price a bps fee on a trade without a leg in that mint', async () => {
+        await lib.updateFeeMint(program, f.mintC.publicKey, mintD.publicKey, 0, 50, f.feeAdmin);
+        f.makerFeePay = await f.funded(mintD, f.maker, 1000_000);
+        await lib.assertProgramError(program, f.make(1000_000, 6000_000, 10_000, 20_000), 'FeeMintUnpriceable');
+    });
+
+    it('rejects a rate above 10000 bps', async () => {
+        await lib.assertProgramError(
+            program, lib.updateFeeMint(program, f.mintC.publicKey, mintD.publicKey, 0, 10_001, f.feeAdmin),
+            'InvalidFeeMint');
+    });
+});
@@ -0,0 +1,130 @@
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+describe('Fee tiers', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+    let makerReceiveC;
+
+    // tiers only price trades with a fee token leg, so the maker sells
+    // mint A for the fee mint
+    const make = (feeInitializer, feeCollect = f.feeCollect) => lib.initialize(
+        program,
+        1000_000,
+        2000_000,
+        f.mintA.publicKey,
+        f.makerDeposit,
+        f.mintC.publicKey,
+        makerReceiveC,
+        f.mintC.publicKey,
+        feeCollect,
+        feeInitializer,
+        100_000,
+        f.makerFeePay,
+        f.maker,
+    );
+    const take = () => lib.exchange(
+        program,
+        f.maker.publicKey,
+        f.mintA.publicKey,
+        f.mintC.publicKey,
+        f.takerFeePay,
+        f.takerReceive,
+        f.takerFeePay,
+        f.mintC.publicKey,
+        f.taker,
+    );
+    const stats = async (owner) => program.account.userStats.fetch(
+        await lib.getUserStatsAddress(program, owner.publicKey, f.mintC.publicKey));
+
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+        makerReceiveC = await f.funded(f.mintC, f.maker, 0);
+        await lib.updateFeeTiers(program, f.mintC.publicKey, [
+            { minVolume: 0, makerBps: 10, takerBps: 30 },
+            { minVolume: 1000_000, makerBps: -5, takerBps: 20 },
+        ], f.feeAdmin);
+        await lib.initializeUserStats(program, f.mintC.publicKey, f.maker);
+        await lib.initializeUserStats(program, f.mintC.publicKey, f.taker);
+    });
+
+    it('charges the base tier and records both sides\' volume', async () => {
+        await make(10_000);
+        await take();
+
+        assert(await f.balance(f.makerFeePay) == 10_000_000 - 2_000);
+        assert(await f.balance(f.takerFeePay) == 10_000_000 - 2000_000 - 6_000);
+        assert(await f.balance(f.feeCollect) == 8_000);
+        assert((await stats(f.maker)).volume.toNumber() == 2000_000);
+        assert((await stats(f.taker)).volume.toNumber() == 2000_000);
+    });
+
+    it('rebates the maker out of protocol revenue once in a higher tier', async () => {
+        await make(10_000);
+        await take();
+        const revenueVault = await lib.initializeFeeDistribution(
+            program, f.mintC.publicKey, [{ recipient: f.feeCollect, bps: 10_000 }], f.feeAdmin);
+        await make(10_000, revenueVault);
+        await take();
+
+        // no maker fee, the taker pays their full 20 bps and the revenue
+        // vault pays the 5 bps rebate
+        assert(await f.balance(f.makerFeePay) == 10_000_000 - 2_000 + 1_000);
+        assert(await f.balance(f.takerFeePay) == 10_000_000 - 4000_000 - 6_000 - 4_000);
+        assert(await f.balance(f.feeCollect) == 8_000);
+        assert(await f.balance(revenueVault) == 4_000 - 1_000);
+    });
+
+    it('pays no rebate while the fee config collects no revenue', async () => {
+        await make(10_000);
+        await take();
+        await make(10_000);
+        await take();
+
+        assert(await f.balance(f.makerFeePay) == 10_000_000 - 2_000);
+        assert(await f.balance(f.feeCollect) == 8_000 + 4_000);
+    });
+
+    it('needs both sides\' stats while tiers are on', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        const args = await f.takeArgs();
+        // the maker's stats come first
+        args[4].makerStats = false;
+        args[5].remainingAccounts.splice(0, 1);
+        await lib.assertProgramError(program, program.rpc.exchange(...args), 'UserStatsMissing');
+    });
+
+    it('rejects stats at someone else\'s address, even empty ones', async () => {
+        const stranger = anchor.web3.Keypair.generate();
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        const args = await f.takeArgs();
+        args[5].remainingAccounts[1].pubkey = await lib.getUserStatsAddress(program, stranger.publicKey, f.mintC.publicKey);
+        await lib.assertProgramError(program, program.rpc.exchange(...args), 'InvalidUserStats');
+    });
+
+    it('caps the tiered maker fee at the initializer\'s fee', async () => {
+        await lib.assertProgramError(program, make(1_999), 'InitializerFeeExceedsMax');
+    });
+
+    it('rejects tier tables that don\'t start at zero or don\'t increase', async () => {
+        for(const tiers of [
+            [{ minVolume: 1, makerBps: 10, takerBps: 30 }],
+            [{ minVolume: 0, makerBps: 10, takerBps: 30 }, { minVolume: 0, makerBps: 5, takerBps: 20 }],
+            [{ minVolume: 0, makerBps: 10, takerBps: 10_001 }],
+        ]){
+            await lib.assertProgramError(
+                program, lib.updateFeeTiers(program, f.mintC.publicKey, tiers, f.feeAdmin), 'InvalidFeeTiers');
+        }
+    });
+});
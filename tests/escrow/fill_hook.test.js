@@ -0,0 +1,87 @@
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const splToken = require('@solana/spl-token');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+describe('Fill hooks', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+    });
+
+    // a hook program that was never deployed
+    const missingHook = () => ({
+        program: anchor.web3.Keypair.generate().publicKey,
+        accounts: [anchor.web3.Keypair.generate().publicKey],
+    });
+
+    it('aborts the fill when the hook is undeployed', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000, { hook: missingHook() });
+        await lib.assertProgramError(program, f.take(), 'FillHookUnavailable');
+        assert(await f.balance(f.takerReceive) == 0);
+        assert('open' in (await f.escrowData()).status);
+    });
+
+    it('aborts the fill when the hook errors', async () => {
+        // the token program rejects the on_fill instruction data
+        const hook = { program: splToken.TOKEN_PROGRAM_ID, accounts: [anchor.web3.Keypair.generate().publicKey] };
+        await f.make(1000_000, 6000_000, 10_000, 20_000, { hook });
+        await assert.rejects(f.take());
+        assert(await f.balance(f.takerReceive) == 0);
+        assert(await f.balance(f.takerDeposit) == 100_000_000);
+        assert('open' in (await f.escrowData()).status);
+
+        // the maker can still get out of a hook that can't succeed
+        await f.cancel();
+        assert('cancelled' in (await f.escrowData()).status);
+    });
+
+    it('requires the taker to pass the hook accounts', async () => {
+        const hook = missingHook();
+        await f.make(1000_000, 6000_000, 10_000, 20_000, { hook });
+        const args = await f.takeArgs();
+        const ctx = args[args.length - 1];
+        ctx.remainingAccounts = ctx.remainingAccounts.filter((meta) => !meta.pubkey.equals(hook.program));
+        await lib.assertProgramError(program, program.rpc.exchange(...args), 'FillHookAccountsMissing');
+    });
+
+    it('rejects a hook into the escrow program or with too many accounts', async () => {
+        const reentrant = { ...missingHook(), program: program.programId };
+        await lib.assertProgramError(
+            program, f.make(1000_000, 6000_000, 10_000, 20_000, { hook: reentrant }), 'InvalidFillHook');
+
+        const accounts = [...Array(9)].map(() => anchor.web3.Keypair.generate().publicKey);
+        await lib.assertProgramError(
+            program, f.make(1000_000, 6000_000, 10_000, 20_000, { hook: { ...missingHook(), accounts } }),
+            'InvalidFillHook');
+    });
+
+    it('keeps hooked escrows out of batch fills', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000, { hook: missingHook() });
+        await lib.assertProgramError(program, lib.batchExchange(
+            program,
+            [await f.escrow()],
+            f.mintA.publicKey,
+            f.mintB.publicKey,
+            f.takerDeposit,
+            f.takerReceive,
+            f.takerFeePay,
+            f.mintC.publicKey,
+            100_000_000,
+            0,
+            f.taker,
+        ), 'HookUnsupported');
+    });
+});
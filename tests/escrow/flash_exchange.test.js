@@ -0,0 +1,68 @@
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const splToken = require('@solana/spl-token');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+describe('Flash exchange', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+    // the taker's empty account the callback has to fund
+    let payFrom;
+
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        payFrom = await f.funded(f.mintB, f.taker, 0);
+    });
+
+    // the callback is a token transfer into payFrom, standing in for a swap
+    const flash = (amount, callbackProgram = splToken.TOKEN_PROGRAM_ID) => {
+        const callback = splToken.Token.createTransferInstruction(
+            splToken.TOKEN_PROGRAM_ID, f.takerDeposit, payFrom, f.taker.publicKey, [], amount);
+        return lib.flashExchange(
+            program,
+            f.maker.publicKey,
+            f.mintA.publicKey,
+            f.mintB.publicKey,
+            payFrom,
+            f.takerReceive,
+            f.takerFeePay,
+            f.mintC.publicKey,
+            callbackProgram,
+            callback.data,
+            callback.keys,
+            f.taker,
+        );
+    };
+
+    it('pays the taker, runs the callback and then pays the maker', async () => {
+        await flash(6000_000);
+
+        assert(await f.balance(f.takerReceive) == 1000_000);
+        assert(await f.balance(f.makerReceive) == 6000_000);
+        assert(await f.balance(payFrom) == 0);
+        assert(await f.balance(f.feeCollect) == 30_000);
+        assert('filled' in (await f.escrowData()).status);
+    });
+
+    it('reverts when the callback leaves too little to pay the maker', async () => {
+        await lib.assertProgramError(program, flash(5999_999), 'FlashPaymentMissing');
+        assert(await f.balance(f.takerReceive) == 0);
+        assert(await f.balance(f.takerDeposit) == 100_000_000);
+        assert('open' in (await f.escrowData()).status);
+    });
+
+    it('refuses a callback into the escrow program', async () => {
+        await lib.assertProgramError(program, flash(6000_000, program.programId), 'InvalidFlashCallback');
+    });
+});
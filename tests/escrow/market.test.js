@@ -0,0 +1,94 @@
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+// anchor's error for a failed `constraint = ...`
+const CONSTRAINT_RAW = 143;
+
+describe('Markets', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+    let admin;
+
+    // mint A is the base, traded in lots of 0.1 at ticks of 0.001 of mint B
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+        admin = await lib.marketAdmin(program);
+        await lib.initializeMarket(program, f.mintA.publicKey, f.mintB.publicKey, 1_000, 100_000, 5000_000, admin);
+    });
+
+    const market = async () => program.account.market.fetch(
+        await lib.getMarketAddress(program, f.mintA.publicKey, f.mintB.publicKey));
+
+    it('only lets the config admin open a market', async () => {
+        await lib.assertProgramError(
+            program,
+            lib.initializeMarket(program, f.mintA.publicKey, f.mintC.publicKey, 1_000, 100_000, 0, f.maker),
+            CONSTRAINT_RAW);
+    });
+
+    it('lets whoever is the config admin update a market', async () => {
+        await lib.assertProgramError(
+            program,
+            lib.updateMarket(program, f.mintA.publicKey, f.mintB.publicKey, 1_000, 100_000, 0, true, f.maker),
+            CONSTRAINT_RAW);
+
+        // a market outlives the admin that opened it
+        const next = anchor.web3.Keypair.generate();
+        await lib.updateProgramConfig(program, next.publicKey, admin);
+        try {
+            await lib.assertProgramError(
+                program,
+                lib.updateMarket(program, f.mintA.publicKey, f.mintB.publicKey, 1_000, 100_000, 0, true, admin),
+                CONSTRAINT_RAW);
+            await lib.updateMarket(program, f.mintA.publicKey, f.mintB.publicKey, 2_000, 100_000, 0, true, next);
+            assert((await market()).tickSize.toNumber() == 2_000);
+        } finally {
+            await lib.updateProgramConfig(program, admin.publicKey, next);
+        }
+    });
+
+    it('rejects a zero tick or lot size', async () => {
+        await lib.assertProgramError(
+            program,
+            lib.initializeMarket(program, f.mintB.publicKey, f.mintC.publicKey, 0, 100_000, 0, admin),
+            'InvalidMarketConfig');
+        await lib.assertProgramError(
+            program,
+            lib.updateMarket(program, f.mintA.publicKey, f.mintB.publicKey, 1_000, 0, 0, true, admin),
+            'InvalidMarketConfig');
+    });
+
+    it('rejects odd lots, off-tick prices and dust', async () => {
+        await lib.assertProgramError(program, f.make(1050_000, 6000_000, 10_000, 20_000), 'OddLotOrder');
+        await lib.assertProgramError(program, f.make(1000_000, 6000_500, 10_000, 20_000), 'InvalidTickSize');
+        await lib.assertProgramError(program, f.make(500_000, 3000_000, 10_000, 20_000), 'BelowMinNotional');
+        assert(await f.escrowData() == null);
+    });
+
+    it('rejects orders while disabled', async () => {
+        await lib.updateMarket(program, f.mintA.publicKey, f.mintB.publicKey, 1_000, 100_000, 5000_000, false, admin);
+        await lib.assertProgramError(program, f.make(1000_000, 6000_000, 10_000, 20_000), 'MarketDisabled');
+    });
+
+    it('counts open orders and filled volume', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        assert((await market()).openOrders.toNumber() == 1);
+
+        await f.take();
+        const filled = await market();
+        assert(filled.openOrders.toNumber() == 0);
+        assert(filled.baseVolume.toNumber() == 1000_000);
+        assert(filled.quoteVolume.toNumber() == 6000_000);
+    });
+});
@@ -0,0 +1,94 @@
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+// anchor's error for a failed `seeds = ...`
+const CONSTRAINT_SEEDS = 146;
+
+describe('Open orders index', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+    });
+
+    const openEscrows = async () => (await lib.getOpenOrders(program, f.maker.publicKey)).escrows;
+
+    it('lists an escrow while it is open and drops it on fill', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        const escrows = await openEscrows();
+        assert(escrows.length == 1 && escrows[0].equals(await f.escrow()));
+
+        await f.take();
+        assert((await openEscrows()).length == 0);
+    });
+
+    it('drops a cancelled escrow', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        await f.cancel();
+        assert((await openEscrows()).length == 0);
+    });
+
+    it('makes, takes and cancels escrows of a wallet without open orders', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000, { openOrders: false });
+        assert(await lib.getOpenOrders(program, f.maker.publicKey) == null);
+        assert((await f.escrowData()).orderEpoch.toNumber() == 0);
+        await f.take();
+        assert('filled' in (await f.escrowData()).status);
+
+        await lib.closeEscrow(program, f.mintA.publicKey, f.mintB.publicKey, f.maker);
+        await f.make(1000_000, 6000_000, 10_000, 20_000, { openOrders: false });
+        await f.cancel();
+        assert('cancelled' in (await f.escrowData()).status);
+        assert(await lib.getOpenOrders(program, f.maker.publicKey) == null);
+    });
+
+    it('rejects open orders of someone else', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        const args = await f.takeArgs();
+        args[5].accounts.openOrders = await lib.initializeOpenOrders(program, 1, f.taker);
+        await lib.assertProgramError(program, program.rpc.exchange(...args), CONSTRAINT_SEEDS);
+    });
+
+    it('rejects a capacity outside 1..=256', async () => {
+        for(const capacity of [0, 257]){
+            await lib.assertProgramError(
+                program, lib.initializeOpenOrders(program, capacity, f.maker), 'InvalidOpenOrdersCapacity');
+        }
+    });
+
+    it('rejects an escrow past the capacity', async () => {
+        await lib.initializeOpenOrders(program, 1, f.maker);
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+
+        // a second pair, receiving the fee mint
+        const makerReceiveC = await f.funded(f.mintC, f.maker, 0);
+        await lib.assertProgramError(program, lib.initialize(
+            program,
+            1000_000,
+            6000_000,
+            f.mintA.publicKey,
+            f.makerDeposit,
+            f.mintC.publicKey,
+            makerReceiveC,
+            f.mintC.publicKey,
+            f.feeCollect,
+            10_000,
+            20_000,
+            f.makerFeePay,
+            f.maker,
+        ), 'OpenOrdersFull');
+        assert((await openEscrows()).length == 1);
+    });
+});
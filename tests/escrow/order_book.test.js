@@ -0,0 +1,166 @@
+const utils = require('../../lib/utils');
+
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+describe('Order book', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+    let admin;
+    let market;
+    // a second maker selling mint A
+    let other;
+
+    // mint A is the base, in lots of 0.1, priced per lot in mint B
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+        admin = await lib.marketAdmin(program);
+        market = await lib.initializeMarket(program, f.mintA.publicKey, f.mintB.publicKey, 1_000, 100_000, 0, admin);
+        await lib.initializeOrderBook(program, f.mintC.publicKey, f.mintA.publicKey, f.mintB.publicKey, f.feeCollect, admin);
+        await lib.updateBookFees(program, f.mintC.publicKey, 1_000, 2_000, f.feeAdmin);
+
+        const wallet = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, wallet.publicKey, 1000_000_000);
+        other = {
+            wallet,
+            base: await f.funded(f.mintA, wallet, 10_000_000),
+            quote: await f.funded(f.mintB, wallet, 0),
+            feePay: await f.funded(f.mintC, wallet, 10_000_000),
+        };
+
+        // seq 0 at 610, seq 1 at 600 per lot
+        await lib.placeOrder(program, market, 'ask', 610_000, 500_000, f.makerDeposit, f.makerReceive, f.makerFeePay, [], f.maker);
+        await lib.placeOrder(program, market, 'ask', 600_000, 500_000, other.base, other.quote, other.feePay, [], other.wallet);
+    });
+
+    const book = async () => program.account.orderBook.fetch((await program.account.market.fetch(market)).orderBook);
+    const bid = (price, quantity, makers) => lib.placeOrder(
+        program, market, 'bid', price, quantity, f.takerReceive, f.takerDeposit, f.takerFeePay, makers, f.taker);
+
+    it('rests orders and prepays the maker fee', async () => {
+        const resting = await book();
+        assert(resting.askCount.toNumber() == 2);
+        assert(resting.asks[0].price.toNumber() == 600_000);
+        assert(await f.balance(f.makerDeposit) == 100_000_000 - 500_000);
+        assert(await f.balance(f.makerFeePay) == 10_000_000 - 1_000);
+    });
+
+    it('fills the best asks first across makers and rests nothing', async () => {
+        await bid(610_000, 800_000, [other.quote, f.makerReceive]);
+
+        assert(await f.balance(f.takerReceive) == 800_000);
+        assert(await f.balance(f.takerDeposit) == 100_000_000 - 3000_000 - 1830_000);
+        assert(await f.balance(other.quote) == 3000_000);
+        assert(await f.balance(f.makerReceive) == 1830_000);
+        // a taker fee per fill, and each maker fee pro rata to what filled
+        assert(await f.balance(f.takerFeePay) == 10_000_000 - 4_000);
+        assert(await f.balance(f.feeCollect) == 4_000 + 1_000 + 600);
+
+        const after = await book();
+        assert(after.askCount.toNumber() == 1 && after.bidCount.toNumber() == 0);
+        assert(after.asks[0].seq.toNumber() == 0 && after.asks[0].quantity.toNumber() == 200_000);
+        assert(after.asks[0].makerFee.toNumber() == 400);
+        const marketData = await program.account.market.fetch(market);
+        assert(marketData.baseVolume.toNumber() == 800_000);
+        assert(marketData.quoteVolume.toNumber() == 4830_000);
+    });
+
+    it('rests the part of a bid that does not cross', async () => {
+        await bid(600_000, 700_000, [other.quote]);
+
+        assert(await f.balance(f.takerReceive) == 500_000);
+        const after = await book();
+        assert(after.bidCount.toNumber() == 1);
+        assert(after.bids[0].price.toNumber() == 600_000 && after.bids[0].quantity.toNumber() == 200_000);
+        assert(await f.balance(f.takerDeposit) == 100_000_000 - 3000_000 - 1200_000);
+    });
+
+    it('doesn\'t rest a remainder below the minimum notional', async () => {
+        await lib.updateMarket(program, f.mintA.publicKey, f.mintB.publicKey, 1_000, 100_000, 1500_000, true, admin);
+        await bid(600_000, 700_000, [other.quote]);
+
+        assert(await f.balance(f.takerReceive) == 500_000);
+        assert((await book()).bidCount.toNumber() == 0);
+        assert(await f.balance(f.takerDeposit) == 100_000_000 - 3000_000);
+    });
+
+    it('pays a maker who can\'t receive into their claim account', async () => {
+        await f.mintB.closeAccount(other.quote, other.wallet.publicKey, other.wallet, []);
+        await bid(600_000, 500_000, [other.quote]);
+
+        const claim = await lib.getClaimAddress(program, other.wallet.publicKey, f.mintB.publicKey);
+        assert(await f.balance(claim) == 3000_000);
+        assert(await f.balance(f.takerReceive) == 500_000);
+    });
+
+    it('evicts the worst order of a full side for a better price', async () => {
+        // 30 more one lot asks fill the side, the worst at 729 per lot
+        for(let i = 0; i < 30; i++){
+            await lib.placeOrder(
+                program, market, 'ask', 700_000 + i * 1_000, 100_000, other.base, other.quote, other.feePay, [], other.wallet);
+        }
+        assert((await book()).askCount.toNumber() == 32);
+        await lib.assertProgramError(
+            program,
+            lib.placeOrder(program, market, 'ask', 729_000, 100_000, f.makerDeposit, f.makerReceive, f.makerFeePay, [], f.maker),
+            'OrderBookFull');
+
+        const base = await f.balance(other.base);
+        const feePay = await f.balance(other.feePay);
+        await lib.placeOrder(program, market, 'ask', 650_000, 100_000, f.makerDeposit, f.makerReceive, f.makerFeePay, [], f.maker);
+
+        const after = await book();
+        assert(after.askCount.toNumber() == 32);
+        assert(after.asks.slice(0, 32).every((order) => order.price.toNumber() != 729_000));
+        assert(after.asks.slice(0, 32).some((order) => order.price.toNumber() == 650_000));
+        // the evicted order's deposit and untouched maker fee go back
+        assert(await f.balance(other.base) == base + 100_000);
+        assert(await f.balance(other.feePay) == feePay + 1_000);
+    });
+
+    it('needs each matched maker\'s receive account in fill order', async () => {
+        await lib.assertProgramError(program, bid(610_000, 800_000, [other.quote]), 'MakerAccountMissing');
+        await lib.assertProgramError(
+            program, bid(610_000, 800_000, [f.makerReceive, other.quote]), 'OrderAccountMismatch');
+        assert(await f.balance(f.takerReceive) == 0);
+    });
+
+    it('refunds the deposit and maker fee on cancel', async () => {
+        await lib.cancelOrder(program, market, 'ask', 0, f.makerDeposit, f.makerFeePay, f.maker);
+
+        assert(await f.balance(f.makerDeposit) == 100_000_000);
+        assert(await f.balance(f.makerFeePay) == 10_000_000);
+        const after = await book();
+        assert(after.askCount.toNumber() == 1 && after.asks[0].seq.toNumber() == 1);
+    });
+
+    it('refunds only the maker fee not yet charged on cancel', async () => {
+        await bid(610_000, 800_000, [other.quote, f.makerReceive]);
+        await lib.cancelOrder(program, market, 'ask', 0, f.makerDeposit, f.makerFeePay, f.maker);
+
+        assert(await f.balance(f.makerDeposit) == 100_000_000 - 300_000);
+        assert(await f.balance(f.makerFeePay) == 10_000_000 - 600);
+    });
+
+    it('only cancels the owner\'s existing orders', async () => {
+        await lib.assertProgramError(
+            program,
+            lib.cancelOrder(program, market, 'ask', 0, f.takerReceive, f.takerFeePay, f.taker),
+            'OrderOwnerMismatch');
+        await lib.assertProgramError(
+            program,
+            lib.cancelOrder(program, market, 'ask', 7, f.makerDeposit, f.makerFeePay, f.maker),
+            'OrderNotFound');
+    });
+});
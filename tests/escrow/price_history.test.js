@@ -0,0 +1,125 @@
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+// quote atoms per base atom are scaled by this
+const PRICE_SCALE = new anchor.BN('1000000000000');
+// anchor's error for a failed `seeds = ...`
+const CONSTRAINT_SEEDS = 146;
+
+function sleep(ms){
+    return new Promise((resolve) => {
+        setTimeout(resolve, ms);
+    });
+}
+
+describe('Price history', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+    });
+
+    // mint A priced in mint B, filled at 6 then at 8
+    const fillTwice = async () => {
+        await lib.initializePriceHistory(program, f.mintA.publicKey, f.mintB.publicKey, f.taker);
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        await f.take();
+        await sleep(2000);
+        await f.make(1000_000, 8000_000, 10_000, 20_000);
+        await f.take();
+        return lib.getPriceHistory(program, f.mintA.publicKey, f.mintB.publicKey);
+    };
+
+    it('records each fill\'s price and volume', async () => {
+        const history = await fillTwice();
+        const [first, second] = history.observations;
+        const elapsed = second.timestamp.sub(first.timestamp);
+
+        assert(history.fills.eqn(2));
+        assert(first.price.eq(PRICE_SCALE.muln(6)));
+        assert(second.price.eq(PRICE_SCALE.muln(8)));
+        assert(history.lastPrice.eq(second.price));
+        assert(second.cumulativePrice.eq(first.price.mul(elapsed)));
+        assert(history.cumulativeBaseVolume.eqn(2000_000));
+        assert(history.cumulativeQuoteVolume.eqn(14_000_000));
+    });
+
+    it('averages over the window', async () => {
+        const history = await fillTwice();
+        const [first, second] = history.observations;
+        const t0 = first.timestamp.toNumber();
+        const t1 = second.timestamp.toNumber();
+        const now = t1 + 10;
+
+        // inside the last price's stand
+        assert(lib.twap(history, now, 5).eq(second.price));
+        // back to the first fill, each price weighted by how long it stood
+        const average = first.price.muln(t1 - t0).add(second.price.muln(10)).divn(now - t0);
+        assert(lib.twap(history, now, now - t0).eq(average));
+        // further back than the history reaches
+        assert(lib.twap(history, now, 3600).eq(average));
+    });
+
+    it('has no average before the first fill', async () => {
+        await lib.initializePriceHistory(program, f.mintB.publicKey, f.mintA.publicKey, f.taker);
+        const history = await lib.getPriceHistory(program, f.mintA.publicKey, f.mintB.publicKey);
+        assert(lib.twap(history, 0, 60) == null);
+    });
+
+    it('skips the history of a pair nobody created', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        await f.take();
+        assert(await lib.getPriceHistory(program, f.mintA.publicKey, f.mintB.publicKey) == null);
+    });
+
+    it('records fills in a batch', async () => {
+        await lib.initializePriceHistory(program, f.mintA.publicKey, f.mintB.publicKey, f.taker);
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        await lib.batchExchange(
+            program,
+            [await f.escrow()],
+            f.mintA.publicKey,
+            f.mintB.publicKey,
+            f.takerDeposit,
+            f.takerReceive,
+            f.takerFeePay,
+            f.mintC.publicKey,
+            6000_000,
+            1000_000,
+            f.taker,
+        );
+        const history = await lib.getPriceHistory(program, f.mintA.publicKey, f.mintB.publicKey);
+        assert(history.fills.eqn(1));
+        assert(history.lastPrice.eq(PRICE_SCALE.muln(6)));
+    });
+
+    it('rejects a take without the pair\'s history', async () => {
+        await lib.initializePriceHistory(program, f.mintA.publicKey, f.mintB.publicKey, f.taker);
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        const args = await f.takeArgs();
+        args[5].accounts.priceHistory = args[5].accounts.market;
+        await lib.assertProgramError(program, program.rpc.exchange(...args), CONSTRAINT_SEEDS);
+        const history = await lib.getPriceHistory(program, f.mintA.publicKey, f.mintB.publicKey);
+        assert(history.fills.eqn(0));
+    });
+
+    it('rejects another pair\'s history', async () => {
+        const other = await lib.initializePriceHistory(program, f.mintA.publicKey, f.mintC.publicKey, f.taker);
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        const args = await f.takeArgs();
+        args[5].accounts.priceHistory = other;
+        await lib.assertProgramError(program, program.rpc.exchange(...args), CONSTRAINT_SEEDS);
+    });
+});
@@ -0,0 +1,80 @@
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+function sleep(ms){
+    return new Promise((resolve) => {
+        setTimeout(resolve, ms);
+    });
+}
+
+describe('Price triggers', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+    let oracle;
+
+    const trigger = (direction, threshold, maxStaleness = 60) => ({
+        price: {
+            trigger: {
+                oracle,
+                direction: { [direction]: {} },
+                threshold: new anchor.BN(threshold),
+                maxStaleness: new anchor.BN(maxStaleness),
+            },
+        },
+    });
+    const makeWith = (condition) => f.make(1000_000, 6000_000, 10_000, 20_000, { conditions: [condition] });
+
+    // mint A at $2
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+        oracle = await lib.initializePriceAccount(program, f.mintA.publicKey, -6, f.maker);
+        await lib.updatePrice(program, oracle, 2000_000, 0, f.maker);
+    });
+
+    it('fills a take-profit once the price rises to it', async () => {
+        await makeWith(trigger('above', 2500_000));
+        await lib.assertProgramError(program, f.take(), 'PriceTriggerNotMet');
+
+        await lib.updatePrice(program, oracle, 2500_000, 0, f.maker);
+        await f.take();
+        assert(await f.balance(f.takerReceive) == 1000_000);
+    });
+
+    it('fills a stop-loss once the price falls to it', async () => {
+        await makeWith(trigger('below', 1500_000));
+        await lib.assertProgramError(program, f.take(), 'PriceTriggerNotMet');
+
+        await lib.updatePrice(program, oracle, 1400_000, 0, f.maker);
+        await f.take();
+        assert(await f.balance(f.makerReceive) == 6000_000);
+    });
+
+    it('won\'t fill off a stale price', async () => {
+        await makeWith(trigger('above', 1000_000, 1));
+        await sleep(3000);
+        await lib.assertProgramError(program, f.take(), 'StalePrice');
+        assert(await f.balance(f.takerReceive) == 0);
+    });
+
+    it('rejects an oracle that isn\'t a price account', async () => {
+        oracle = f.makerDeposit;
+        await makeWith(trigger('above', 1000_000));
+        await lib.assertProgramError(program, f.take(), 'InvalidPriceFeed');
+    });
+
+    it('rejects a trigger without a threshold or staleness window', async () => {
+        await lib.assertProgramError(program, makeWith(trigger('above', 0)), 'InvalidPriceTrigger');
+        await lib.assertProgramError(program, makeWith(trigger('above', 1000_000, 0)), 'InvalidPriceTrigger');
+    });
+});
@@ -0,0 +1,75 @@
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+describe('Proceeds split', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+    let owners;
+    let recipients;
+
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+        owners = [0, 1, 2].map(() => anchor.web3.Keypair.generate());
+        recipients = [];
+        for(const owner of owners){
+            recipients.push(await f.funded(f.mintB, owner, 0));
+        }
+    });
+
+    const split = (bps) => bps.map((share, i) => ({ recipient: recipients[i], bps: share }));
+
+    it('splits by bps and gives the rounding dust to the first recipient', async () => {
+        await f.make(1000_000, 1000_001, 10_000, 20_000, { proceedsSplit: split([3333, 3333, 3334]) });
+        await f.take();
+
+        assert(await f.balance(recipients[0]) == 333_301);
+        assert(await f.balance(recipients[1]) == 333_300);
+        assert(await f.balance(recipients[2]) == 333_400);
+        assert(await f.balance(f.makerReceive) == 0);
+    });
+
+    it('parks the share of a recipient that can\'t receive with its owner', async () => {
+        await f.make(1000_000, 1000_000, 10_000, 20_000, { proceedsSplit: split([5000, 5000]) });
+        await f.mintB.closeAccount(recipients[1], owners[1].publicKey, owners[1], []);
+        await f.take();
+
+        assert(await f.balance(recipients[0]) == 500_000);
+        const claimAccount = await lib.getClaimAddress(program, owners[1].publicKey, f.mintB.publicKey);
+        assert(await f.balance(claimAccount) == 500_000);
+    });
+
+    it('rejects shares that don\'t add up to 10000 bps', async () => {
+        for(const bps of [[5000, 4999], [10_000, 0], [2000, 2000, 2000, 2000, 2000]]){
+            while(recipients.length < bps.length){
+                recipients.push(await f.funded(f.mintB, owners[0], 0));
+            }
+            await lib.assertProgramError(
+                program, f.make(1000_000, 1000_000, 10_000, 20_000, { proceedsSplit: split(bps) }), 'InvalidProceedsSplit');
+        }
+    });
+
+    it('rejects a recipient that isn\'t a receive mint token account', async () => {
+        recipients[1] = await f.funded(f.mintA, owners[1], 0);
+        await lib.assertProgramError(
+            program,
+            f.make(1000_000, 1000_000, 10_000, 20_000, { proceedsSplit: split([5000, 5000]) }),
+            'InvalidSplitRecipient');
+
+        recipients[1] = owners[1].publicKey;
+        await lib.assertProgramError(
+            program,
+            f.make(1000_000, 1000_000, 10_000, 20_000, { proceedsSplit: split([5000, 5000]) }),
+            'InvalidSplitRecipient');
+    });
+});
@@ -0,0 +1,77 @@
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+// anchor's error for a failed `constraint = ...`
+const CONSTRAINT_RAW = 143;
+
+describe('Settlement receipts', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+    });
+
+    const closeReceipt = (receipt, owner, payer) => program.rpc.closeReceipt({
+        accounts: { owner: owner.publicKey, payer, receipt },
+        signers: [owner],
+    });
+
+    it('writes a receipt for each side of a fill, paid by the taker', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        const receipts = { taker: anchor.web3.Keypair.generate(), maker: anchor.web3.Keypair.generate() };
+        await f.take({ receipts });
+
+        const escrow = await f.escrow();
+        for(const [receipt, owner] of [[receipts.taker, f.taker], [receipts.maker, f.maker]]){
+            const data = await program.account.settlementReceipt.fetch(receipt.publicKey);
+            assert(data.owner.equals(owner.publicKey));
+            assert(data.payer.equals(f.taker.publicKey));
+            assert(data.escrow.equals(escrow));
+            assert(data.initializer.equals(f.maker.publicKey));
+            assert(data.taker.equals(f.taker.publicKey));
+            assert(data.initializerAmount.toNumber() == 1000_000);
+            assert(data.takerAmount.toNumber() == 6000_000);
+            assert(data.feeAmountInitializer.toNumber() == 10_000);
+            assert(data.feeAmountTaker.toNumber() == 20_000);
+            assert('filled' in data.status);
+        }
+    });
+
+    it('writes only the receipts asked for', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        const maker = anchor.web3.Keypair.generate();
+        await f.take({ receipts: { maker } });
+
+        const data = await program.account.settlementReceipt.fetch(maker.publicKey);
+        assert(data.owner.equals(f.maker.publicKey));
+    });
+
+    it('lets the owner close a receipt and refunds its payer', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        const maker = anchor.web3.Keypair.generate();
+        await f.take({ receipts: { maker } });
+
+        const rent = await provider.connection.getBalance(maker.publicKey);
+        await lib.assertProgramError(
+            program, closeReceipt(maker.publicKey, f.taker, f.taker.publicKey), CONSTRAINT_RAW);
+        await lib.assertProgramError(
+            program, closeReceipt(maker.publicKey, f.maker, f.maker.publicKey), CONSTRAINT_RAW);
+
+        const before = await provider.connection.getBalance(f.taker.publicKey);
+        await closeReceipt(maker.publicKey, f.maker, f.taker.publicKey);
+        assert(await provider.connection.getAccountInfo(maker.publicKey) == null);
+        assert(await provider.connection.getBalance(f.taker.publicKey) == before + rent);
+    });
+});
@@ -0,0 +1,79 @@
+const utils = require('../../lib/utils');
+
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+// anchor's error for a failed `constraint = ...`
+const CONSTRAINT_RAW = 143;
+
+describe('Third-party recipients', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+    // cold storage, which never signs
+    let cold;
+
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+        cold = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, cold.publicKey, 1000_000_000);
+    });
+
+    const makeWithRefund = (refundTokenAccount) => lib.initialize(
+        program,
+        1000_000,
+        6000_000,
+        f.mintA.publicKey,
+        f.makerDeposit,
+        f.mintB.publicKey,
+        f.makerReceive,
+        f.mintC.publicKey,
+        f.feeCollect,
+        10_000,
+        20_000,
+        f.makerFeePay,
+        f.maker,
+        {},
+        refundTokenAccount,
+    );
+
+    it('settles proceeds and the taker\'s side to other owners', async () => {
+        f.makerReceive = await f.funded(f.mintB, cold, 0);
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        f.takerReceive = await f.funded(f.mintA, cold, 0);
+        await f.take();
+
+        assert(await f.balance(f.makerReceive) == 6000_000);
+        assert(await f.balance(f.takerReceive) == 1000_000);
+    });
+
+    it('refunds a cancelled deposit to the refund recipient', async () => {
+        const refund = await f.funded(f.mintA, cold, 0);
+        await makeWithRefund(refund);
+        assert((await f.escrowData()).refundTokenAccount.equals(refund));
+
+        await f.cancel();
+        assert(await f.balance(refund) == 1000_000);
+        assert(await f.balance(f.makerDeposit) == 100_000_000 - 1000_000);
+    });
+
+    it('rejects a refund recipient of another mint', async () => {
+        await lib.assertProgramError(program, makeWithRefund(await f.funded(f.mintB, cold, 0)), CONSTRAINT_RAW);
+    });
+
+    it('rejects a taker receive account of another mint', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        f.takerReceive = await f.funded(f.mintB, cold, 0);
+        await lib.assertProgramError(program, f.take(), 'InvalidReceiveTokenAccount');
+    });
+});
@@ -0,0 +1,110 @@
+const utils = require('../../lib/utils');
+
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+describe('Referrals', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+    let referrer;
+
+    // referrers get 10% of the fee of whoever they referred
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+        await lib.updateReferralShare(program, f.mintC.publicKey, 1000, f.feeAdmin);
+        const owner = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, owner.publicKey, 1000_000_000);
+        await lib.initializeReferrer(program, f.mintC.publicKey, owner);
+        referrer = { owner: owner.publicKey, tokenAccount: await f.funded(f.mintC, owner, 0) };
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+    });
+
+    it('pays the referrer its cut of the taker fee only', async () => {
+        await f.take({}, referrer);
+
+        assert(await f.balance(referrer.tokenAccount) == 2_000);
+        assert(await f.balance(f.takerFeePay) == 10_000_000 - 20_000);
+        assert(await f.balance(f.feeCollect) == 10_000 + 18_000);
+        const stats = await program.account.referrerStats.fetch(
+            await lib.getReferrerStatsAddress(program, referrer.owner, f.mintC.publicKey));
+        assert(stats.fills.toNumber() == 1 && stats.feeEarned.toNumber() == 2_000);
+    });
+
+    it('pays whoever referred the maker their cut of the initializer fee', async () => {
+        const owner = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, owner.publicKey, 1000_000_000);
+        await lib.initializeReferrer(program, f.mintC.publicKey, owner);
+        const makerReferrer = { owner: owner.publicKey, tokenAccount: await f.funded(f.mintC, owner, 0) };
+        await f.cancel();
+        await f.make(1000_000, 6000_000, 10_000, 20_000, { referrer: makerReferrer });
+        assert((await f.escrowData()).referrer.equals(makerReferrer.tokenAccount));
+
+        await f.take({}, referrer);
+
+        assert(await f.balance(makerReferrer.tokenAccount) == 1_000);
+        assert(await f.balance(referrer.tokenAccount) == 2_000);
+        assert(await f.balance(f.feeCollect) == 9_000 + 18_000);
+        const stats = await program.account.referrerStats.fetch(
+            await lib.getReferrerStatsAddress(program, makerReferrer.owner, f.mintC.publicKey));
+        assert(stats.fills.toNumber() == 1 && stats.feeEarned.toNumber() == 1_000);
+    });
+
+    it('counts both cuts when one referrer brought both sides', async () => {
+        await f.cancel();
+        await f.make(1000_000, 6000_000, 10_000, 20_000, { referrer });
+
+        await f.take({}, referrer);
+
+        assert(await f.balance(referrer.tokenAccount) == 1_000 + 2_000);
+        const stats = await program.account.referrerStats.fetch(
+            await lib.getReferrerStatsAddress(program, referrer.owner, f.mintC.publicKey));
+        assert(stats.fills.toNumber() == 2 && stats.feeEarned.toNumber() == 3_000);
+    });
+
+    it("won't fill without the escrow's referrer", async () => {
+        await f.cancel();
+        await f.make(1000_000, 6000_000, 10_000, 20_000, { referrer });
+
+        const args = await f.takeArgs();
+        args[4].makerReferrer = false;
+        args[5].remainingAccounts.splice(0, 2);
+        await lib.assertProgramError(program, program.rpc.exchange(...args), 'ReferrerMissing');
+    });
+
+    it('rejects the maker referring itself', async () => {
+        await lib.initializeReferrer(program, f.mintC.publicKey, f.maker);
+        await f.cancel();
+        await lib.assertProgramError(
+            program,
+            f.make(1000_000, 6000_000, 10_000, 20_000, { referrer: { owner: f.maker.publicKey, tokenAccount: f.makerFeePay } }),
+            'SelfReferral');
+    });
+
+    it('rejects the taker referring itself', async () => {
+        await lib.initializeReferrer(program, f.mintC.publicKey, f.taker);
+        await lib.assertProgramError(
+            program, f.take({}, { owner: f.taker.publicKey, tokenAccount: f.takerFeePay }), 'SelfReferral');
+    });
+
+    it('rejects a referrer account outside the fee token', async () => {
+        const owner = { publicKey: referrer.owner };
+        referrer.tokenAccount = await f.funded(f.mintA, owner, 0);
+        await lib.assertProgramError(program, f.take({}, referrer), 'InvalidReferrer');
+    });
+
+    it('caps the referral share at 10000 bps', async () => {
+        await lib.assertProgramError(
+            program, lib.updateReferralShare(program, f.mintC.publicKey, 10_001, f.feeAdmin), 'InvalidReferralShare');
+    });
+});
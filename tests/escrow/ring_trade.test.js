@@ -0,0 +1,130 @@
+const utils = require('../../lib/utils');
+
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const splToken = require('@solana/spl-token');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+describe('Ring trade', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+    let mintD;
+
+    // an escrow of a new maker, depositing `amount` of `depositMint`
+    const makeLeg = async (depositMint, amount, receiveMint, takerAmount) => {
+        const wallet = anchor.web3.Keypair.generate();
+        await utils.createWallet(provider.connection, wallet.publicKey, 1000_000_000);
+        const leg = {
+            deposit: await f.funded(depositMint, wallet, amount),
+            receive: await f.funded(receiveMint, wallet, 0),
+        };
+        await lib.initialize(
+            program,
+            amount,
+            takerAmount,
+            depositMint.publicKey,
+            leg.deposit,
+            receiveMint.publicKey,
+            leg.receive,
+            f.mintC.publicKey,
+            f.feeCollect,
+            10_000,
+            20_000,
+            await f.funded(f.mintC, wallet, 10_000),
+            wallet,
+        );
+        [leg.escrow] = await anchor.web3.PublicKey.findProgramAddress(
+            [wallet.publicKey.toBuffer(), depositMint.publicKey.toBuffer(), receiveMint.publicKey.toBuffer()],
+            program.programId);
+        return leg;
+    };
+
+    const crank = (escrows) => lib.ringTrade(program, escrows, f.mintC.publicKey, f.taker);
+
+    // fees may be paid in every ring mint at 50 bps of the leg
+    const acceptRingMints = async () => {
+        const collectors = {};
+        for(const [name, mint] of [['a', f.mintA], ['b', f.mintB], ['d', mintD]]){
+            collectors[name] = await f.funded(mint, f.feeAdmin, 0);
+            await lib.addFeeMint(program, f.mintC.publicKey, mint.publicKey, collectors[name], 0, 50, f.feeAdmin);
+        }
+        return collectors;
+    };
+
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+        mintD = await splToken.Token.createMint(
+            provider.connection, f.maker, f.mintAuthority.publicKey, null, 6, splToken.TOKEN_PROGRAM_ID);
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+    });
+
+    it('takes each leg\'s taker fee out of its surplus', async () => {
+        const collectors = await acceptRingMints();
+        // the maker's A pays for D, which pays for B, which pays the maker
+        const wantsA = await makeLeg(mintD, 3500_000, f.mintA, 990_000);
+        const wantsD = await makeLeg(f.mintB, 6100_000, mintD, 3000_000);
+        await crank([await f.escrow(), wantsA.escrow, wantsD.escrow]);
+
+        assert(await f.balance(f.makerReceive) == 6000_000);
+        assert(await f.balance(wantsA.receive) == 990_000);
+        assert(await f.balance(wantsD.receive) == 3000_000);
+        // each surplus less 50 bps of the leg's deposit goes back
+        assert(await f.balance(f.makerDeposit) == 100_000_000 - 1000_000 + 10_000 - 5_000);
+        assert(await f.balance(wantsA.deposit) == 500_000 - 17_500);
+        assert(await f.balance(wantsD.deposit) == 100_000 - 30_500);
+        assert(await f.balance(collectors.a) == 5_000);
+        assert(await f.balance(collectors.d) == 17_500);
+        assert(await f.balance(collectors.b) == 30_500);
+        // the makers' prepaid fees, and nothing from the cranker
+        assert(await f.balance(f.feeCollect) == 30_000);
+        assert(await f.balance(f.takerFeePay) == 10_000_000);
+        for(const escrow of [await f.escrow(), wantsA.escrow, wantsD.escrow]){
+            assert('filled' in (await utils.getEscrowAccount(program, escrow)).status);
+        }
+    });
+
+    it('rejects a leg whose surplus doesn\'t cover its taker fee', async () => {
+        await acceptRingMints();
+        const wantsA = await makeLeg(mintD, 3000_010, f.mintA, 990_000);
+        const wantsD = await makeLeg(f.mintB, 6100_000, mintD, 3000_000);
+        await lib.assertProgramError(
+            program, crank([await f.escrow(), wantsA.escrow, wantsD.escrow]), 'RingAmountUnsatisfiable');
+        assert(await f.balance(f.takerFeePay) == 10_000_000);
+    });
+
+    it('can\'t take a taker fee in a mint fees aren\'t paid in', async () => {
+        const wantsA = await makeLeg(mintD, 3500_000, f.mintA, 1000_000);
+        const wantsD = await makeLeg(f.mintB, 6000_000, mintD, 3000_000);
+        await lib.assertProgramError(
+            program, crank([await f.escrow(), wantsA.escrow, wantsD.escrow]), 'RingFeeUnpayable');
+    });
+
+    it('needs at least two legs', async () => {
+        await lib.assertProgramError(program, crank([await f.escrow()]), 'RingTooShort');
+    });
+
+    it('rejects legs whose mints don\'t chain', async () => {
+        const wantsA = await makeLeg(mintD, 3500_000, f.mintA, 1000_000);
+        const wantsD = await makeLeg(f.mintB, 6000_000, mintD, 3000_000);
+        await lib.assertProgramError(
+            program, crank([await f.escrow(), wantsD.escrow, wantsA.escrow]), 'RingMintMismatch');
+    });
+
+    it('rejects a leg that doesn\'t cover the next ask', async () => {
+        const wantsA = await makeLeg(mintD, 2500_000, f.mintA, 1000_000);
+        const wantsD = await makeLeg(f.mintB, 6000_000, mintD, 3000_000);
+        await lib.assertProgramError(
+            program, crank([await f.escrow(), wantsA.escrow, wantsD.escrow]), 'RingAmountUnsatisfiable');
+        assert('open' in (await f.escrowData()).status);
+    });
+});
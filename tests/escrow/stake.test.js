@@ -0,0 +1,72 @@
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+describe('Stake discounts', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+    });
+
+    // 25% off from 1 staked fee token, 50% off from 5, an hour to unbond
+    const openVault = () => lib.initializeStakeVault(program, f.mintC.publicKey, 3600, [
+        { minStake: 1000_000, discountBps: 2500 },
+        { minStake: 5000_000, discountBps: 5000 },
+    ], f.feeAdmin);
+
+    it('discounts both sides by their active stake', async () => {
+        await openVault();
+        await lib.stake(program, f.mintC.publicKey, 5000_000, f.makerFeePay, f.maker);
+        await lib.stake(program, f.mintC.publicKey, 1000_000, f.takerFeePay, f.taker);
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        assert((await f.escrowData()).feeAmountInitializer.toNumber() == 5_000);
+        await f.take();
+
+        assert(await f.balance(f.makerFeePay) == 10_000_000 - 5000_000 - 5_000);
+        assert(await f.balance(f.takerFeePay) == 10_000_000 - 1000_000 - 15_000);
+        assert(await f.balance(f.feeCollect) == 20_000);
+    });
+
+    it('gives no discount for unbonding stake', async () => {
+        await openVault();
+        await lib.stake(program, f.mintC.publicKey, 1000_000, f.takerFeePay, f.taker);
+        await lib.unstake(program, f.mintC.publicKey, 1000_000, f.taker);
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        await f.take();
+
+        assert(await f.balance(f.takerFeePay) == 10_000_000 - 1000_000 - 20_000);
+    });
+
+    it('holds unstaked tokens until the unbonding period ends', async () => {
+        await openVault();
+        await lib.stake(program, f.mintC.publicKey, 1000_000, f.takerFeePay, f.taker);
+        await lib.assertProgramError(
+            program, lib.unstake(program, f.mintC.publicKey, 1000_001, f.taker), 'InsufficientStake');
+        await lib.assertProgramError(
+            program, lib.withdrawStake(program, f.mintC.publicKey, f.takerFeePay, f.taker), 'NothingToWithdraw');
+
+        await lib.unstake(program, f.mintC.publicKey, 1000_000, f.taker);
+        await lib.assertProgramError(
+            program, lib.withdrawStake(program, f.mintC.publicKey, f.takerFeePay, f.taker), 'StakeStillUnbonding');
+        assert(await f.balance(f.takerFeePay) == 10_000_000 - 1000_000);
+    });
+
+    it('rejects a discount curve that doesn\'t increase', async () => {
+        await lib.assertProgramError(program, lib.initializeStakeVault(program, f.mintC.publicKey, 3600, [
+            { minStake: 1000_000, discountBps: 5000 },
+            { minStake: 5000_000, discountBps: 2500 },
+        ], f.feeAdmin), 'InvalidStakeDiscounts');
+    });
+});
@@ -0,0 +1,78 @@
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+describe('Escrow status', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    const now = () => Math.floor(Date.now() / 1000);
+    let f;
+
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+    });
+
+    it('keeps a filled escrow as a tombstone', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        // built while open, with a different fee cap so it isn't a duplicate
+        const again = await f.takeArgs({ maxTakerFee: 20_001 });
+        await f.take();
+
+        assert('filled' in (await f.escrowData()).status);
+        await lib.assertProgramError(program, program.rpc.exchange(...again), 'EscrowNotOpen');
+        await lib.assertProgramError(program, f.cancel(), 'EscrowNotOpen');
+        assert(await f.balance(f.takerReceive) == 1000_000);
+    });
+
+    it('refunds and marks a cancelled escrow', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        assert(await f.balance(f.makerDeposit) == 100_000_000 - 1000_000);
+        await f.cancel();
+
+        assert('cancelled' in (await f.escrowData()).status);
+        assert(await f.balance(f.makerDeposit) == 100_000_000);
+        assert(await f.balance(f.makerFeePay) == 10_000_000);
+    });
+
+    it('only lets the initializer cancel an unexpired escrow', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000, {
+            conditions: [{ expiry: { ends: new anchor.BN(now() + 3600) } }],
+        });
+        await lib.assertProgramError(program, f.cancel(f.taker), 'CancelUnauthorized');
+        assert('open' in (await f.escrowData()).status);
+    });
+
+    it('lets anyone cancel an expired escrow and marks it expired', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000, {
+            conditions: [{ expiry: { ends: new anchor.BN(now() - 3600) } }],
+        });
+        await lib.assertProgramError(program, f.take(), 'EscrowExpired');
+
+        await f.cancel(f.taker);
+        assert('expired' in (await f.escrowData()).status);
+        assert(await f.balance(f.makerDeposit) == 100_000_000);
+    });
+
+    it('closes only settled escrows and frees the pair for a new one', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        await lib.assertProgramError(
+            program, lib.closeEscrow(program, f.mintA.publicKey, f.mintB.publicKey, f.maker), 'EscrowStillOpen');
+
+        await f.cancel();
+        await lib.closeEscrow(program, f.mintA.publicKey, f.mintB.publicKey, f.maker);
+        assert(await f.escrowData() == null);
+
+        await f.make(2000_000, 6000_000, 10_000, 20_000);
+        assert('open' in (await f.escrowData()).status);
+        assert((await f.escrowData()).initializerAmount.toNumber() == 2000_000);
+    });
+});
@@ -0,0 +1,114 @@
+const utils = require('../../lib/utils');
+
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const splToken = require('@solana/spl-token');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+// packet size limit of a serialized transaction
+const PACKET_DATA_SIZE = 1232;
+
+describe('Exchange transaction size', () => {
+    const homedir = os.homedir();
+    process.env.ANCHOR_WALLET = `${homedir}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const programId = new anchor.web3.PublicKey(idl.metadata.address);
+    const program = new anchor.Program(idl, programId);
+    const mintAuthority = anchor.web3.Keypair.generate();
+
+    it('fits an exchange using every optional account', async () => {
+        const walletA = anchor.web3.Keypair.generate();
+        const walletB = anchor.web3.Keypair.generate();
+        const walletReferrer = anchor.web3.Keypair.generate();
+        const walletFeeCollector = anchor.web3.Keypair.generate();
+        for(const wallet of [walletA, walletB, walletReferrer, walletFeeCollector]){
+            await utils.createWallet(provider.connection, wallet.publicKey, 1000_000_000);
+        }
+
+        const [mintA, mintB, mintC] = await Promise.all([0, 1, 2].map(() => splToken.Token.createMint(
+            provider.connection, walletA, mintAuthority.publicKey, null, 6, splToken.TOKEN_PROGRAM_ID)));
+
+        const initializerDepositTokenAccount = await mintA.createAccount(walletA.publicKey);
+        await mintA.mintTo(initializerDepositTokenAccount, mintAuthority.publicKey, [mintAuthority], 1000_000);
+        const initializerReceiveTokenAccount = await mintB.createAccount(walletA.publicKey);
+        const initializerFeePayTokenAccount = await mintC.createAccount(walletA.publicKey);
+        await mintC.mintTo(initializerFeePayTokenAccount, mintAuthority.publicKey, [mintAuthority], 1000_000);
+        const takerDepositTokenAccount = await mintB.createAccount(walletB.publicKey);
+        await mintB.mintTo(takerDepositTokenAccount, mintAuthority.publicKey, [mintAuthority], 6000_000);
+        const takerReceiveTokenAccount = await mintA.createAccount(walletB.publicKey);
+        const takerFeePayTokenAccount = await mintC.createAccount(walletB.publicKey);
+        await mintC.mintTo(takerFeePayTokenAccount, mintAuthority.publicKey, [mintAuthority], 1000_000);
+        const referrerTokenAccount = await mintC.createAccount(walletReferrer.publicKey);
+        const feeCollectTokenAccount = await mintC.createAccount(walletFeeCollector.publicKey);
+
+        // every optional account the exchange can take
+        await lib.initializePda(program, mintC.publicKey, walletFeeCollector);
+        await lib.updateReferralShare(program, mintC.publicKey, 1000, walletFeeCollector);
+        await lib.initializeReferrer(program, mintC.publicKey, walletReferrer);
+        await lib.initializeUserStats(program, mintC.publicKey, walletA);
+        await lib.initializeUserStats(program, mintC.publicKey, walletB);
+        await lib.initializeStakeVault(program, mintC.publicKey, 60, [{ minStake: 100, discountBps: 1000 }], walletFeeCollector);
+        await lib.stake(program, mintC.publicKey, 100, takerFeePayTokenAccount, walletB);
+        const priceFeed = await lib.initializePriceAccount(program, mintC.publicKey, -6, walletFeeCollector);
+        await lib.updatePrice(program, priceFeed, 1000_000, 0, walletFeeCollector);
+        await lib.updateUsdFees(program, mintC.publicKey, {
+            priceFeed,
+            makerUsd: 10_000,
+            takerUsd: 20_000,
+            maxStaleness: 60,
+            maxConfBps: 100,
+            fallbackMakerFee: 10_000,
+            fallbackTakerFee: 20_000,
+        }, walletFeeCollector);
+        await lib.initializePriceHistory(program, mintA.publicKey, mintB.publicKey, walletA);
+
+        await lib.initialize(
+            program,
+            1000_000,
+            6000_000,
+            mintA.publicKey,
+            initializerDepositTokenAccount,
+            mintB.publicKey,
+            initializerReceiveTokenAccount,
+            mintC.publicKey,
+            feeCollectTokenAccount,
+            10_000,
+            20_000,
+            initializerFeePayTokenAccount,
+            walletA,
+        );
+
+        const args = await lib.exchangeArgs(
+            program,
+            walletA.publicKey,
+            mintA.publicKey,
+            mintB.publicKey,
+            takerDepositTokenAccount,
+            takerReceiveTokenAccount,
+            takerFeePayTokenAccount,
+            mintC.publicKey,
+            walletB,
+            { owner: walletReferrer.publicKey, tokenAccount: referrerTokenAccount },
+            { maxTakerFee: 20_000 },
+        );
+        assert(Object.values(args[4]).every((set) => set));
+
+        const tx = program.transaction.exchange(...args);
+        tx.feePayer = walletB.publicKey;
+        tx.recentBlockhash = (await provider.connection.getRecentBlockhash()).blockhash;
+        tx.sign(walletB);
+        const size = tx.serialize().length;
+        assert(size <= PACKET_DATA_SIZE, `exchange transaction is ${size} bytes`);
+
+        await anchor.web3.sendAndConfirmRawTransaction(provider.connection, tx.serialize());
+        assert(await utils.getTokenAccountBalance(provider.connection, takerReceiveTokenAccount) == 1000_000);
+        assert(await utils.getTokenAccountBalance(provider.connection, referrerTokenAccount) > 0);
+    });
+});
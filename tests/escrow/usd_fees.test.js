@@ -0,0 +1,102 @@
+const assert = require('assert');
+const anchor = require('@project-serum/anchor');
+const process = require('process');
+const os = require('os');
+const fs = require('fs');
+const lib = require('../lib');
+
+function sleep(ms){
+    return new Promise((resolve) => {
+        setTimeout(resolve, ms);
+    });
+}
+
+describe('USD fees', () => {
+    process.env.ANCHOR_WALLET = `${os.homedir()}/.config/solana/id.json`;
+
+    const provider = anchor.Provider.local();
+    anchor.setProvider(provider);
+
+    const idl = JSON.parse(fs.readFileSync('./target/idl/scrt_escrow.json', 'utf8'));
+    const program = new anchor.Program(idl, new anchor.web3.PublicKey(idl.metadata.address));
+
+    let f;
+    let feed;
+
+    const usdFees = (overrides = {}) => lib.updateUsdFees(program, f.mintC.publicKey, {
+        priceFeed: feed,
+        makerUsd: 10_000,
+        takerUsd: 20_000,
+        maxStaleness: 60,
+        maxConfBps: 100,
+        fallbackMakerFee: 8_000,
+        fallbackTakerFee: 16_000,
+        ...overrides,
+    }, f.feeAdmin);
+
+    // mint C at $2, fees of 1 and 2 cents
+    beforeEach(async () => {
+        f = await lib.createFixture(program);
+        feed = await lib.initializePriceAccount(program, f.mintC.publicKey, -6, f.feeAdmin);
+        await lib.updatePrice(program, feed, 2000_000, 0, f.feeAdmin);
+        await usdFees();
+    });
+
+    it('prices both fees off the feed', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        assert(await f.balance(f.makerFeePay) == 10_000_000 - 5_000);
+        await f.take();
+
+        assert(await f.balance(f.takerFeePay) == 10_000_000 - 10_000);
+        assert(await f.balance(f.feeCollect) == 15_000);
+    });
+
+    it('prices the taker fee at fill', async () => {
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        await lib.updatePrice(program, feed, 1000_000, 0, f.feeAdmin);
+        await f.take();
+
+        assert(await f.balance(f.takerFeePay) == 10_000_000 - 20_000);
+        assert(await f.balance(f.feeCollect) == 25_000);
+    });
+
+    it('falls back to the flat fees on a wide confidence interval', async () => {
+        await lib.updatePrice(program, feed, 2000_000, 20_001, f.feeAdmin);
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        await f.take();
+
+        assert(await f.balance(f.makerFeePay) == 10_000_000 - 8_000);
+        assert(await f.balance(f.takerFeePay) == 10_000_000 - 16_000);
+    });
+
+    it('falls back to the flat fees on a stale price', async () => {
+        await usdFees({ maxStaleness: 1 });
+        await sleep(3000);
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        await f.take();
+
+        assert(await f.balance(f.makerFeePay) == 10_000_000 - 8_000);
+        assert(await f.balance(f.takerFeePay) == 10_000_000 - 16_000);
+    });
+
+    it('holds the maker to their fee cap', async () => {
+        await lib.assertProgramError(program, f.make(1000_000, 6000_000, 4_999, 20_000), 'InitializerFeeExceedsMax');
+    });
+
+    it('rejects a feed that isn\'t a price account', async () => {
+        await usdFees({ priceFeed: f.makerDeposit });
+        await lib.assertProgramError(program, f.make(1000_000, 6000_000, 10_000, 20_000), 'InvalidPriceFeed');
+    });
+
+    it('rejects a config without a staleness window', async () => {
+        await lib.assertProgramError(program, usdFees({ maxStaleness: 0 }), 'InvalidUsdFees');
+    });
+
+    it('leaves the flat fees alone once turned off', async () => {
+        await usdFees({ priceFeed: new anchor.web3.PublicKey(0), maxStaleness: 0 });
+        await f.make(1000_000, 6000_000, 10_000, 20_000);
+        await f.take();
+
+        assert(await f.balance(f.feeCollect) == 30_000);
+    });
+});
@@ -1,8 +1,304 @@
 const anchor = require('@project-serum/anchor');
-const { TOKEN_PROGRAM_ID } = require('@solana/spl-token');
+const assert = require('assert');
+const splToken = require('@solana/spl-token');
+const { TOKEN_PROGRAM_ID, ASSOCIATED_TOKEN_PROGRAM_ID } = require('@solana/spl-token');
 const utils = require('../lib/utils');
 
 const PDA_SEED  = "ser-escrow";
+const OPEN_ORDERS_SEED = "open-orders";
+const OPEN_ORDERS_CAPACITY = 32;
+const MARKET_SEED = "market";
+const PROGRAM_CONFIG_SEED = "program-config";
+const BPF_LOADER_UPGRADEABLE_ID = new anchor.web3.PublicKey("BPFLoaderUpgradeab1e11111111111111111111111");
+const ESCROW_PDA_SEED = "escrow";
+const CLAIM_SEED = "claim";
+const REFERRER_SEED = "referrer";
+const USER_STATS_SEED = "user-stats";
+const STAKE_SEED = "stake";
+const STAKE_VAULT_SEED = "stake-vault";
+const FEE_REVENUE_SEED = "fee-revenue";
+const FEE_VAULT_SEED = "fee-vault";
+const PRICE_SEED = "price";
+const PRICE_HISTORY_SEED = "price-history";
+const PRICE_HISTORY_CAPACITY = 64;
+
+async function getClaimAddress(program, owner, mint) {
+    const [claim] = await anchor.web3.PublicKey.findProgramAddress(
+        [owner.toBuffer(), mint.toBuffer(), Buffer.from(CLAIM_SEED)], program.programId);
+    return claim;
+}
+
+async function getReferrerStatsAddress(program, owner, feeToken) {
+    const [stats] = await anchor.web3.PublicKey.findProgramAddress(
+        [owner.toBuffer(), feeToken.toBuffer(), Buffer.from(REFERRER_SEED)], program.programId);
+    return stats;
+}
+
+async function getUserStatsAddress(program, owner, feeToken) {
+    const [stats] = await anchor.web3.PublicKey.findProgramAddress(
+        [owner.toBuffer(), feeToken.toBuffer(), Buffer.from(USER_STATS_SEED)], program.programId);
+    return stats;
+}
+
+async function getStakeAddress(program, owner, feeToken) {
+    const [stake] = await anchor.web3.PublicKey.findProgramAddress(
+        [owner.toBuffer(), feeToken.toBuffer(), Buffer.from(STAKE_SEED)], program.programId);
+    return stake;
+}
+
+async function getPriceAccountAddress(program, authority, product) {
+    const [priceAccount] = await anchor.web3.PublicKey.findProgramAddress(
+        [authority.toBuffer(), product.toBuffer(), Buffer.from(PRICE_SEED)], program.programId);
+    return priceAccount;
+}
+
+// the fee config's price feed, any account stands in while USD fees are off
+function priceFeed(pdaData, pdaAccount) {
+    const feed = pdaData.usdFees.priceFeed;
+    return feed.equals(new anchor.web3.PublicKey(0)) ? pdaAccount : feed;
+}
+
+// vault and collector of a fee mint, the fee token's collector is the
+// escrow's own until fees are distributed from revenue vaults
+function feeVault(pdaData, mint) {
+    if(mint.equals(pdaData.feeToken)){
+        return pdaData.vaultFeeAccount;
+    }
+    return pdaData.acceptedFeeMints.find((feeMint) => feeMint.mint.equals(mint)).vault;
+}
+
+async function feeCollector(pdaData, mint, escrowCollector) {
+    if(!pdaData.feeRevenueVault.equals(new anchor.web3.PublicKey(0))){
+        return revenueVault(pdaData, mint);
+    }
+    if(mint.equals(pdaData.feeToken)){
+        return escrowCollector;
+    }
+    return pdaData.acceptedFeeMints.find((feeMint) => feeMint.mint.equals(mint)).collector;
+}
+
+// where distributable fees in a mint collect
+async function revenueVault(pdaData, mint) {
+    if(mint.equals(pdaData.feeToken)){
+        return pdaData.feeRevenueVault;
+    }
+    return utils.getAssociatedTokenAddress(mint, pdaData.vaultFeeAuthority, true);
+}
+
+async function getMarketAddress(program, mintA, mintB) {
+    const [lo, hi] = Buffer.compare(mintA.toBuffer(), mintB.toBuffer()) < 0 ? [mintA, mintB] : [mintB, mintA];
+    const [market] = await anchor.web3.PublicKey.findProgramAddress(
+        [lo.toBuffer(), hi.toBuffer(), Buffer.from(MARKET_SEED)], program.programId);
+    return market;
+}
+
+async function getPriceHistoryAddress(program, mintA, mintB) {
+    const [lo, hi] = Buffer.compare(mintA.toBuffer(), mintB.toBuffer()) < 0 ? [mintA, mintB] : [mintB, mintA];
+    const [priceHistory] = await anchor.web3.PublicKey.findProgramAddress(
+        [lo.toBuffer(), hi.toBuffer(), Buffer.from(PRICE_HISTORY_SEED)], program.programId);
+    return priceHistory;
+}
+
+async function getPriceHistory(program, mintA, mintB) {
+    try{
+        return await program.account.priceHistory.fetch(await getPriceHistoryAddress(program, mintA, mintB));
+    }catch(e){
+        return null;
+    }
+}
+
+// time weighted average price of a fetched history over the last window
+// seconds before now, as PriceHistory::twap computes it on chain
+function twap(history, now, window) {
+    const fills = history.fills.toNumber();
+    if(fills == 0){
+        return null;
+    }
+    const start = now - window;
+    let from = null;
+    for(let age = 0; age < Math.min(fills, PRICE_HISTORY_CAPACITY); age++){
+        from = history.observations[(fills - 1 - age) % PRICE_HISTORY_CAPACITY];
+        if(from.timestamp.toNumber() <= start){
+            break;
+        }
+    }
+    let fromTime = from.timestamp.toNumber();
+    let fromCumulative = from.cumulativePrice;
+    if(fromTime < start){
+        // the price after that fill stood until start
+        fromCumulative = fromCumulative.add(from.price.muln(start - fromTime));
+        fromTime = start;
+    }
+    if(now <= fromTime){
+        return history.lastPrice;
+    }
+    const elapsed = Math.max(now - history.lastUpdate.toNumber(), 0);
+    const cumulative = history.cumulativePrice.add(history.lastPrice.muln(elapsed));
+    return cumulative.sub(fromCumulative).divn(now - fromTime);
+}
+
+async function initializePriceHistory(
+    program,
+    baseMint,
+    quoteMint,
+    signer,
+) {
+    const priceHistory = await getPriceHistoryAddress(program, baseMint, quoteMint);
+    await program.rpc.initializePriceHistory({
+        accounts: {
+            payer: signer.publicKey,
+            baseMint,
+            quoteMint,
+            priceHistory,
+            systemProgram: anchor.web3.SystemProgram.programId,
+        },
+        signers: [signer],
+    });
+    return priceHistory;
+}
+
+async function getProgramConfigAddress(program) {
+    const [programConfig] = await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from(PROGRAM_CONFIG_SEED)], program.programId);
+    return programConfig;
+}
+
+// signer has to be the program's upgrade authority
+async function initializeProgramConfig(
+    program,
+    admin,
+    signer,
+) {
+    const programConfig = await getProgramConfigAddress(program);
+    const [programData] = await anchor.web3.PublicKey.findProgramAddress(
+        [program.programId.toBuffer()], BPF_LOADER_UPGRADEABLE_ID);
+    await program.rpc.initializeProgramConfig(
+        admin,
+        {
+            accounts: {
+                authority: signer.publicKey,
+                programData,
+                programConfig,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            },
+            signers: [signer],
+        },
+    );
+    return programConfig;
+}
+
+async function updateProgramConfig(program, admin, signer) {
+    await program.rpc.updateProgramConfig(
+        admin,
+        {
+            accounts: {
+                admin: signer.publicKey,
+                programConfig: await getProgramConfigAddress(program),
+            },
+            signers: [signer],
+        },
+    );
+}
+
+async function initializeMarket(
+    program,
+    baseMint,
+    quoteMint,
+    tickSize,
+    lotSize,
+    minNotional,
+    signer,
+) {
+    const market = await getMarketAddress(program, baseMint, quoteMint);
+    await program.rpc.initializeMarket(
+        new anchor.BN(tickSize),
+        new anchor.BN(lotSize),
+        new anchor.BN(minNotional),
+        {
+            accounts: {
+                admin: signer.publicKey,
+                programConfig: await getProgramConfigAddress(program),
+                baseMint,
+                quoteMint,
+                market,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            },
+            signers: [signer],
+        },
+    );
+    return market;
+}
+
+async function updateMarket(
+    program,
+    baseMint,
+    quoteMint,
+    tickSize,
+    lotSize,
+    minNotional,
+    enabled,
+    signer,
+) {
+    await program.rpc.updateMarket(
+        new anchor.BN(tickSize),
+        new anchor.BN(lotSize),
+        new anchor.BN(minNotional),
+        enabled,
+        {
+            accounts: {
+                admin: signer.publicKey,
+                programConfig: await getProgramConfigAddress(program),
+                market: await getMarketAddress(program, baseMint, quoteMint),
+            },
+            signers: [signer],
+        },
+    );
+}
+
+// the provider wallet deploys the program, so it creates the program config
+// and stays its admin across test files
+async function marketAdmin(program) {
+    const admin = program.provider.wallet.payer;
+    if(await program.provider.connection.getAccountInfo(await getProgramConfigAddress(program)) == null){
+        await initializeProgramConfig(program, admin.publicKey, admin);
+    }
+    return admin;
+}
+
+async function getOpenOrdersAddress(program, owner) {
+    const [openOrders] = await anchor.web3.PublicKey.findProgramAddress(
+        [owner.toBuffer(), Buffer.from(OPEN_ORDERS_SEED)], program.programId);
+    return openOrders;
+}
+
+async function getOpenOrders(program, owner) {
+    const openOrders = await getOpenOrdersAddress(program, owner);
+    try {
+        return await program.account.openOrders.fetch(openOrders);
+    } catch (e) {
+        return null;
+    }
+}
+
+async function initializeOpenOrders(
+    program,
+    capacity,
+    signer,
+) {
+    const openOrders = await getOpenOrdersAddress(program, signer.publicKey);
+    await program.rpc.initializeOpenOrders(
+        capacity,
+        {
+            accounts: {
+                owner: signer.publicKey,
+                openOrders,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            },
+            signers: [signer],
+        },
+    );
+    return openOrders;
+}
 
 async function initializePda(
     program,
@@ -54,6 +350,8 @@ async function initialize(
     feeAmountTaker,
     initFeePayTokenAcc,
     signer,
+    options = {},
+    refundTokenAcc = null,
 ) {
 
     const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
@@ -67,35 +365,87 @@ async function initialize(
         [signer.publicKey.toBuffer(), depositToken.toBuffer(), receiveToken.toBuffer()], program.programId);
     const [vaultAccount] = await anchor.web3.PublicKey.findProgramAddress(
         [escrow.toBuffer()], program.programId);
-    const [vaultFeeAccount] = await anchor.web3.PublicKey.findProgramAddress(
-        [escrow.toBuffer(), feeToken.toBuffer()], program.programId);
+    const [vaultAuthority] = await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from(ESCROW_PDA_SEED), escrow.toBuffer()], program.programId);
     
+    // the index is optional, `openOrders: false` makes the escrow without it
+    const openOrders = await getOpenOrdersAddress(program, signer.publicKey);
+    if(options.openOrders !== false && await getOpenOrders(program, signer.publicKey) == null){
+        await initializeOpenOrders(program, OPEN_ORDERS_CAPACITY, signer);
+    }
+
     const escrowData = await utils.getEscrowAccount(program, escrow);
-    if(escrowData == null)
+    // a settled escrow of the pair stays until its rent is reclaimed
+    const instructions = [];
+    if(escrowData != null && !('open' in escrowData.status)){
+        instructions.push(program.instruction.closeEscrow({
+            accounts: {
+                initializer: signer.publicKey,
+                escrowAccount: escrow,
+            },
+        }));
+    }
+    if(escrowData == null || instructions.length > 0)
     {
         await program.rpc.initialize(
             new anchor.BN(initDepositTokenAmount),
             new anchor.BN(takerAmount),
             new anchor.BN(feeAmountInitializer),
             new anchor.BN(feeAmountTaker),
+            {
+                hook: options.hook || null,
+                canonicalReceive: options.canonicalReceive || false,
+                proceedsSplit: options.proceedsSplit || [],
+                deductFees: options.deductFees || false,
+                conditions: options.conditions || [],
+                // referrer is {owner, tokenAccount}
+                referrer: options.referrer ? options.referrer.tokenAccount : null,
+            },
             {
                 accounts: {
                     initializer: signer.publicKey,
                     feeToken,
                     pdaAccount,
                     escrowAccount: escrow,
+                    openOrders,
+                    market: await getMarketAddress(program, depositToken, receiveToken),
+                    makerStats: await getUserStatsAddress(program, signer.publicKey, feeToken),
+                    makerStake: await getStakeAddress(program, signer.publicKey, feeToken),
+                    priceFeed: priceFeed(pdaData, pdaAccount),
+                    vaultAuthority,
                     vaultAccount: vaultAccount,
                     depositToken: depositToken,
                     initializerDepositTokenAccount: initDepositTokenAcc,
                     receiveToken: receiveToken,
                     initializerReceiveTokenAccount: initReceiveTokenAcc,                    
-                    vaultFeeAccount: pdaData.vaultFeeAccount,
+                    refundTokenAccount: refundTokenAcc == null ? initDepositTokenAcc : refundTokenAcc,
                     feeCollectTokenAccount,
-                    initializerFeePayingTokenAccount: initFeePayTokenAcc,
                     tokenProgram: TOKEN_PROGRAM_ID,
                     systemProgram: anchor.web3.SystemProgram.programId,
                     rent: anchor.web3.SYSVAR_RENT_PUBKEY,
                 },
+                // the fee paying account and its mint's fee vault lead unless
+                // fees are deducted, split recipients are checked against the
+                // receive mint, the maker's referrer follows them
+                remainingAccounts: [
+                    ...(options.deductFees
+                        ? []
+                        : [
+                            { pubkey: initFeePayTokenAcc, isSigner: false, isWritable: true },
+                            {
+                                pubkey: feeVault(pdaData, await utils.getTokenAccountMint(program.provider.connection, initFeePayTokenAcc)),
+                                isSigner: false,
+                                isWritable: true,
+                            },
+                        ]),
+                    ...[
+                        ...(options.proceedsSplit || []).map((share) => share.recipient),
+                        ...(options.referrer
+                            ? [options.referrer.tokenAccount, await getReferrerStatsAddress(program, options.referrer.owner, feeToken)]
+                            : []),
+                    ].map((pubkey) => ({ pubkey, isSigner: false, isWritable: false })),
+                ],
+                instructions,
                 signers: [signer],
             },
         );
@@ -111,6 +461,8 @@ async function cancel(
     receiveToken,
     feeToken,
     signer,
+    // anyone may cancel someone else's escrow once it has expired
+    initializer = signer.publicKey,
 ) {
 
     const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
@@ -121,7 +473,7 @@ async function cancel(
     }
 
     const [escrow] = await anchor.web3.PublicKey.findProgramAddress(
-        [signer.publicKey.toBuffer(), depositToken.toBuffer(), receiveToken.toBuffer()], program.programId);
+        [initializer.toBuffer(), depositToken.toBuffer(), receiveToken.toBuffer()], program.programId);
 
     const escrowData = await utils.getEscrowAccount(program, escrow);
     if(escrowData == null)
@@ -132,23 +484,118 @@ async function cancel(
     await program.rpc.cancel(
         {
             accounts: {
-                initializer: signer.publicKey,
+                initializer,
                 pdaAccount,
                 escrowAccount: escrow,
+                openOrders: await getOpenOrdersAddress(program, initializer),
+                market: await getMarketAddress(program, depositToken, receiveToken),
                 vaultAccount: escrowData.vaultAccount,
                 vaultAuthority: escrowData.vaultAuthority,          
-                vaultFeeAccount: pdaData.vaultFeeAccount,
+                vaultFeeAccount: feeVault(pdaData, escrowData.initializerFeeMint),
                 vaultFeeAuthority: pdaData.vaultFeeAuthority,
-                initializerDepositTokenAccount: escrowData.initializerDepositTokenAccount,
+                refundTokenAccount: escrowData.refundTokenAccount,
                 initializerFeePayingTokenAccount: escrowData.initializerFeePayingTokenAccount,
                 tokenProgram: TOKEN_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            },
+            // a third party only pays for the transaction
+            signers: signer.publicKey.equals(initializer) ? [signer] : [],
+        },
+    );
+}
+
+async function closeEscrow(
+    program,
+    depositToken,
+    receiveToken,
+    signer,
+) {
+    const [escrow] = await anchor.web3.PublicKey.findProgramAddress(
+        [signer.publicKey.toBuffer(), depositToken.toBuffer(), receiveToken.toBuffer()], program.programId);
+
+    await program.rpc.closeEscrow(
+        {
+            accounts: {
+                initializer: signer.publicKey,
+                escrowAccount: escrow,
             },
             signers: [signer],
         },
     );
 }
 
-async function exchange(
+function hookAccounts(hook) {
+    if(hook == null){
+        return [];
+    }
+    return [
+        { pubkey: hook.program, isSigner: false, isWritable: false },
+        ...hook.accounts.map((pubkey) => ({ pubkey, isSigner: false, isWritable: true })),
+    ];
+}
+
+// price oracles and approvers, in condition order
+function conditionAccounts(conditions) {
+    return conditions
+        .filter((condition) => condition.price || condition.approval)
+        .map((condition) => condition.price
+            ? { pubkey: condition.price.trigger.oracle, isSigner: false, isWritable: false }
+            : { pubkey: condition.approval.approver, isSigner: true, isWritable: false });
+}
+
+// optional exchange accounts, passed only when they exist, in ExchangeExtras order
+async function exchangeExtras(program, pdaData, escrowData, taker, takerFeePayAcc, feeToken, referrer, receipts = {}) {
+    const connection = program.provider.connection;
+    const initializer = escrowData.initializerKey;
+    const existing = async (pubkey) => (await connection.getAccountInfo(pubkey)) != null ? pubkey : null;
+    // the stats of the maker's referrer go by the owner of their token account
+    const makerReferrer = async (tokenAccount) => {
+        const info = await connection.getParsedAccountInfo(tokenAccount);
+        const owner = info.value
+            ? new anchor.web3.PublicKey(info.value.data.parsed.info.owner)
+            : new anchor.web3.PublicKey(0);
+        return [tokenAccount, await getReferrerStatsAddress(program, owner, feeToken)];
+    };
+    const stats = async (owner) => pdaData.feeTiers.length > 0
+        ? getUserStatsAddress(program, owner, feeToken)
+        : existing(await getUserStatsAddress(program, owner, feeToken));
+    const accounts = {
+        // deducted fees come out of the traded amounts
+        takerFeePaying: escrowData.feeDeducted ? null : takerFeePayAcc,
+        referrer: referrer
+            ? [referrer.tokenAccount, await getReferrerStatsAddress(program, referrer.owner, feeToken)]
+            : null,
+        makerReferrer: escrowData.referrer.equals(new anchor.web3.PublicKey(0))
+            ? null
+            : await makerReferrer(escrowData.referrer),
+        // with fee tiers on both stats addresses go in, opened or not
+        makerStats: await stats(initializer),
+        takerStats: await stats(taker),
+        takerStake: await existing(await getStakeAddress(program, taker, feeToken)),
+        priceFeed: pdaData.usdFees.priceFeed.equals(new anchor.web3.PublicKey(0)) ? null : pdaData.usdFees.priceFeed,
+        // receipts are fresh keypairs, {taker, maker}
+        takerReceipt: receipts.taker ? receipts.taker.publicKey : null,
+        makerReceipt: receipts.maker ? receipts.maker.publicKey : null,
+        // the maker rebate is paid out of it
+        feeRevenueVault: escrowData.makerRebate.toNumber() > 0 && !pdaData.feeRevenueVault.equals(new anchor.web3.PublicKey(0))
+            ? pdaData.feeRevenueVault
+            : null,
+    };
+    const extras = {};
+    const remainingAccounts = [];
+    Object.entries(accounts).forEach(([name, value]) => {
+        extras[name] = value != null;
+        [].concat(value || []).forEach((pubkey) => remainingAccounts.push({
+            pubkey,
+            isSigner: name == 'takerReceipt' || name == 'makerReceipt',
+            isWritable: name != 'takerStake' && name != 'priceFeed',
+        }));
+    });
+    return { extras, remainingAccounts };
+}
+
+// arguments of an exchange instruction, for program.rpc/transaction/instruction.exchange
+async function exchangeArgs(
     program,
     initializer,
     depositToken,
@@ -158,6 +605,8 @@ async function exchange(
     takerFeePayAcc,
     feeToken,
     signer,
+    referrer = null,
+    witness = {},
 ) {
 
     const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
@@ -175,39 +624,1132 @@ async function exchange(
     {
         return 'no exist escrow';
     }
-    if(escrowData.initialized == 0)
+    if(!('open' in escrowData.status))
     {
-        return 'escrow is not initailized';
+        return 'escrow is not open';
     }
 
-    await program.rpc.exchange(
+    // referrer is {owner, tokenAccount}
+    const receipts = witness.receipts || {};
+    const { extras, remainingAccounts } = await exchangeExtras(
+        program, pdaData, escrowData, signer.publicKey, takerFeePayAcc, feeToken, referrer, receipts);
+    // deducted fees go to the collector's associated accounts for the
+    // traded mints, or to their revenue vaults once fees are distributed
+    const distributes = !pdaData.feeRevenueVault.equals(new anchor.web3.PublicKey(0));
+    const deductedCollector = (mint) => distributes
+        ? revenueVault(pdaData, mint)
+        : utils.getAssociatedTokenAddress(mint, pdaData.deductedFeeCollector, true);
+    const initializerFeeCollector = escrowData.feeDeducted
+        ? await deductedCollector(depositToken)
+        : await feeCollector(pdaData, escrowData.initializerFeeMint, escrowData.feeCollectTokenAccount);
+    const takerFeeCollector = escrowData.feeDeducted
+        ? await deductedCollector(receiveToken)
+        : await feeCollector(
+            pdaData,
+            await utils.getTokenAccountMint(program.provider.connection, takerFeePayAcc),
+            escrowData.feeCollectTokenAccount,
+        );
+
+    return [
+        new anchor.BN(escrowData.takerAmount),
+        new anchor.BN(escrowData.initializerAmount),
+        new anchor.BN(witness.maxTakerFee != null ? witness.maxTakerFee : escrowData.feeAmountTaker),
+        (witness.preimages || []).map((preimage) => Buffer.from(preimage)),
+        extras,
         {
             accounts: {
                 taker: signer.publicKey,
                 pdaAccount,
                 initializer,
                 escrowAccount: escrow,
+                openOrders: await getOpenOrdersAddress(program, initializer),
+                market: await getMarketAddress(program, depositToken, receiveToken),
+                priceHistory: await getPriceHistoryAddress(program, depositToken, receiveToken),
                 takerDepositTokenAccount: takerDepositToken,
                 takerReceiveTokenAccount: takerReceiveToken,
+                depositToken,
+                initializerReceiveTokenAccount: escrowData.initializerReceiveTokenAccount,
+                receiveToken,
+                claimAccount: await getClaimAddress(program, initializer, receiveToken),
+                vaultAccount: escrowData.vaultAccount,
+                vaultAuthority: escrowData.vaultAuthority,
+                vaultFeeAccount: feeVault(pdaData, escrowData.initializerFeeMint),
+                vaultFeeAuthority: pdaData.vaultFeeAuthority,
+                initializerFeeCollectTokenAccount: initializerFeeCollector,
+                takerFeeCollectTokenAccount: takerFeeCollector,
+                initializerFeePayingTokenAccount: escrowData.initializerFeePayingTokenAccount,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+                rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+                associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+            },
+            remainingAccounts: [
+                ...remainingAccounts,
+                ...escrowData.proceedsSplit.map((share) => ({ pubkey: share.recipient, isSigner: false, isWritable: true })),
+                ...(await Promise.all(escrowData.proceedsOwners.map(async (owner) => (
+                    { pubkey: await getClaimAddress(program, owner, receiveToken), isSigner: false, isWritable: true }
+                )))),
+                ...conditionAccounts(escrowData.conditions),
+                ...hookAccounts(escrowData.hook),
+            ],
+            signers: [signer, ...(witness.approvers || []), ...Object.values(receipts)],
+        },
+    ];
+}
+
+async function exchange(program, ...params) {
+    const args = await exchangeArgs(program, ...params);
+    if(typeof args == 'string'){
+        return args;
+    }
+    await program.rpc.exchange(...args);
+    return "ok";
+}
+
+async function flashExchange(
+    program,
+    initializer,
+    depositToken,
+    receiveToken,
+    takerDepositToken,
+    takerReceiveToken,
+    takerFeePayAcc,
+    feeToken,
+    callbackProgram,
+    callbackData,
+    callbackAccounts,
+    signer,
+) {
+    const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
+        [program.programId.toBuffer(), feeToken.toBuffer(), Buffer.from(PDA_SEED)], program.programId);
+    const pdaData = await utils.getPdaAccount(program, pdaAccount);
+    if(pdaData == null){
+        return 'pda is not initialized';
+    }
+
+    const [escrow] = await anchor.web3.PublicKey.findProgramAddress(
+        [initializer.toBuffer(), depositToken.toBuffer(), receiveToken.toBuffer()], program.programId);
+
+    const escrowData = await utils.getEscrowAccount(program, escrow);
+    if(escrowData == null)
+    {
+        return 'no exist escrow';
+    }
+    if(!('open' in escrowData.status))
+    {
+        return 'escrow is not open';
+    }
+
+    await program.rpc.flashExchange(
+        new anchor.BN(escrowData.takerAmount),
+        new anchor.BN(escrowData.initializerAmount),
+        new anchor.BN(escrowData.feeAmountTaker),
+        callbackData,
+        {
+            accounts: {
+                taker: signer.publicKey,
+                pdaAccount,
+                initializer,
+                escrowAccount: escrow,
+                openOrders: await getOpenOrdersAddress(program, initializer),
+                market: await getMarketAddress(program, depositToken, receiveToken),
+                priceHistory: await getPriceHistoryAddress(program, depositToken, receiveToken),
+                takerReceiveTokenAccount: takerReceiveToken,
+                takerDepositTokenAccount: takerDepositToken,
                 initializerReceiveTokenAccount: escrowData.initializerReceiveTokenAccount,
+                receiveToken,
+                claimAccount: await getClaimAddress(program, initializer, receiveToken),
                 vaultAccount: escrowData.vaultAccount,
                 vaultAuthority: escrowData.vaultAuthority,
                 vaultFeeAccount: pdaData.vaultFeeAccount,
                 vaultFeeAuthority: pdaData.vaultFeeAuthority,
-                feeCollectTokenAccount: escrowData.feeCollectTokenAccount,
-                takerFeePayingTokenAccount: takerFeePayAcc,                
+                feeCollectTokenAccount: await feeCollector(pdaData, feeToken, escrowData.feeCollectTokenAccount),
+                takerFeePayingTokenAccount: takerFeePayAcc,
+                takerStake: await getStakeAddress(program, signer.publicKey, feeToken),
+                priceFeed: priceFeed(pdaData, pdaAccount),
+                callbackProgram,
                 tokenProgram: TOKEN_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+                rent: anchor.web3.SYSVAR_RENT_PUBKEY,
             },
+            remainingAccounts: callbackAccounts,
             signers: [signer],
         },
     );
     return "ok";
 }
 
+async function claim(
+    program,
+    mint,
+    destinationTokenAccount,
+    signer,
+) {
+    await program.rpc.claim({
+        accounts: {
+            owner: signer.publicKey,
+            mint,
+            claimAccount: await getClaimAddress(program, signer.publicKey, mint),
+            destinationTokenAccount,
+            tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        signers: [signer],
+    });
+}
+
+async function initializeReferrer(
+    program,
+    feeToken,
+    signer,
+) {
+    await program.rpc.initializeReferrer({
+        accounts: {
+            owner: signer.publicKey,
+            feeToken,
+            referrerStats: await getReferrerStatsAddress(program, signer.publicKey, feeToken),
+            systemProgram: anchor.web3.SystemProgram.programId,
+        },
+        signers: [signer],
+    });
+}
+
+async function updateReferralShare(
+    program,
+    feeToken,
+    referralShareBps,
+    signer,
+) {
+    const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
+        [program.programId.toBuffer(), feeToken.toBuffer(), Buffer.from(PDA_SEED)], program.programId);
+    await program.rpc.updateReferralShare(referralShareBps, {
+        accounts: {
+            admin: signer.publicKey,
+            pdaAccount,
+        },
+        signers: [signer],
+    });
+}
+
+async function updateFeeTiers(
+    program,
+    feeToken,
+    feeTiers,
+    signer,
+) {
+    const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
+        [program.programId.toBuffer(), feeToken.toBuffer(), Buffer.from(PDA_SEED)], program.programId);
+    await program.rpc.updateFeeTiers(
+        feeTiers.map((tier) => ({
+            minVolume: new anchor.BN(tier.minVolume),
+            makerBps: tier.makerBps,
+            takerBps: tier.takerBps,
+        })),
+        {
+            accounts: {
+                admin: signer.publicKey,
+                pdaAccount,
+            },
+            signers: [signer],
+        },
+    );
+}
+
+async function initializeUserStats(
+    program,
+    feeToken,
+    signer,
+) {
+    await program.rpc.initializeUserStats({
+        accounts: {
+            owner: signer.publicKey,
+            feeToken,
+            userStats: await getUserStatsAddress(program, signer.publicKey, feeToken),
+            systemProgram: anchor.web3.SystemProgram.programId,
+        },
+        signers: [signer],
+    });
+}
+
+async function initializeStakeVault(
+    program,
+    feeToken,
+    unbondingPeriod,
+    stakeDiscounts,
+    signer,
+) {
+    const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
+        [program.programId.toBuffer(), feeToken.toBuffer(), Buffer.from(PDA_SEED)], program.programId);
+    const pdaData = await utils.getPdaAccount(program, pdaAccount);
+    const [stakeVault] = await anchor.web3.PublicKey.findProgramAddress(
+        [pdaAccount.toBuffer(), Buffer.from(STAKE_VAULT_SEED)], program.programId);
+    await program.rpc.initializeStakeVault(
+        new anchor.BN(unbondingPeriod),
+        stakeDiscounts.map((step) => ({
+            minStake: new anchor.BN(step.minStake),
+            discountBps: step.discountBps,
+        })),
+        {
+            accounts: {
+                admin: signer.publicKey,
+                pdaAccount,
+                feeToken,
+                vaultFeeAuthority: pdaData.vaultFeeAuthority,
+                stakeVault,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+                rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+            },
+            signers: [signer],
+        },
+    );
+    return stakeVault;
+}
+
+async function stake(
+    program,
+    feeToken,
+    amount,
+    sourceTokenAccount,
+    signer,
+) {
+    const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
+        [program.programId.toBuffer(), feeToken.toBuffer(), Buffer.from(PDA_SEED)], program.programId);
+    const pdaData = await utils.getPdaAccount(program, pdaAccount);
+    const stakeAccount = await getStakeAddress(program, signer.publicKey, feeToken);
+    if(await program.provider.connection.getAccountInfo(stakeAccount) == null){
+        await program.rpc.initializeStakeAccount({
+            accounts: {
+                owner: signer.publicKey,
+                feeToken,
+                stakeAccount,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            },
+            signers: [signer],
+        });
+    }
+    await program.rpc.stake(new anchor.BN(amount), {
+        accounts: {
+            owner: signer.publicKey,
+            pdaAccount,
+            stakeAccount,
+            stakeVault: pdaData.stakeVault,
+            sourceTokenAccount,
+            tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        signers: [signer],
+    });
+}
+
+async function unstake(
+    program,
+    feeToken,
+    amount,
+    signer,
+) {
+    const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
+        [program.programId.toBuffer(), feeToken.toBuffer(), Buffer.from(PDA_SEED)], program.programId);
+    await program.rpc.unstake(new anchor.BN(amount), {
+        accounts: {
+            owner: signer.publicKey,
+            pdaAccount,
+            stakeAccount: await getStakeAddress(program, signer.publicKey, feeToken),
+        },
+        signers: [signer],
+    });
+}
+
+async function withdrawStake(
+    program,
+    feeToken,
+    destinationTokenAccount,
+    signer,
+) {
+    const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
+        [program.programId.toBuffer(), feeToken.toBuffer(), Buffer.from(PDA_SEED)], program.programId);
+    const pdaData = await utils.getPdaAccount(program, pdaAccount);
+    await program.rpc.withdrawStake({
+        accounts: {
+            owner: signer.publicKey,
+            pdaAccount,
+            stakeAccount: await getStakeAddress(program, signer.publicKey, feeToken),
+            stakeVault: pdaData.stakeVault,
+            vaultFeeAuthority: pdaData.vaultFeeAuthority,
+            destinationTokenAccount,
+            tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        signers: [signer],
+    });
+}
+
+async function initializeFeeDistribution(
+    program,
+    feeToken,
+    feeDistribution,
+    signer,
+) {
+    const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
+        [program.programId.toBuffer(), feeToken.toBuffer(), Buffer.from(PDA_SEED)], program.programId);
+    const pdaData = await utils.getPdaAccount(program, pdaAccount);
+    const [feeRevenueVault] = await anchor.web3.PublicKey.findProgramAddress(
+        [pdaAccount.toBuffer(), Buffer.from(FEE_REVENUE_SEED)], program.programId);
+    await program.rpc.initializeFeeDistribution(feeDistribution, {
+        accounts: {
+            admin: signer.publicKey,
+            pdaAccount,
+            feeToken,
+            vaultFeeAuthority: pdaData.vaultFeeAuthority,
+            feeRevenueVault,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: anchor.web3.SystemProgram.programId,
+            rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        },
+        signers: [signer],
+    });
+    return feeRevenueVault;
+}
+
+async function updateFeeDistribution(
+    program,
+    feeToken,
+    feeDistribution,
+    signer,
+) {
+    const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
+        [program.programId.toBuffer(), feeToken.toBuffer(), Buffer.from(PDA_SEED)], program.programId);
+    await program.rpc.updateFeeDistribution(feeDistribution, {
+        accounts: {
+            admin: signer.publicKey,
+            pdaAccount,
+        },
+        signers: [signer],
+    });
+}
+
+// opens the revenue vault of another mint than the fee token, the fee
+// authority's associated account for it; the provider pays for it
+async function openRevenueVault(program, feeToken, mint) {
+    const pdaData = await utils.getPdaAccountData(program, feeToken);
+    const [vault, instructions] = await utils.createAssociatedTokenAccount(
+        program.provider.connection, mint, program.provider.wallet, pdaData.vaultFeeAuthority, true);
+    if(instructions.length > 0){
+        await program.provider.send(new anchor.web3.Transaction().add(...instructions));
+    }
+    return vault;
+}
+
+// pays out the revenue vault of `mint`, in another mint than the fee token
+// the recipients' owners are paid in their associated accounts
+async function distributeFees(
+    program,
+    feeToken,
+    mint = feeToken,
+) {
+    const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
+        [program.programId.toBuffer(), feeToken.toBuffer(), Buffer.from(PDA_SEED)], program.programId);
+    const pdaData = await utils.getPdaAccount(program, pdaAccount);
+    const recipients = pdaData.feeDistribution.map((share) => share.recipient);
+    const destinations = mint.equals(feeToken) ? [] : await Promise.all(recipients.map(async (recipient) => {
+        const info = await program.provider.connection.getParsedAccountInfo(recipient);
+        const owner = new anchor.web3.PublicKey(info.value.data.parsed.info.owner);
+        return utils.getAssociatedTokenAddress(mint, owner, true);
+    }));
+    await program.rpc.distributeFees({
+        accounts: {
+            pdaAccount,
+            feeRevenueVault: await revenueVault(pdaData, mint),
+            vaultFeeAuthority: pdaData.vaultFeeAuthority,
+            tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        remainingAccounts: [...recipients, ...destinations].map((pubkey) => ({ pubkey, isSigner: false, isWritable: true })),
+    });
+}
+
+async function addFeeMint(
+    program,
+    feeToken,
+    mint,
+    collector,
+    flatFee,
+    feeBps,
+    signer,
+) {
+    const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
+        [program.programId.toBuffer(), feeToken.toBuffer(), Buffer.from(PDA_SEED)], program.programId);
+    const pdaData = await utils.getPdaAccount(program, pdaAccount);
+    const [feeVault] = await anchor.web3.PublicKey.findProgramAddress(
+        [pdaAccount.toBuffer(), mint.toBuffer(), Buffer.from(FEE_VAULT_SEED)], program.programId);
+    await program.rpc.addFeeMint(new anchor.BN(flatFee), feeBps, {
+        accounts: {
+            admin: signer.publicKey,
+            pdaAccount,
+            mint,
+            vaultFeeAuthority: pdaData.vaultFeeAuthority,
+            feeVault,
+            collector,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: anchor.web3.SystemProgram.programId,
+            rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        },
+        signers: [signer],
+    });
+    return feeVault;
+}
+
+async function updateFeeMint(
+    program,
+    feeToken,
+    mint,
+    flatFee,
+    feeBps,
+    signer,
+) {
+    const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
+        [program.programId.toBuffer(), feeToken.toBuffer(), Buffer.from(PDA_SEED)], program.programId);
+    await program.rpc.updateFeeMint(mint, new anchor.BN(flatFee), feeBps, {
+        accounts: {
+            admin: signer.publicKey,
+            pdaAccount,
+        },
+        signers: [signer],
+    });
+}
+
+// a null collector turns deducted fees off for new escrows
+async function updateDeductedFees(
+    program,
+    feeToken,
+    collector,
+    makerBps,
+    takerBps,
+    signer,
+) {
+    const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
+        [program.programId.toBuffer(), feeToken.toBuffer(), Buffer.from(PDA_SEED)], program.programId);
+    await program.rpc.updateDeductedFees(collector || new anchor.web3.PublicKey(0), makerBps, takerBps, {
+        accounts: {
+            admin: signer.publicKey,
+            pdaAccount,
+        },
+        signers: [signer],
+    });
+}
+
+// usdFees is {priceFeed, makerUsd, takerUsd, maxStaleness, maxConfBps,
+// fallbackMakerFee, fallbackTakerFee}, USD amounts in millionths
+async function updateUsdFees(
+    program,
+    feeToken,
+    usdFees,
+    signer,
+) {
+    const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
+        [program.programId.toBuffer(), feeToken.toBuffer(), Buffer.from(PDA_SEED)], program.programId);
+    await program.rpc.updateUsdFees(
+        {
+            priceFeed: usdFees.priceFeed,
+            makerUsd: new anchor.BN(usdFees.makerUsd),
+            takerUsd: new anchor.BN(usdFees.takerUsd),
+            maxStaleness: new anchor.BN(usdFees.maxStaleness),
+            maxConfBps: usdFees.maxConfBps,
+            fallbackMakerFee: new anchor.BN(usdFees.fallbackMakerFee),
+            fallbackTakerFee: new anchor.BN(usdFees.fallbackTakerFee),
+            feeDecimals: 0,
+        },
+        {
+            accounts: {
+                admin: signer.publicKey,
+                pdaAccount,
+                feeToken,
+            },
+            signers: [signer],
+        },
+    );
+}
+
+// local price feeds need a program built with the test-oracle feature
+async function initializePriceAccount(
+    program,
+    product,
+    expo,
+    signer,
+) {
+    const priceAccount = await getPriceAccountAddress(program, signer.publicKey, product);
+    await program.rpc.initializePriceAccount(expo, {
+        accounts: {
+            authority: signer.publicKey,
+            product,
+            priceAccount,
+            systemProgram: anchor.web3.SystemProgram.programId,
+        },
+        signers: [signer],
+    });
+    return priceAccount;
+}
+
+async function updatePrice(
+    program,
+    priceAccount,
+    price,
+    conf,
+    signer,
+) {
+    await program.rpc.updatePrice(new anchor.BN(price), new anchor.BN(conf), {
+        accounts: {
+            authority: signer.publicKey,
+            priceAccount,
+        },
+        signers: [signer],
+    });
+}
+
+async function updateReceiveAccount(
+    program,
+    escrow,
+    initReceiveTokenAcc,
+    signer,
+) {
+    await program.rpc.updateReceiveAccount({
+        accounts: {
+            initializer: signer.publicKey,
+            escrowAccount: escrow,
+            initializerReceiveTokenAccount: initReceiveTokenAcc,
+        },
+        signers: [signer],
+    });
+}
+
+async function batchExchange(
+    program,
+    escrows,
+    depositToken,
+    receiveToken,
+    takerDepositToken,
+    takerReceiveToken,
+    takerFeePayAcc,
+    feeToken,
+    maxTotalSpend,
+    minTotalReceive,
+    signer,
+) {
+    const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
+        [program.programId.toBuffer(), feeToken.toBuffer(), Buffer.from(PDA_SEED)], program.programId);
+    const pdaData = await utils.getPdaAccount(program, pdaAccount);
+    if(pdaData == null){
+        return 'pda is not initialized';
+    }
+
+    const remainingAccounts = [];
+    for(let i = 0; i < escrows.length; i++){
+        const escrowData = await utils.getEscrowAccount(program, escrows[i]);
+        if(escrowData == null){
+            return 'no exist escrow';
+        }
+        const initializer = escrowData.initializerKey;
+        [
+            escrows[i],
+            initializer,
+            await getOpenOrdersAddress(program, initializer),
+            escrowData.vaultAccount,
+            escrowData.vaultAuthority,
+            escrowData.initializerReceiveTokenAccount,
+            await feeCollector(pdaData, escrowData.feeToken, escrowData.feeCollectTokenAccount),
+            await getClaimAddress(program, initializer, receiveToken),
+        ].forEach((pubkey, j) => remainingAccounts.push({ pubkey, isWritable: j != 4, isSigner: false }));
+    }
+
+    await program.rpc.batchExchange(
+        new anchor.BN(maxTotalSpend),
+        new anchor.BN(minTotalReceive),
+        {
+            accounts: {
+                taker: signer.publicKey,
+                pdaAccount,
+                depositToken,
+                receiveToken,
+                market: await getMarketAddress(program, depositToken, receiveToken),
+                priceHistory: await getPriceHistoryAddress(program, depositToken, receiveToken),
+                takerDepositTokenAccount: takerDepositToken,
+                takerReceiveTokenAccount: takerReceiveToken,
+                takerFeePayingTokenAccount: takerFeePayAcc,
+                takerStake: await getStakeAddress(program, signer.publicKey, feeToken),
+                priceFeed: priceFeed(pdaData, pdaAccount),
+                vaultFeeAccount: pdaData.vaultFeeAccount,
+                vaultFeeAuthority: pdaData.vaultFeeAuthority,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+                rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+            },
+            remainingAccounts,
+            signers: [signer],
+        },
+    );
+    return "ok";
+}
+
+async function batchCancel(
+    program,
+    escrows,
+    feeToken,
+    signer,
+) {
+    const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
+        [program.programId.toBuffer(), feeToken.toBuffer(), Buffer.from(PDA_SEED)], program.programId);
+    const pdaData = await utils.getPdaAccount(program, pdaAccount);
+    if(pdaData == null){
+        return 'pda is not initialized';
+    }
+
+    const remainingAccounts = [];
+    for(let i = 0; i < escrows.length; i++){
+        const escrowData = await utils.getEscrowAccount(program, escrows[i]);
+        if(escrowData == null){
+            return 'no exist escrow';
+        }
+        [
+            escrows[i],
+            escrowData.vaultAccount,
+            escrowData.vaultAuthority,
+            escrowData.refundTokenAccount,
+            escrowData.initializerFeePayingTokenAccount,
+            await getMarketAddress(program, escrowData.depositToken, escrowData.receiveToken),
+        ].forEach((pubkey, j) => remainingAccounts.push({ pubkey, isWritable: j != 2, isSigner: false }));
+    }
+
+    await program.rpc.batchCancel(
+        {
+            accounts: {
+                initializer: signer.publicKey,
+                pdaAccount,
+                openOrders: await getOpenOrdersAddress(program, signer.publicKey),
+                vaultFeeAccount: pdaData.vaultFeeAccount,
+                vaultFeeAuthority: pdaData.vaultFeeAuthority,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            },
+            remainingAccounts,
+            signers: [signer],
+        },
+    );
+    return "ok";
+}
+
+async function ringTrade(
+    program,
+    escrows,
+    feeToken,
+    signer,
+) {
+    const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
+        [program.programId.toBuffer(), feeToken.toBuffer(), Buffer.from(PDA_SEED)], program.programId);
+    const pdaData = await utils.getPdaAccount(program, pdaAccount);
+    if(pdaData == null){
+        return 'pda is not initialized';
+    }
+
+    const remainingAccounts = [];
+    for(let i = 0; i < escrows.length; i++){
+        const escrowData = await utils.getEscrowAccount(program, escrows[i]);
+        if(escrowData == null){
+            return 'no exist escrow';
+        }
+        [
+            escrows[i],
+            escrowData.initializerKey,
+            await getOpenOrdersAddress(program, escrowData.initializerKey),
+            escrowData.vaultAccount,
+            escrowData.vaultAuthority,
+            escrowData.initializerReceiveTokenAccount,
+            escrowData.refundTokenAccount,
+            await feeCollector(pdaData, escrowData.feeToken, escrowData.feeCollectTokenAccount),
+            await getMarketAddress(program, escrowData.depositToken, escrowData.receiveToken),
+            escrowData.receiveToken,
+            await getClaimAddress(program, escrowData.initializerKey, escrowData.receiveToken),
+            // legs in mints fees can't be paid in have no taker fee to collect
+            pdaData.acceptedFeeMints.some((feeMint) => feeMint.mint.equals(escrowData.depositToken))
+                || escrowData.depositToken.equals(feeToken)
+                ? await feeCollector(pdaData, escrowData.depositToken, escrowData.feeCollectTokenAccount)
+                : escrowData.feeCollectTokenAccount,
+            await getPriceHistoryAddress(program, escrowData.depositToken, escrowData.receiveToken),
+        ].forEach((pubkey, j) => remainingAccounts.push({ pubkey, isWritable: j != 4 && j != 9, isSigner: false }));
+    }
+
+    await program.rpc.ringTrade(
+        {
+            accounts: {
+                cranker: signer.publicKey,
+                pdaAccount,
+                priceFeed: priceFeed(pdaData, pdaAccount),
+                vaultFeeAccount: pdaData.vaultFeeAccount,
+                vaultFeeAuthority: pdaData.vaultFeeAuthority,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+                rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+            },
+            remainingAccounts,
+            signers: [signer],
+        },
+    );
+    return "ok";
+}
+
+async function bumpOrderEpoch(
+    program,
+    signer,
+) {
+    await program.rpc.bumpOrderEpoch(
+        {
+            accounts: {
+                owner: signer.publicKey,
+                openOrders: await getOpenOrdersAddress(program, signer.publicKey),
+            },
+            signers: [signer],
+        },
+    );
+}
+
+const ORDER_BOOK_SEED = "order-book";
+const BOOK_CAPACITY = 32;
+const ORDER_BOOK_SIZE = 8 + 32 * 5 + 8 * 3 + 2 * BOOK_CAPACITY * 160;
+
+async function initializeOrderBook(
+    program,
+    feeToken,
+    baseMint,
+    quoteMint,
+    feeCollectTokenAccount,
+    signer,
+) {
+    const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
+        [program.programId.toBuffer(), feeToken.toBuffer(), Buffer.from(PDA_SEED)], program.programId);
+    const market = await getMarketAddress(program, baseMint, quoteMint);
+    const orderBook = anchor.web3.Keypair.generate();
+    const [bookAuthority] = await anchor.web3.PublicKey.findProgramAddress(
+        [orderBook.publicKey.toBuffer(), Buffer.from(ORDER_BOOK_SEED)], program.programId);
+    const [baseVault] = await anchor.web3.PublicKey.findProgramAddress(
+        [orderBook.publicKey.toBuffer(), baseMint.toBuffer()], program.programId);
+    const [quoteVault] = await anchor.web3.PublicKey.findProgramAddress(
+        [orderBook.publicKey.toBuffer(), quoteMint.toBuffer()], program.programId);
+
+    await program.rpc.initializeOrderBook(
+        {
+            accounts: {
+                admin: signer.publicKey,
+                programConfig: await getProgramConfigAddress(program),
+                market,
+                pdaAccount,
+                orderBook: orderBook.publicKey,
+                baseMint,
+                quoteMint,
+                bookAuthority,
+                baseVault,
+                quoteVault,
+                feeCollectTokenAccount,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+                rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+            },
+            instructions: [
+                anchor.web3.SystemProgram.createAccount({
+                    fromPubkey: signer.publicKey,
+                    newAccountPubkey: orderBook.publicKey,
+                    space: ORDER_BOOK_SIZE,
+                    lamports: await program.provider.connection.getMinimumBalanceForRentExemption(ORDER_BOOK_SIZE),
+                    programId: program.programId,
+                }),
+            ],
+            signers: [signer, orderBook],
+        },
+    );
+    return orderBook.publicKey;
+}
+
+async function updateBookFees(
+    program,
+    feeToken,
+    bookMakerFee,
+    bookTakerFee,
+    signer,
+) {
+    const [pdaAccount] = await anchor.web3.PublicKey.findProgramAddress(
+        [program.programId.toBuffer(), feeToken.toBuffer(), Buffer.from(PDA_SEED)], program.programId);
+    await program.rpc.updateBookFees(new anchor.BN(bookMakerFee), new anchor.BN(bookTakerFee), {
+        accounts: {
+            admin: signer.publicKey,
+            pdaAccount,
+        },
+        signers: [signer],
+    });
+}
+
+async function placeOrder(
+    program,
+    market,
+    side,
+    price,
+    quantity,
+    ownerBaseTokenAccount,
+    ownerQuoteTokenAccount,
+    ownerFeePayingTokenAccount,
+    makerReceiveAccounts,
+    signer,
+) {
+    const marketData = await program.account.market.fetch(market);
+    const bookData = await program.account.orderBook.fetch(marketData.orderBook);
+    const pdaData = await utils.getPdaAccount(program, bookData.feeConfig);
+    const [bookAuthority] = await anchor.web3.PublicKey.findProgramAddress(
+        [marketData.orderBook.toBuffer(), Buffer.from(ORDER_BOOK_SEED)], program.programId);
+
+    // each maker's receive account is followed by their claim account for
+    // the mint they receive, found through their resting order. Makers get
+    // the mint this side deposits.
+    const [resting, ownSide, mint] = side == 'bid'
+        ? [bookData.asks, bookData.bids, marketData.quoteMint]
+        : [bookData.bids, bookData.asks, marketData.baseMint];
+    const accounts = [];
+    for(const receiveAccount of makerReceiveAccounts){
+        const order = resting.find((order) => order.receiveAccount.equals(receiveAccount));
+        const owner = order ? order.owner : receiveAccount;
+        accounts.push(receiveAccount, await getClaimAddress(program, owner, mint));
+    }
+    // a full side evicts its worst order, whose deposit and maker fee go back
+    const count = (side == 'bid' ? bookData.bidCount : bookData.askCount).toNumber();
+    if(count >= BOOK_CAPACITY){
+        const worst = ownSide[count - 1];
+        accounts.push(
+            worst.refundAccount,
+            await getClaimAddress(program, worst.owner, mint),
+            worst.feeAccount,
+            await getClaimAddress(program, worst.owner, pdaData.feeToken),
+        );
+    }
+
+    await program.rpc.placeOrder(
+        side == 'bid' ? { bid: {} } : { ask: {} },
+        new anchor.BN(price),
+        new anchor.BN(quantity),
+        {
+            accounts: {
+                owner: signer.publicKey,
+                market,
+                orderBook: marketData.orderBook,
+                pdaAccount: bookData.feeConfig,
+                baseVault: bookData.baseVault,
+                quoteVault: bookData.quoteVault,
+                bookAuthority,
+                ownerBaseTokenAccount,
+                ownerQuoteTokenAccount,
+                ownerFeePayingTokenAccount,
+                priceHistory: await getPriceHistoryAddress(program, marketData.baseMint, marketData.quoteMint),
+                baseMint: marketData.baseMint,
+                quoteMint: marketData.quoteMint,
+                feeToken: pdaData.feeToken,
+                vaultFeeAccount: pdaData.vaultFeeAccount,
+                vaultFeeAuthority: pdaData.vaultFeeAuthority,
+                feeCollectTokenAccount: await feeCollector(pdaData, pdaData.feeToken, bookData.feeCollectTokenAccount),
+                tokenProgram: TOKEN_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+                rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+            },
+            remainingAccounts: accounts.map((pubkey) => ({ pubkey, isWritable: true, isSigner: false })),
+            signers: [signer],
+        },
+    );
+}
+
+async function cancelOrder(
+    program,
+    market,
+    side,
+    seq,
+    refundTokenAccount,
+    ownerFeePayingTokenAccount,
+    signer,
+) {
+    const marketData = await program.account.market.fetch(market);
+    const bookData = await program.account.orderBook.fetch(marketData.orderBook);
+    const pdaData = await utils.getPdaAccount(program, bookData.feeConfig);
+    const [bookAuthority] = await anchor.web3.PublicKey.findProgramAddress(
+        [marketData.orderBook.toBuffer(), Buffer.from(ORDER_BOOK_SEED)], program.programId);
+
+    await program.rpc.cancelOrder(
+        side == 'bid' ? { bid: {} } : { ask: {} },
+        new anchor.BN(seq),
+        {
+            accounts: {
+                owner: signer.publicKey,
+                market,
+                orderBook: marketData.orderBook,
+                pdaAccount: bookData.feeConfig,
+                baseVault: bookData.baseVault,
+                quoteVault: bookData.quoteVault,
+                bookAuthority,
+                refundTokenAccount,
+                ownerFeePayingTokenAccount,
+                vaultFeeAccount: pdaData.vaultFeeAccount,
+                vaultFeeAuthority: pdaData.vaultFeeAuthority,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            },
+            signers: [signer],
+        },
+    );
+}
+
+// fails unless `promise` rejects with the program error `name`, or the
+// anchor error `code` for constraint failures. Errors raised under a CPI
+// aren't in the caller's IDL, so those are matched in the raw message.
+async function assertProgramError(program, promise, name) {
+    const code = typeof name == 'number' ? name : program.idl.errors.find((error) => error.name == name).code;
+    await assert.rejects(promise, (err) => {
+        if(err.code == null){
+            const custom = `custom program error: 0x${code.toString(16)}`;
+            assert(String(err.message).includes(custom), `expected ${name}, got ${err}`);
+            return true;
+        }
+        assert.strictEqual(err.code, code, `expected ${name}, got ${err.code} ${err.msg || err}`);
+        return true;
+    });
+}
+
+// a maker depositing mint A for mint B and a taker on the other side, both
+// funded, with fees in mint C under a fresh fee config run by feeAdmin
+async function createFixture(program, amounts = {}) {
+    const connection = program.provider.connection;
+    const mintAuthority = anchor.web3.Keypair.generate();
+    const maker = anchor.web3.Keypair.generate();
+    const taker = anchor.web3.Keypair.generate();
+    const feeAdmin = anchor.web3.Keypair.generate();
+    for(const wallet of [maker, taker, feeAdmin]){
+        await utils.createWallet(connection, wallet.publicKey, 1000_000_000);
+    }
+    const createMint = () => splToken.Token.createMint(
+        connection, maker, mintAuthority.publicKey, null, 6, TOKEN_PROGRAM_ID);
+    const mintA = await createMint();
+    const mintB = await createMint();
+    const mintC = await createMint();
+    const funded = async (mint, owner, amount) => {
+        const account = await mint.createAccount(owner.publicKey);
+        if(amount > 0){
+            await mint.mintTo(account, mintAuthority.publicKey, [mintAuthority], amount);
+        }
+        return account;
+    };
+
+    const f = {
+        program,
+        mintAuthority,
+        maker,
+        taker,
+        feeAdmin,
+        mintA,
+        mintB,
+        mintC,
+        makerDeposit: await funded(mintA, maker, amounts.maker || 100_000_000),
+        makerReceive: await funded(mintB, maker, 0),
+        makerFeePay: await funded(mintC, maker, amounts.fees || 10_000_000),
+        takerDeposit: await funded(mintB, taker, amounts.taker || 100_000_000),
+        takerReceive: await funded(mintA, taker, 0),
+        takerFeePay: await funded(mintC, taker, amounts.fees || 10_000_000),
+        feeCollect: await funded(mintC, feeAdmin, 0),
+    };
+    await initializePda(program, mintC.publicKey, feeAdmin);
+
+    f.funded = funded;
+    f.balance = (account) => utils.getTokenAccountBalance(connection, account);
+    f.escrow = async () => (await anchor.web3.PublicKey.findProgramAddress(
+        [maker.publicKey.toBuffer(), mintA.publicKey.toBuffer(), mintB.publicKey.toBuffer()], program.programId))[0];
+    f.escrowData = async () => utils.getEscrowAccount(program, await f.escrow());
+    f.make = (initializerAmount, takerAmount, feeInitializer, feeTaker, options = {}) => initialize(
+        program,
+        initializerAmount,
+        takerAmount,
+        mintA.publicKey,
+        f.makerDeposit,
+        mintB.publicKey,
+        f.makerReceive,
+        mintC.publicKey,
+        f.feeCollect,
+        feeInitializer,
+        feeTaker,
+        f.makerFeePay,
+        maker,
+        options,
+    );
+    f.takeArgs = (witness = {}, referrer = null, signer = taker) => exchangeArgs(
+        program,
+        maker.publicKey,
+        mintA.publicKey,
+        mintB.publicKey,
+        f.takerDeposit,
+        f.takerReceive,
+        f.takerFeePay,
+        mintC.publicKey,
+        signer,
+        referrer,
+        witness,
+    );
+    f.take = async (witness = {}, referrer = null) => {
+        await program.rpc.exchange(...await f.takeArgs(witness, referrer));
+    };
+    f.cancel = (signer = maker) => cancel(
+        program, mintA.publicKey, mintB.publicKey, mintC.publicKey, signer, maker.publicKey);
+    return f;
+}
+
 module.exports = {
+    assertProgramError,
+    createFixture,
+    getOpenOrdersAddress,
+    getOpenOrders,
+    initializeOpenOrders,
+    getMarketAddress,
+    getPriceHistoryAddress,
+    getPriceHistory,
+    twap,
+    initializePriceHistory,
+    getProgramConfigAddress,
+    initializeProgramConfig,
+    updateProgramConfig,
+    initializeMarket,
+    updateMarket,
+    marketAdmin,
+    initializeOrderBook,
+    updateBookFees,
+    placeOrder,
+    cancelOrder,
     initializePda,
     initialize,
     cancel,
+    closeEscrow,
     exchange,
+    exchangeArgs,
+    flashExchange,
+    getClaimAddress,
+    claim,
+    updateReceiveAccount,
+    getReferrerStatsAddress,
+    initializeReferrer,
+    updateReferralShare,
+    getUserStatsAddress,
+    updateFeeTiers,
+    initializeUserStats,
+    getStakeAddress,
+    initializeStakeVault,
+    stake,
+    unstake,
+    withdrawStake,
+    initializeFeeDistribution,
+    updateFeeDistribution,
+    distributeFees,
+    openRevenueVault,
+    addFeeMint,
+    updateFeeMint,
+    updateDeductedFees,
+    getPriceAccountAddress,
+    initializePriceAccount,
+    updatePrice,
+    updateUsdFees,
+    batchExchange,
+    batchCancel,
+    bumpOrderEpoch,
+    ringTrade,
 }
 